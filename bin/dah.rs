@@ -1,6 +1,10 @@
-use clap::{ArgAction, Parser};
-use git2::Repository;
-use git_toolbox::app::dah::Application;
+use std::time::Duration;
+
+use clap::{Parser, ValueEnum};
+use git2::{Config as GitConfig, ErrorCode, Repository};
+use git_toolbox::app::dah::{
+    Application, ChangeKind, ColorMode, DirtyPolicy, FileConfig, LogFormat, Trailer,
+};
 
 #[derive(Parser)]
 #[command(
@@ -14,33 +18,327 @@ struct Cli {
     // ask: bool,
     #[arg(
         long,
-        help = "Increase number of commits to scan in history",
-        default_value = "100"
+        help = "Increase number of commits to scan in history; 100 unless dah.limit is set"
     )]
-    limit: usize,
+    limit: Option<usize>,
     #[arg(
-        long = "cooperative",
+        long,
         visible_alias = "no-force",
-        help = "Extra safety for team programming; meaning always rebase HEAD onto the remote branch and don't push with force",
-        action = ArgAction::SetFalse,
+        overrides_with = "force_push",
+        help = "Extra safety for team programming; meaning always rebase HEAD onto the remote branch and don't push with force"
+    )]
+    cooperative: bool,
+    #[arg(
+        long,
+        overrides_with = "cooperative",
+        help = "Push with force even if dah.forcepush is false"
+    )]
+    force_push: bool,
+    #[arg(
+        long,
+        overrides_with = "fetch",
+        help = "Do not invoke git-fetch automatically"
+    )]
+    no_fetch: bool,
+    #[arg(
+        long,
+        overrides_with = "no_fetch",
+        help = "Invoke git-fetch even if dah.fetch is false"
+    )]
+    fetch: bool,
+    #[arg(
+        long,
+        help = "Fetch all remotes, instead of the one HEAD tracks and --push-remote only"
+    )]
+    fetch_all: bool,
+    #[arg(
+        long,
+        overrides_with = "prune",
+        help = "Keep remote tracking branches deleted on the remote when fetching"
+    )]
+    no_prune: bool,
+    #[arg(
+        long,
+        overrides_with = "no_prune",
+        help = "Prune remote tracking branches even if dah.prune is false"
+    )]
+    prune: bool,
+    #[arg(
+        long = "no-verify",
+        visible_alias = "commit-hook-bypass",
+        overrides_with = "verify",
+        help = "Bypass pre-commit and pre-push hooks by passing --no-verify to git-commit and git-push"
+    )]
+    no_verify: bool,
+    #[arg(
+        long,
+        overrides_with = "no_verify",
+        help = "Run pre-commit and pre-push hooks even if dah.noverify is true"
+    )]
+    verify: bool,
+    #[arg(
+        long,
+        help = "Rebase with libgit2 instead of git pull --rebase, stopping at conflicts to resolve"
+    )]
+    builtin_rebase: bool,
+    #[arg(
+        long,
+        visible_alias = "tags",
+        help = "Push annotated tags reachable from the pushed commits too, by passing --follow-tags to git-push"
+    )]
+    follow_tags: bool,
+    #[arg(
+        long,
+        help = "Create branches with git checkout -b instead of git switch -c, for git older than 2.23"
+    )]
+    legacy_checkout: bool,
+    #[arg(
+        long,
+        help = "Capture output of git commands, showing it only when they fail, instead of passing it through"
+    )]
+    quiet_git: bool,
+    #[arg(
+        long,
+        help = "Warn if the remote tracking branch doesn't point to HEAD after push, like when git pushed nothing"
+    )]
+    verify_push: bool,
+    #[arg(
+        long,
+        overrides_with = "no_auto_message",
+        help = "Commit with a message generated from staged paths, instead of opening the editor"
+    )]
+    auto_message: bool,
+    #[arg(
+        long,
+        overrides_with = "auto_message",
+        help = "Open the editor for commit message even if dah.automessage is true"
+    )]
+    no_auto_message: bool,
+    #[arg(
+        long,
+        help = "Format of log output",
+        value_enum,
+        default_value_t = LogFormat::Text
+    )]
+    log_format: LogFormat,
+    #[arg(
+        long,
+        help = "Colorize log output",
+        value_enum,
+        default_value_t = ColorMode::Auto
+    )]
+    color: ColorMode,
+    #[arg(long, help = "Same as --color never", conflicts_with = "color")]
+    no_color: bool,
+    #[arg(
+        long,
+        help = "Rebase instead of force push if the remote branch has commits authored by others"
+    )]
+    protect_current_upstream: bool,
+    #[arg(
+        long,
+        help = "Push local commits on the default or protected branch to its remote tracking branch, instead of renaming the branch, if they can be fast-forwarded"
+    )]
+    push_protected: bool,
+    #[arg(
+        long,
+        value_name = "REMOTE",
+        help = "Push to the specified remote, while HEAD keeps tracking and being rebased onto its remote tracking branch"
     )]
-    allow_force_push: bool,
+    push_remote: Option<String>,
     #[arg(
-        long = "no-fetch",
-        help = "Do not invoke git-fetch automatically",
-        action = ArgAction::SetFalse,
+        long,
+        help = "Log a table of time taken by each repository query and command at the end, to diagnose slow runs"
+    )]
+    timings: bool,
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 0,
+        help = "Sleep for the milliseconds between actions, to ease the load on slow filesystems or remotes"
     )]
-    fetch_first: bool,
+    interval: u64,
+    #[arg(
+        long,
+        value_name = "BYTES",
+        help = "Warn before committing staged files larger than the bytes"
+    )]
+    warn_large: Option<u64>,
+    #[arg(
+        long,
+        value_name = "CMD",
+        help = "Run the shell command, like a formatter, before staging changes. Staging is aborted if it fails"
+    )]
+    pre_stage: Option<String>,
+    #[arg(
+        long,
+        value_name = "KEY=VALUE",
+        help = "Add a trailer like Refs=PROJ-123 to commit message, by passing --trailer to git-commit. Can be repeated"
+    )]
+    trailer: Vec<Trailer>,
+    #[arg(
+        long,
+        value_name = "KIND",
+        value_enum,
+        help = "Don't count the kind of changes in work tree or index as ones to stage or commit. Can be repeated"
+    )]
+    ignore_change: Vec<ChangeKind>,
+    #[arg(
+        long,
+        help = "Refuse to run inside a submodule, to avoid pushing to the submodule's remote by mistake"
+    )]
+    no_submodule: bool,
+}
+
+/// Defaults for options, read from `dah` section of git config and `.dah.toml`.
+#[derive(Debug, Default, PartialEq)]
+struct Config {
+    limit: Option<usize>,
+    force_push: Option<bool>,
+    fetch: Option<bool>,
+    prune: Option<bool>,
+    no_verify: Option<bool>,
+    auto_message: Option<bool>,
+    remote: Option<String>,
+    pre_stage: Option<String>,
+    ignore_change: Option<Vec<ChangeKind>>,
+}
+
+fn not_found_as_none<T>(r: Result<T, git2::Error>) -> Result<Option<T>, git2::Error> {
+    match r {
+        Ok(v) => Ok(Some(v)),
+        Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Boolean option given by a pair of flags, falling back to config if neither is given.
+fn flag_or_config(on: bool, off: bool, config: Option<bool>) -> bool {
+    match (on, off) {
+        (true, _) => true,
+        (_, true) => false,
+        _ => config.unwrap_or(false),
+    }
+}
+
+impl Config {
+    fn load(config: &GitConfig) -> Result<Config, Box<dyn std::error::Error>> {
+        let limit = match not_found_as_none(config.get_i64("dah.limit"))? {
+            Some(n) => Some(usize::try_from(n)?),
+            None => None,
+        };
+        Ok(Config {
+            limit,
+            force_push: not_found_as_none(config.get_bool("dah.forcepush"))?,
+            fetch: not_found_as_none(config.get_bool("dah.fetch"))?,
+            prune: not_found_as_none(config.get_bool("dah.prune"))?,
+            no_verify: not_found_as_none(config.get_bool("dah.noverify"))?,
+            auto_message: not_found_as_none(config.get_bool("dah.automessage"))?,
+            remote: not_found_as_none(config.get_string("dah.remote"))?,
+            pre_stage: not_found_as_none(config.get_string("dah.prestage"))?,
+            ignore_change: match not_found_as_none(config.get_string("dah.ignorechange"))? {
+                Some(kinds) => Some(
+                    kinds
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|k| !k.is_empty())
+                        .map(|k| ChangeKind::from_str(k, true))
+                        .collect::<Result<_, _>>()?,
+                ),
+                None => None,
+            },
+        })
+    }
+
+    /// Fill options by `.dah.toml`, which takes precedence over git config.
+    fn overridden_by(self, file: &FileConfig) -> Self {
+        Self {
+            limit: file.limit.or(self.limit),
+            // the file may only turn force push off, never on against the user's config.
+            force_push: match file.force_push {
+                Some(false) => Some(false),
+                _ => self.force_push,
+            },
+            fetch: file.fetch.or(self.fetch),
+            prune: file.prune.or(self.prune),
+            // not from `.dah.toml`, as anyone can commit it; see `FileConfig`.
+            no_verify: self.no_verify,
+            auto_message: file.auto_message.or(self.auto_message),
+            remote: self.remote,
+            pre_stage: self.pre_stage,
+            ignore_change: self.ignore_change,
+        }
+    }
 }
 
 impl Cli {
+    /// Fill options not given in command line by config.
+    fn apply_config(self, config: Config) -> Self {
+        Self {
+            limit: self.limit.or(config.limit),
+            cooperative: flag_or_config(
+                self.cooperative,
+                self.force_push,
+                config.force_push.map(|b| !b),
+            ),
+            no_fetch: flag_or_config(self.no_fetch, self.fetch, config.fetch.map(|b| !b)),
+            no_prune: flag_or_config(self.no_prune, self.prune, config.prune.map(|b| !b)),
+            no_verify: flag_or_config(self.no_verify, self.verify, config.no_verify),
+            auto_message: flag_or_config(
+                self.auto_message,
+                self.no_auto_message,
+                config.auto_message,
+            ),
+            push_remote: self.push_remote.or(config.remote),
+            pre_stage: self.pre_stage.or(config.pre_stage),
+            ignore_change: if self.ignore_change.is_empty() {
+                config.ignore_change.unwrap_or_default()
+            } else {
+                self.ignore_change
+            },
+            ..self
+        }
+    }
+
     fn into_app(self) -> Result<Application, Box<dyn std::error::Error>> {
         let repo = Repository::open_from_env()?;
+        let file_config = FileConfig::load(&repo)?;
+        let cli = self.apply_config(Config::load(&repo.config()?)?.overridden_by(&file_config));
         let app = Application::new(repo)
-            .with_step(self.step)
-            .with_limit(self.limit)
-            .with_allow_force_push(self.allow_force_push)
-            .with_fetch_first(self.fetch_first);
+            .with_file_config(file_config)
+            .with_step(cli.step)
+            .with_limit(cli.limit.unwrap_or(100))
+            .with_allow_force_push(!cli.cooperative)
+            .with_fetch_first(!cli.no_fetch)
+            .with_fetch_all(cli.fetch_all)
+            .with_prune(!cli.no_prune)
+            .with_no_verify(cli.no_verify)
+            .with_builtin_rebase(cli.builtin_rebase)
+            .with_follow_tags(cli.follow_tags)
+            .with_legacy_checkout(cli.legacy_checkout)
+            .with_quiet_git(cli.quiet_git)
+            .with_verify_push(cli.verify_push)
+            .with_auto_message(cli.auto_message)
+            .with_log_format(cli.log_format)
+            .with_color(if cli.no_color {
+                ColorMode::Never
+            } else {
+                cli.color
+            })
+            .with_protect_current_upstream(cli.protect_current_upstream)
+            .with_push_to_protected(cli.push_protected)
+            .with_push_remote(cli.push_remote)
+            .with_timings(cli.timings)
+            .with_interval(Duration::from_millis(cli.interval))
+            .with_warn_large(cli.warn_large)
+            .with_pre_stage(cli.pre_stage)
+            .with_trailers(cli.trailer)
+            .with_no_submodule(cli.no_submodule)
+            .with_dirty_policy(
+                cli.ignore_change
+                    .into_iter()
+                    .fold(DirtyPolicy::default(), DirtyPolicy::excluding),
+            );
         Ok(app)
     }
 }
@@ -48,3 +346,165 @@ impl Cli {
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     Cli::parse().into_app().and_then(|cmd| cmd.run())
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use git2::{ConfigLevel, Repository};
+    use tempfile::TempDir;
+
+    use super::{ChangeKind, Cli, Config, FileConfig};
+
+    #[test]
+    fn config_reads_dah_section() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo = Repository::init_bare(tmpdir.path())?;
+        assert_eq!(Config::load(&repo.config()?)?, Config::default());
+
+        {
+            let mut config = repo.config()?.open_level(ConfigLevel::Local)?;
+            config.set_i64("dah.limit", 10)?;
+            config.set_bool("dah.forcepush", false)?;
+            config.set_bool("dah.fetch", false)?;
+            config.set_bool("dah.prune", false)?;
+            config.set_bool("dah.noverify", true)?;
+            config.set_bool("dah.automessage", true)?;
+            config.set_str("dah.remote", "myfork")?;
+            config.set_str("dah.prestage", "cargo fmt")?;
+            config.set_str("dah.ignorechange", "typechange, renamed")?;
+        }
+        assert_eq!(
+            Config::load(&repo.config()?)?,
+            Config {
+                limit: Some(10),
+                force_push: Some(false),
+                fetch: Some(false),
+                prune: Some(false),
+                no_verify: Some(true),
+                auto_message: Some(true),
+                remote: Some("myfork".to_owned()),
+                pre_stage: Some("cargo fmt".to_owned()),
+                ignore_change: Some(vec![ChangeKind::Typechange, ChangeKind::Renamed]),
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn dah_toml_overrides_git_config() {
+        let config = Config {
+            limit: Some(10),
+            fetch: Some(false),
+            remote: Some("myfork".to_owned()),
+            ..Default::default()
+        };
+        let file = FileConfig {
+            limit: Some(20),
+            prune: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.overridden_by(&file),
+            Config {
+                limit: Some(20),
+                fetch: Some(false),
+                prune: Some(false),
+                remote: Some("myfork".to_owned()),
+                ..Default::default()
+            }
+        );
+
+        // force push can be turned off, but not on
+        let config = |force_push| Config {
+            force_push,
+            ..Default::default()
+        };
+        for (user, file, want) in [
+            (None, Some(false), Some(false)),
+            (Some(true), Some(false), Some(false)),
+            (None, Some(true), None),
+            (Some(false), Some(true), Some(false)),
+        ] {
+            let file = FileConfig {
+                force_push: file,
+                ..Default::default()
+            };
+            assert_eq!(config(user).overridden_by(&file), config(want));
+        }
+    }
+
+    #[test]
+    fn cli_overrides_config() {
+        let config = || Config {
+            limit: Some(10),
+            force_push: Some(false),
+            fetch: Some(true),
+            remote: Some("myfork".to_owned()),
+            ignore_change: Some(vec![ChangeKind::Typechange]),
+            ..Default::default()
+        };
+
+        let cli = Cli::parse_from(["git-dah"]).apply_config(config());
+        assert_eq!(cli.limit, Some(10));
+        assert!(cli.cooperative);
+        assert!(!cli.no_fetch);
+        assert!(!cli.no_verify);
+        assert_eq!(cli.push_remote.as_deref(), Some("myfork"));
+        assert_eq!(cli.ignore_change, vec![ChangeKind::Typechange]);
+
+        let cli = Cli::parse_from([
+            "git-dah",
+            "--limit",
+            "5",
+            "--no-fetch",
+            "--push-remote",
+            "other",
+            "--ignore-change",
+            "new",
+        ])
+        .apply_config(config());
+        assert_eq!(cli.ignore_change, vec![ChangeKind::New]);
+        assert_eq!(cli.limit, Some(5));
+        assert!(cli.no_fetch);
+        assert_eq!(cli.push_remote.as_deref(), Some("other"));
+    }
+
+    #[test]
+    fn cli_turns_off_config() {
+        let config = || Config {
+            force_push: Some(false),
+            fetch: Some(false),
+            prune: Some(false),
+            no_verify: Some(true),
+            auto_message: Some(true),
+            ..Default::default()
+        };
+
+        let cli = Cli::parse_from(["git-dah"]).apply_config(config());
+        assert!(cli.cooperative);
+        assert!(cli.no_fetch);
+        assert!(cli.no_prune);
+        assert!(cli.no_verify);
+        assert!(cli.auto_message);
+
+        let cli = Cli::parse_from([
+            "git-dah",
+            "--force-push",
+            "--fetch",
+            "--prune",
+            "--verify",
+            "--no-auto-message",
+        ])
+        .apply_config(config());
+        assert!(!cli.cooperative);
+        assert!(!cli.no_fetch);
+        assert!(!cli.no_prune);
+        assert!(!cli.no_verify);
+        assert!(!cli.auto_message);
+
+        // the last one wins
+        let cli = Cli::parse_from(["git-dah", "--verify", "--no-verify"]).apply_config(config());
+        assert!(cli.no_verify);
+    }
+}
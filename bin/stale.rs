@@ -1,9 +1,23 @@
-use chrono::{DateTime, Local};
-use clap::{arg, Parser};
-use git2::{Branch, BranchType, PushOptions, RemoteCallbacks, Repository};
-use git_toolbox::{git::GitTime, reltime::Reltime};
+use chrono::{DateTime, FixedOffset, Local};
+use clap::{Parser, ValueEnum};
+use git2::{Branch, BranchType, Config, ErrorCode, Oid, PushOptions, Repository};
+use git_toolbox::{
+    git::{escape_control, fnmatch, CredentialCallback, GitTime, HeadRef},
+    json,
+    reltime::Reltime,
+};
 use log::{error, info, warn};
-use std::{collections::HashMap, error::Error, process::exit};
+use regex::RegexSet;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    error::Error,
+    ffi::CString,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    process::exit,
+};
 
 #[derive(Parser)]
 #[command(
@@ -17,29 +31,267 @@ struct Cli {
         help = "Combined with --delete, perform deletion on remote repository instead"
     )]
     push: bool,
+    #[arg(
+        long,
+        help = "Combined with --delete --push, delete local branches too, after their deletion is pushed",
+        requires = "push"
+    )]
+    local: bool,
+    #[arg(
+        long,
+        value_name = "REMOTE",
+        help = "Combined with --delete --push, delete only branches tracking the remote, skipping others",
+        requires = "push"
+    )]
+    remote: Option<String>,
+    #[arg(
+        long,
+        help = "Combined with --delete, delete the default branch (init.defaultbranch), the branch HEAD tracks and branches with unpushed commits too, if selected",
+        requires = "delete"
+    )]
+    force: bool,
+    #[arg(
+        long,
+        value_name = "N",
+        help = "Combined with --delete, abort without deleting anything if more than N branches are selected, unless --force",
+        requires = "delete"
+    )]
+    max_delete: Option<usize>,
+    #[arg(
+        long,
+        help = "Combined with --delete, skip branches whose tip is pointed to by a tag [default]",
+        overrides_with = "no_protect_tagged"
+    )]
+    protect_tagged: bool,
+    #[arg(
+        long,
+        help = "Combined with --delete, delete branches whose tip is pointed to by a tag too",
+        overrides_with = "protect_tagged"
+    )]
+    no_protect_tagged: bool,
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        help = "Protect branches having a tagged commit within N first-parent commits from the tip, too"
+    )]
+    protect_tagged_depth: usize,
     #[arg(long,
         help = "Select local branch with commit times older than the specified relative time",
         value_parser = parse_reltime)]
     since: Option<Reltime>,
-    #[arg(help = "Select branches with specified prefixes, or select all if unset")]
+    #[arg(long,
+        help = "Select local branch with commit times newer than the specified relative time, i.e. recently touched ones. Cannot be combined with --delete",
+        value_parser = parse_reltime,
+        conflicts_with = "delete")]
+    newer_than: Option<Reltime>,
+    #[arg(
+        long,
+        value_name = "REF",
+        help = "Select local branch whose tip is an ancestor of the specified commit-ish, like a released tag"
+    )]
+    ancestor_of: Option<String>,
+    #[arg(
+        long,
+        help = "Select local branch whose upstream was deleted from the remote, like git-branch shows [gone]. Run git fetch --prune before"
+    )]
+    upstream_gone: bool,
+    #[arg(
+        long,
+        value_name = "PREFIX",
+        help = "Exclude branches with specified prefix, even if selected. Can be repeated"
+    )]
+    exclude: Vec<String>,
+    #[arg(long, help = "Interpret --exclude as regular expressions")]
+    regex: bool,
+    #[arg(long, value_enum, default_value_t = ListFormat::NameOnly, help = "Format of listed branches")]
+    format: ListFormat,
+    #[arg(
+        long,
+        help = "List branches in a table with their upstream, ahead and behind counts and last commit date, including ones with upstream",
+        conflicts_with = "delete"
+    )]
+    status: bool,
+    #[arg(
+        long,
+        help = "Print a JSON object summarizing scanned, matched, deleted and failed branches at the end"
+    )]
+    json_summary: bool,
+    #[arg(
+        help = "Select branches with specified prefixes or globs like 'release/*.0', or select all if unset. Defaults to stale.prefix config"
+    )]
     branches: Vec<String>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum ListFormat {
+    NameOnly,
+    Full,
+}
+
+enum Excludes {
+    Prefix(Vec<String>),
+    Regex(RegexSet),
+}
+
+impl Excludes {
+    fn is_match(&self, branch_name: &str) -> bool {
+        match self {
+            Excludes::Prefix(prefixes) => prefixes
+                .iter()
+                .any(|prefix| branch_name.starts_with(prefix)),
+            Excludes::Regex(set) => set.is_match(branch_name),
+        }
+    }
+}
+
 fn parse_reltime(arg: &str) -> Result<Reltime, String> {
-    Reltime::try_from(arg).map_err(|e| format!("while parsing {} got error: {}", arg, e))
+    // the error names the input and the offending token by itself.
+    Reltime::try_from(arg).map_err(|e| e.to_string())
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("failed to remove {} branch(es): {}", .failures.len(), format_failures(.failures))]
+struct DeletionError {
+    failures: Vec<(String, git2::Error)>,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("{count} branch(es) selected for deletion, more than --max-delete {max}; nothing deleted. Narrow the selection, raise the cap or use --force")]
+struct TooManyBranches {
+    count: usize,
+    max: usize,
+}
+
+fn format_failures(failures: &[(String, git2::Error)]) -> String {
+    failures
+        .iter()
+        .map(|(branch_name, e)| format!("'{}' ({})", branch_name, e.message()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Name of the lock file in the git directory, held while deleting branches.
+const LOCK_FILE: &str = "stale.lock";
+
+#[derive(thiserror::Error, Debug)]
+#[error("another git-stale is deleting branches; remove {0:?} if it is left over")]
+struct LockHeld(PathBuf);
+
+/// Lock file held while deleting branches, so that overlapping runs don't race.
+///
+/// The file is removed when dropped.
+#[derive(Debug)]
+struct Lock {
+    path: PathBuf,
+}
+
+impl Lock {
+    fn acquire(repo: &Repository) -> Result<Lock, Box<dyn Error>> {
+        // lock the common directory, as worktrees share branches.
+        let path = repo.commondir().join(LOCK_FILE);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                let lock = Lock { path };
+                writeln!(file, "{}", std::process::id())?;
+                Ok(lock)
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(LockHeld(path).into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            warn!("failed to remove lock file {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// Report of the whole run, for `--json-summary`.
+#[derive(Debug, Default, PartialEq)]
+struct Summary {
+    operation: &'static str,
+    scanned: usize,
+    matched: Vec<String>,
+    deleted: Vec<String>,
+    failed: Vec<String>,
+}
+
+impl Summary {
+    fn to_json(&self) -> String {
+        format!(
+            r#"{{"operation":"{}","scanned":{},"matched":{},"deleted":{},"failed":{}}}"#,
+            self.operation,
+            self.scanned,
+            json::string_array(&self.matched),
+            json::string_array(&self.deleted),
+            json::string_array(&self.failed),
+        )
+    }
 }
 
 struct Command {
     repo: Repository,
     delete: bool,
     push: bool,
+    local: bool,
+    remote: Option<String>,
     since: Option<DateTime<Local>>,
+    newer_than: Option<DateTime<Local>>,
+    ancestor_of: Option<Oid>,
+    upstream_gone: bool,
+    format: ListFormat,
+    status: bool,
+    json_summary: bool,
     branches: Vec<String>,
+    excludes: Excludes,
+    protected_branches: Vec<String>,
+    /// Branches never deleted without `--force`.
+    guarded_branches: Vec<String>,
+    force: bool,
+    max_delete: Option<usize>,
+    /// Commits pointed to by tags, protected from deletion.
+    tagged_commits: HashSet<Oid>,
+    protect_tagged_depth: usize,
 }
 
 impl Command {
     fn run(&self) -> Result<(), Box<dyn Error>> {
+        let summary = RefCell::new(Summary {
+            operation: match (self.delete, self.push) {
+                (true, true) => "push",
+                (true, false) => "delete",
+                _ => "list",
+            },
+            scanned: self.repo.branches(Some(BranchType::Local))?.count(),
+            ..Default::default()
+        });
+        let result = self.run_with_summary(&summary);
+        if self.json_summary {
+            println!("{}", summary.borrow().to_json());
+        }
+        result
+    }
+
+    fn run_with_summary(&self, summary: &RefCell<Summary>) -> Result<(), Box<dyn Error>> {
+        let _lock = if self.delete {
+            Some(Lock::acquire(&self.repo)?)
+        } else {
+            None
+        };
         if self.delete && self.push {
+            // names recorded in summary by refname, as pushes report refnames.
+            let names = RefCell::new(HashMap::new());
+            let name_of = |refname: &str| {
+                names
+                    .borrow()
+                    .get(refname)
+                    .cloned()
+                    .unwrap_or_else(|| refname.to_owned())
+            };
             let refspecs: HashMap<String, Vec<String>> = HashMap::new();
             let mut refspecs = self.for_each(refspecs, |mut refspecs, branch| {
                 let upstream = branch.upstream()?;
@@ -50,10 +302,26 @@ impl Command {
                     .and_then(|u| u.split('/').next());
                 let branch_name = branch.get().name();
 
+                if let (Some(remote), Some(upstream)) = (&self.remote, upstream) {
+                    if remote != upstream {
+                        info!(
+                            "branch '{}' skipped because it tracks {}, not {}",
+                            display_safe(branch_name.unwrap_or_default()),
+                            upstream,
+                            remote
+                        );
+                        return Ok(refspecs);
+                    }
+                }
+                let formatted = self.format_branch(&branch)?;
+                summary.borrow_mut().matched.push(formatted.clone());
+
                 if let (Some(remote_name), Some(branch_name)) = (upstream, branch_name) {
+                    names.borrow_mut().insert(branch_name.to_owned(), formatted);
                     info!(
                         "branch '{}' will be deleted from {}",
-                        branch_name, remote_name
+                        display_safe(branch_name),
+                        remote_name
                     );
 
                     // refspec has <src>:<dst> format, so leaving <src> empty will delete <dst>.
@@ -67,42 +335,192 @@ impl Command {
 
                 Ok(refspecs)
             })?;
+            self.check_max_delete(refspecs.values().map(Vec::len).sum())?;
+            // refnames deleted from remotes; same as the local ones.
+            let pushed = RefCell::new(Vec::new());
             for (remote_name, refspecs) in refspecs.drain() {
                 let mut remote = self.repo.find_remote(&remote_name)?;
-                let mut callbacks = RemoteCallbacks::new();
+                let mut callbacks =
+                    CredentialCallback::new(self.repo.config()?).into_remote_callbacks();
                 callbacks.push_update_reference(|refname, status| {
+                    let mut summary = summary.borrow_mut();
                     if let Some(error) = status {
-                        warn!("push failed: {}, status = {}", refname, error);
+                        warn!("push failed: {}, status = {}", display_safe(refname), error);
+                        summary.failed.push(name_of(refname));
                     } else {
-                        info!("pushed: {}", refname);
+                        info!("pushed: {}", display_safe(refname));
+                        summary.deleted.push(name_of(refname));
+                        pushed
+                            .borrow_mut()
+                            .push((refname.to_owned(), name_of(refname)));
                     }
                     Ok(())
                 });
                 let mut push_options = PushOptions::new();
                 push_options.remote_callbacks(callbacks);
                 if let Err(e) = remote.push(refspecs.as_slice(), Some(&mut push_options)) {
-                    warn!("failed to remove branches from {}: {}", remote_name, e)
+                    warn!("failed to remove branches from {}: {}", remote_name, e);
+                    summary.borrow_mut().failed.extend(
+                        refspecs
+                            .iter()
+                            .map(|refspec| name_of(refspec.trim_start_matches(':'))),
+                    );
                 }
             }
+            if self.local {
+                self.delete_pushed(pushed.into_inner(), summary)?;
+            }
         } else if self.delete {
-            self.for_each((), |_, mut branch| {
-                if let Some(branch_name) = branch.get().name() {
-                    let branch_name = branch_name.to_owned();
-                    if let Err(e) = branch.delete() {
-                        warn!("failed to remove branch '{}': {}", branch_name, e)
-                    }
+            let refnames = self.for_each(Vec::new(), |mut refnames, branch| {
+                if !self.force && !self.is_pushed(&branch)? {
+                    warn!(
+                        "branch '{}' skipped because it has commits not pushed to any remote. Use --force to delete it anyway.",
+                        display_safe(branch.get().name().unwrap_or_default())
+                    );
+                    return Ok(refnames);
                 }
-                Ok(())
+                if let Some(refname) = branch.get().name() {
+                    refnames.push(refname.to_owned());
+                }
+                Ok(refnames)
             })?;
+            self.check_max_delete(refnames.len())?;
+
+            let mut failures = Vec::new();
+            for refname in refnames {
+                let mut branch = Branch::wrap(self.repo.find_reference(&refname)?);
+                let formatted = self.format_branch(&branch)?;
+                let mut summary = summary.borrow_mut();
+                summary.matched.push(formatted.clone());
+                if let Err(e) = branch.delete() {
+                    warn!(
+                        "failed to remove branch '{}': {}",
+                        display_safe(&refname),
+                        e
+                    );
+                    summary.failed.push(formatted);
+                    failures.push((refname, e));
+                } else {
+                    summary.deleted.push(formatted);
+                }
+            }
+            if !failures.is_empty() {
+                return Err(DeletionError { failures }.into());
+            }
+        } else if self.status {
+            let rows = self.for_each(Vec::new(), |mut rows, branch| {
+                let row = self.status_row(&branch)?;
+                summary
+                    .borrow_mut()
+                    .matched
+                    .push(self.format_branch(&branch)?);
+                rows.push(row);
+                Ok(rows)
+            })?;
+            for line in format_table(STATUS_HEADER, &rows) {
+                self.print_listed(&line);
+            }
         } else {
             self.for_each((), |_, branch| {
-                println!("{}", branch.get().name().unwrap());
+                let formatted = self.format_branch(&branch)?;
+                self.print_listed(&formatted);
+                summary.borrow_mut().matched.push(formatted);
                 Ok(())
             })?;
         }
         Ok(())
     }
 
+    /// Print a line of listing to stdout, or to stderr with `--json-summary`
+    /// so that stdout can be parsed as JSON.
+    fn print_listed(&self, line: &str) {
+        if self.json_summary {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    /// Refuse to delete more branches than `--max-delete` at once, unless `--force`.
+    fn check_max_delete(&self, count: usize) -> Result<(), TooManyBranches> {
+        match self.max_delete {
+            Some(max) if count > max && !self.force => Err(TooManyBranches { count, max }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Delete local branches whose deletion is pushed, for `--local`.
+    ///
+    /// Takes refnames and their names recorded in summary.
+    fn delete_pushed(
+        &self,
+        refnames: Vec<(String, String)>,
+        summary: &RefCell<Summary>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut failures = Vec::new();
+        for (refname, name) in refnames {
+            let result = self
+                .repo
+                .find_reference(&refname)
+                .and_then(|r| Branch::wrap(r).delete());
+            if let Err(e) = result {
+                warn!(
+                    "failed to remove local branch '{}': {}",
+                    display_safe(&refname),
+                    e
+                );
+                summary.borrow_mut().failed.push(name);
+                failures.push((refname, e));
+            } else {
+                info!("deleted: {}", display_safe(&refname));
+            }
+        }
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(DeletionError { failures }.into())
+        }
+    }
+
+    fn format_branch(&self, branch: &Branch) -> Result<String, Box<dyn Error>> {
+        let refname = match branch.get().name() {
+            Some(refname) => HeadRef::new(refname)?,
+            None => return Err("branch name is not valid UTF-8".into()),
+        };
+        match self.format {
+            ListFormat::NameOnly => Ok(refname.branch().unwrap_or(refname.as_str()).to_owned()),
+            ListFormat::Full => Ok(refname.into_string()),
+        }
+    }
+
+    /// Columns of `--status` table for the branch; see `STATUS_HEADER`.
+    fn status_row(&self, branch: &Branch) -> Result<[String; 5], Box<dyn Error>> {
+        let commit = branch.get().peel_to_commit()?;
+        let upstream = match branch.upstream() {
+            Ok(upstream) => Some(upstream),
+            Err(e) if e.code() == ErrorCode::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        let (upstream, ahead, behind) = match upstream {
+            Some(upstream) => {
+                let (ahead, behind) = self
+                    .repo
+                    .graph_ahead_behind(commit.id(), upstream.get().peel_to_commit()?.id())?;
+                let name = String::from_utf8_lossy(upstream.get().shorthand_bytes());
+                (display_safe(&name), ahead.to_string(), behind.to_string())
+            }
+            None => ("-".to_owned(), "-".to_owned(), "-".to_owned()),
+        };
+        let commit_time: DateTime<FixedOffset> = GitTime::from(commit.time()).into();
+        Ok([
+            display_safe(&self.format_branch(branch)?),
+            upstream,
+            ahead,
+            behind,
+            commit_time.to_rfc3339(),
+        ])
+    }
+
     fn for_each<S, F: Fn(S, Branch<'_>) -> Result<S, Box<dyn Error>>>(
         &self,
         init: S,
@@ -115,14 +533,7 @@ impl Command {
                 continue;
             }
 
-            let commit = branch.get().peel_to_commit()?;
-            let commit_time: GitTime = commit.time().into();
-
-            if let Some(s) = self.since {
-                if s > commit_time.into() {
-                    st = f(st, branch)?;
-                }
-            } else if branch.upstream().is_err() {
+            if self.is_stale(&branch)? {
                 st = f(st, branch)?;
             }
         }
@@ -130,6 +541,94 @@ impl Command {
         Ok(st)
     }
 
+    fn is_stale(&self, branch: &Branch) -> Result<bool, Box<dyn Error>> {
+        let commit = branch.get().peel_to_commit()?;
+
+        if let Some(ancestor_of) = self.ancestor_of {
+            let contained = commit.id() == ancestor_of
+                || self.repo.graph_descendant_of(ancestor_of, commit.id())?;
+            if !contained {
+                return Ok(false);
+            }
+        }
+
+        if self.upstream_gone && !self.is_upstream_gone(branch)? {
+            return Ok(false);
+        }
+
+        let commit_time: DateTime<FixedOffset> = GitTime::from(commit.time()).into();
+        if let Some(n) = self.newer_than {
+            if commit_time <= n {
+                return Ok(false);
+            }
+        }
+
+        if let Some(s) = self.since {
+            Ok(s > commit_time)
+        } else if self.ancestor_of.is_some() || self.upstream_gone || self.newer_than.is_some() {
+            Ok(true)
+        } else {
+            // `--status` is to audit branches, including ones still tracking their upstream.
+            Ok(self.status || branch.upstream().is_err())
+        }
+    }
+
+    /// Check if the tip of the branch, or its first parents up to the depth, is tagged.
+    fn is_tagged(&self, branch: &Branch) -> Result<bool, Box<dyn Error>> {
+        if self.tagged_commits.is_empty() {
+            return Ok(false);
+        }
+        let mut commit = branch.get().peel_to_commit()?;
+        for _ in 0..self.protect_tagged_depth {
+            if self.tagged_commits.contains(&commit.id()) {
+                return Ok(true);
+            }
+            match commit.parent(0) {
+                Ok(parent) => commit = parent,
+                Err(e) if e.code() == ErrorCode::NotFound => return Ok(false),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(self.tagged_commits.contains(&commit.id()))
+    }
+
+    /// Check if the tip of the branch is reachable from any remote tracking branch.
+    fn is_pushed(&self, branch: &Branch) -> Result<bool, Box<dyn Error>> {
+        let tip = branch.get().peel_to_commit()?.id();
+        for remote_branch in self.repo.branches(Some(BranchType::Remote))? {
+            let (remote_branch, _) = remote_branch?;
+            // symbolic ref like origin/HEAD points to other remote tracking branch.
+            let Some(remote_tip) = remote_branch.get().target() else {
+                continue;
+            };
+            if remote_tip == tip || self.repo.graph_descendant_of(remote_tip, tip)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Check if the branch has upstream configured but its remote tracking branch doesn't exist.
+    fn is_upstream_gone(&self, branch: &Branch) -> Result<bool, Box<dyn Error>> {
+        let Some(refname) = branch.get().name() else {
+            return Ok(false);
+        };
+        let upstream_name = match self.repo.branch_upstream_name(refname) {
+            Ok(name) => name,
+            // never tracked
+            Err(e) if e.code() == ErrorCode::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        let Some(upstream_name) = upstream_name.as_str() else {
+            return Ok(false);
+        };
+        match self.repo.find_reference(upstream_name) {
+            Ok(_) => Ok(false),
+            Err(e) if e.code() == ErrorCode::NotFound => Ok(true),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     fn match_branch(&self, branch: &Branch) -> Result<bool, Box<dyn Error>> {
         match branch.name()? {
             None => Ok(false),
@@ -137,38 +636,223 @@ impl Command {
                 if branch.is_head() {
                     info!(
                         "branch '{}' ignored. NOTE: HEAD branch is always ignored.",
-                        branch_name
+                        display_safe(branch_name)
+                    );
+                    Ok(false)
+                } else if self.delete
+                    && !self.force
+                    && self.guarded_branches.iter().any(|b| b == branch_name)
+                {
+                    warn!(
+                        "branch '{}' ignored because it is the default branch or tracked by HEAD. Use --force to delete it anyway.",
+                        display_safe(branch_name)
+                    );
+                    Ok(false)
+                } else if self.delete && self.is_tagged(branch)? {
+                    info!(
+                        "branch '{}' ignored because it is tagged. Use --no-protect-tagged to delete it.",
+                        display_safe(branch_name)
+                    );
+                    Ok(false)
+                } else if self.is_protected(branch_name) {
+                    info!(
+                        "branch '{}' ignored because it is protected by stale.protect.",
+                        display_safe(branch_name)
+                    );
+                    Ok(false)
+                } else if self.excludes.is_match(branch_name) {
+                    info!(
+                        "branch '{}' ignored because it is excluded.",
+                        display_safe(branch_name)
                     );
                     Ok(false)
                 } else if self.branches.is_empty() {
                     Ok(true)
                 } else {
-                    match self
+                    Ok(self
                         .branches
                         .iter()
-                        .find(|&prefix| branch_name.starts_with(prefix))
-                    {
-                        Some(_) => Ok(true),
-                        None => Ok(false),
-                    }
+                        .any(|selector| select_branch(selector, branch_name)))
                 }
             }
         }
     }
+
+    fn is_protected(&self, branch_name: &str) -> bool {
+        let branch_name = CString::new(branch_name).unwrap();
+        self.protected_branches.iter().any(|pat| {
+            let pat = CString::new(pat.as_str()).unwrap();
+            fnmatch(pat.as_c_str(), branch_name.as_c_str())
+        })
+    }
+}
+
+/// Escape control characters in branch name given in short or full form, for logs.
+fn display_safe(name: &str) -> String {
+    escape_control(name)
+}
+
+/// Match branch name against selector; a glob if it has any of `*?[`, or a prefix otherwise.
+fn select_branch(selector: &str, branch_name: &str) -> bool {
+    if selector.contains(['*', '?', '[']) {
+        let pat = CString::new(selector).unwrap();
+        let branch_name = CString::new(branch_name).unwrap();
+        fnmatch(pat.as_c_str(), branch_name.as_c_str())
+    } else {
+        branch_name.starts_with(selector)
+    }
+}
+
+/// Columns of `--status` table.
+const STATUS_HEADER: [&str; 5] = ["branch", "upstream", "ahead", "behind", "last_commit"];
+
+/// Lines of the table with columns padded to align, separated by two spaces.
+fn format_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) -> Vec<String> {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    std::iter::once(header.map(str::to_owned))
+        .chain(rows.iter().cloned())
+        .map(|row| {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ");
+            line.trim_end().to_owned()
+        })
+        .collect()
+}
+
+/// Commits pointed to by tags, peeling annotated tags.
+fn get_tagged_commits(repo: &Repository) -> Result<HashSet<Oid>, git2::Error> {
+    let mut commits = HashSet::new();
+    for reference in repo.references_glob("refs/tags/*")? {
+        // tags pointing to trees or blobs protect no branches.
+        if let Ok(commit) = reference?.peel_to_commit() {
+            commits.insert(commit.id());
+        }
+    }
+    Ok(commits)
+}
+
+/// Read `:`-separated list from git config, or empty list if unset.
+fn get_config_list(config: &Config, name: &str) -> Result<Vec<String>, git2::Error> {
+    match config.get_string(name) {
+        Ok(s) => Ok(s
+            .split(':')
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_owned())
+            .collect()),
+        Err(e) if e.code() == ErrorCode::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Default branch like git-dah resolves, and the branch HEAD tracks if any.
+///
+/// The latter is given by the name on its remote, which is also the name
+/// `--push` would delete.
+fn get_guarded_branches(repo: &Repository) -> Result<Vec<String>, git2::Error> {
+    let mut guarded = Vec::new();
+    match repo.config()?.get_string("init.defaultbranch") {
+        Ok(name) => guarded.push(name),
+        Err(e) if e.code() == ErrorCode::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let head = match repo.head() {
+        Ok(head) => head,
+        Err(e) if e.code() == ErrorCode::UnbornBranch || e.code() == ErrorCode::NotFound => {
+            return Ok(guarded)
+        }
+        Err(e) => return Err(e),
+    };
+    let Some(head_name) = head.name().filter(|_| head.is_branch()) else {
+        return Ok(guarded);
+    };
+    let (upstream, remote) = match (
+        repo.branch_upstream_name(head_name),
+        repo.branch_upstream_remote(head_name),
+    ) {
+        (Ok(upstream), Ok(remote)) => (upstream, remote),
+        (Err(e), _) | (_, Err(e)) if e.code() == ErrorCode::NotFound => return Ok(guarded),
+        (Err(e), _) | (_, Err(e)) => return Err(e),
+    };
+    if let (Some(upstream), Some(remote)) = (upstream.as_str(), remote.as_str()) {
+        let prefix = if remote == "." {
+            "refs/heads/".to_owned()
+        } else {
+            format!("refs/remotes/{}/", remote)
+        };
+        if let Some(name) = upstream.strip_prefix(&prefix) {
+            guarded.push(name.to_owned());
+        }
+    }
+    Ok(guarded)
 }
 
 impl Cli {
     fn into_command(self) -> Result<Command, Box<dyn Error>> {
         let repo = Repository::open_from_env()?;
+        self.into_command_with_repo(repo)
+    }
+
+    fn into_command_with_repo(self, repo: Repository) -> Result<Command, Box<dyn Error>> {
         let now = Local::now();
         let since = self.since.map(|s| now - s);
+        let newer_than = self.newer_than.map(|s| now - s);
+
+        let config = repo.config()?;
+        let branches = if self.branches.is_empty() {
+            get_config_list(&config, "stale.prefix")?
+        } else {
+            self.branches
+        };
+        let excludes = if self.regex {
+            Excludes::Regex(RegexSet::new(&self.exclude)?)
+        } else {
+            Excludes::Prefix(self.exclude)
+        };
+        let protected_branches = get_config_list(&config, "stale.protect")?;
+        let guarded_branches = get_guarded_branches(&repo)?;
+        // the last one of --protect-tagged and --no-protect-tagged wins.
+        let protect_tagged = self.protect_tagged || !self.no_protect_tagged;
+        let tagged_commits = if self.delete && protect_tagged {
+            get_tagged_commits(&repo)?
+        } else {
+            HashSet::new()
+        };
+        let ancestor_of = match self.ancestor_of {
+            Some(r) => Some(repo.revparse_single(&r)?.peel_to_commit()?.id()),
+            None => None,
+        };
 
         Ok(Command {
             repo,
             delete: self.delete,
             push: self.push,
+            local: self.local,
+            remote: self.remote,
             since,
-            branches: self.branches,
+            newer_than,
+            ancestor_of,
+            upstream_gone: self.upstream_gone,
+            format: self.format,
+            status: self.status,
+            json_summary: self.json_summary,
+            branches,
+            excludes,
+            protected_branches,
+            guarded_branches,
+            force: self.force,
+            max_delete: self.max_delete,
+            tagged_commits,
+            protect_tagged_depth: self.protect_tagged_depth,
         })
     }
 }
@@ -183,3 +867,869 @@ fn main() -> ! {
         Ok(_) => exit(0),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use git2::{BranchType, ConfigLevel, Repository, Signature, Time, WorktreeAddOptions};
+    use tempfile::TempDir;
+    use url::Url;
+
+    use std::cell::RefCell;
+
+    use git_toolbox::reltime::Reltime;
+
+    use super::{
+        display_safe, format_table, Cli, Command, DeletionError, Lock, LockHeld, Summary,
+        TooManyBranches,
+    };
+
+    /// Names of the branches `cmd` selects.
+    fn selected_names(cmd: &Command) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        cmd.for_each(Vec::new(), |mut v, branch| {
+            v.push(branch.name()?.unwrap().to_owned());
+            Ok(v)
+        })
+    }
+
+    fn init_repo_with_branches(
+        path: &std::path::Path,
+        branches: &[&str],
+    ) -> Result<Repository, Box<dyn std::error::Error>> {
+        let repo = Repository::init_bare(path)?;
+        {
+            let author = Signature::now("foo", "foo@example.com")?;
+            let tree = repo.treebuilder(None)?;
+            let tree = tree.write()?;
+            let tree = repo.find_tree(tree)?;
+            let oid = repo.commit(
+                Some("refs/heads/main"),
+                &author,
+                &author,
+                "Initial commit",
+                &tree,
+                &[],
+            )?;
+            let commit = repo.find_commit(oid)?;
+            for branch in branches {
+                repo.branch(branch, &commit, false)?;
+            }
+            repo.set_head("refs/heads/main")?;
+        }
+        Ok(repo)
+    }
+
+    #[test]
+    fn command_selects_branches_by_config() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo = init_repo_with_branches(
+            tmpdir.path(),
+            &["feature/a", "feature/keep", "fix/b", "release/v1"],
+        )?;
+        {
+            let mut config = repo.config()?.open_level(ConfigLevel::Local)?;
+            config.set_str("stale.prefix", "feature/:fix/")?;
+            config.set_str("stale.protect", "*/keep")?;
+        }
+
+        let cli = Cli::parse_from(["git-stale"]);
+        let cmd = cli.into_command_with_repo(repo)?;
+        let selected = selected_names(&cmd)?;
+        assert_eq!(selected, vec!["feature/a", "fix/b"]);
+
+        // CLI args override stale.prefix
+        let repo = Repository::open_bare(tmpdir.path())?;
+        let cli = Cli::parse_from(["git-stale", "release/", "feature/"]);
+        let cmd = cli.into_command_with_repo(repo)?;
+        let selected = selected_names(&cmd)?;
+        assert_eq!(selected, vec!["feature/a", "release/v1"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_selects_branches_by_glob() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        init_repo_with_branches(
+            tmpdir.path(),
+            &["release/1.0", "release/1.1", "release/2.0", "feature/a"],
+        )?;
+
+        for (args, want) in [
+            (
+                &["git-stale", "release/*.0"][..],
+                vec!["release/1.0", "release/2.0"],
+            ),
+            (&["git-stale", "release/?.1"][..], vec!["release/1.1"]),
+            // glob matches against the full branch name
+            (
+                &["git-stale", "release/1"][..],
+                vec!["release/1.0", "release/1.1"],
+            ),
+            (&["git-stale", "release/[12]"][..], vec![]),
+            (
+                &["git-stale", "release/*.0", "feature/"][..],
+                vec!["feature/a", "release/1.0", "release/2.0"],
+            ),
+        ] {
+            let repo = Repository::open_bare(tmpdir.path())?;
+            let cmd = Cli::parse_from(args).into_command_with_repo(repo)?;
+            let selected = selected_names(&cmd)?;
+            assert_eq!(selected, want, "{:?}", args);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_excludes_branches() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        init_repo_with_branches(
+            tmpdir.path(),
+            &["feature/a", "feature/keep-b", "feature/c-wip", "fix/d"],
+        )?;
+
+        for (args, want) in [
+            (
+                &["git-stale", "feature/", "--exclude", "feature/keep-"][..],
+                vec!["feature/a", "feature/c-wip"],
+            ),
+            (
+                &[
+                    "git-stale",
+                    "--exclude",
+                    "feature/keep-",
+                    "--exclude",
+                    "fix/",
+                ][..],
+                vec!["feature/a", "feature/c-wip"],
+            ),
+            // excludes take precedence over includes
+            (&["git-stale", "fix/", "--exclude", "fix/"][..], vec![]),
+            (
+                &["git-stale", "feature/", "--regex", "--exclude=-wip$|keep"][..],
+                vec!["feature/a"],
+            ),
+        ] {
+            let repo = Repository::open_bare(tmpdir.path())?;
+            let cmd = Cli::parse_from(args).into_command_with_repo(repo)?;
+            let selected = selected_names(&cmd)?;
+            assert_eq!(selected, want, "{:?}", args);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_selects_branches_by_ancestor_of() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo = Repository::init_bare(tmpdir.path())?;
+        {
+            let author = Signature::now("foo", "foo@example.com")?;
+            let tree = repo.treebuilder(None)?;
+            let tree = tree.write()?;
+            let tree = repo.find_tree(tree)?;
+            let c1 = repo.commit(None, &author, &author, "1", &tree, &[])?;
+            let c1 = repo.find_commit(c1)?;
+            let c2 = repo.commit(None, &author, &author, "2", &tree, &[&c1])?;
+            let c2 = repo.find_commit(c2)?;
+            let c3 = repo.commit(None, &author, &author, "3", &tree, &[&c2])?;
+            let c3 = repo.find_commit(c3)?;
+
+            repo.tag("v1", c2.as_object(), &author, "release v1", false)?;
+            repo.branch("main", &c3, false)?;
+            repo.branch("shipped", &c1, false)?;
+            repo.branch("released", &c2, false)?;
+            repo.branch("wip", &c3, false)?;
+            repo.set_head("refs/heads/main")?;
+        }
+
+        let cli = Cli::parse_from(["git-stale", "--ancestor-of", "v1"]);
+        let cmd = cli.into_command_with_repo(repo)?;
+        let selected = selected_names(&cmd)?;
+        assert_eq!(selected, vec!["released", "shipped"]);
+
+        // AND with prefixes
+        let repo = Repository::open_bare(tmpdir.path())?;
+        let cli = Cli::parse_from(["git-stale", "--ancestor-of", "v1", "ship"]);
+        let cmd = cli.into_command_with_repo(repo)?;
+        let selected = selected_names(&cmd)?;
+        assert_eq!(selected, vec!["shipped"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cli_parses_reltime_of_multiple_components() {
+        let cli = Cli::try_parse_from(["git-stale", "--since", "2 weeks 3 days"]).unwrap();
+        assert_eq!(cli.since, Some(Reltime::checked(0, 0, 2, 3).unwrap()));
+
+        let Err(err) = Cli::try_parse_from(["git-stale", "--since", "2 weeks 3 dayz"]) else {
+            panic!("wanted parse error");
+        };
+        let message = err.to_string();
+        assert!(
+            message.contains(r#"cannot parse "dayz" in "2 weeks 3 dayz""#),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn command_deletes_nothing_over_max_delete() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo =
+            init_repo_with_branches(tmpdir.path(), &["feature/a", "feature/b", "feature/c"])?;
+        let main = repo.refname_to_id("refs/heads/main")?;
+        repo.reference("refs/remotes/origin/main", main, false, "test")?;
+        let branches = |repo: &Repository| -> Result<usize, git2::Error> {
+            Ok(repo.branches(Some(BranchType::Local))?.count())
+        };
+
+        let cli = Cli::parse_from(["git-stale", "--delete", "--max-delete", "2", "feature/"]);
+        let err = cli
+            .into_command_with_repo(Repository::open_bare(tmpdir.path())?)?
+            .run()
+            .expect_err("wanted TooManyBranches");
+        let err = err
+            .downcast_ref::<TooManyBranches>()
+            .expect("wanted TooManyBranches");
+        assert_eq!((err.count, err.max), (3, 2));
+        assert_eq!(branches(&repo)?, 4);
+
+        let cli = Cli::parse_from(["git-stale", "--delete", "--max-delete", "3", "feature/"]);
+        cli.into_command_with_repo(Repository::open_bare(tmpdir.path())?)?
+            .run()?;
+        assert_eq!(branches(&repo)?, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_refuses_to_delete_while_locked() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo = init_repo_with_branches(tmpdir.path(), &["feature/a"])?;
+        repo.reference(
+            "refs/remotes/origin/feature/a",
+            repo.refname_to_id("refs/heads/feature/a")?,
+            false,
+            "test",
+        )?;
+
+        let lock = Lock::acquire(&repo)?;
+        let err = Lock::acquire(&repo).expect_err("wanted LockHeld");
+        assert!(err.downcast_ref::<LockHeld>().is_some());
+
+        let cli = Cli::parse_from(["git-stale", "--delete", "feature/"]);
+        let err = cli
+            .into_command_with_repo(Repository::open_bare(tmpdir.path())?)?
+            .run()
+            .expect_err("wanted LockHeld");
+        assert!(err.downcast_ref::<LockHeld>().is_some());
+        assert!(repo.find_branch("feature/a", BranchType::Local).is_ok());
+
+        // listing needs no lock
+        let cli = Cli::parse_from(["git-stale", "feature/"]);
+        cli.into_command_with_repo(Repository::open_bare(tmpdir.path())?)?
+            .run()?;
+
+        drop(lock);
+        let cli = Cli::parse_from(["git-stale", "--delete", "feature/"]);
+        cli.into_command_with_repo(Repository::open_bare(tmpdir.path())?)?
+            .run()?;
+        assert!(repo.find_branch("feature/a", BranchType::Local).is_err());
+        assert!(!tmpdir.path().join("stale.lock").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_never_deletes_tagged_branches() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo = Repository::init_bare(tmpdir.path())?;
+        {
+            let author = Signature::now("foo", "foo@example.com")?;
+            let tree = repo.treebuilder(None)?;
+            let tree = tree.write()?;
+            let tree = repo.find_tree(tree)?;
+            let c1 = repo.commit(None, &author, &author, "1", &tree, &[])?;
+            let c1 = repo.find_commit(c1)?;
+            let c2 = repo.commit(None, &author, &author, "2", &tree, &[&c1])?;
+            let c2 = repo.find_commit(c2)?;
+            let c3 = repo.commit(None, &author, &author, "3", &tree, &[&c2])?;
+            let c3 = repo.find_commit(c3)?;
+
+            repo.tag("v1", c1.as_object(), &author, "release v1", false)?;
+            repo.tag_lightweight("v2", c2.as_object(), false)?;
+            repo.branch("main", &c3, false)?;
+            repo.branch("release/1", &c1, false)?;
+            repo.branch("release/2", &c2, false)?;
+            repo.branch("wip", &c3, false)?;
+            repo.set_head("refs/heads/main")?;
+            // pretend everything is pushed
+            repo.reference("refs/remotes/origin/main", c3.id(), false, "test")?;
+        }
+        let branches = |path: &std::path::Path| -> Result<Vec<String>, Box<dyn std::error::Error>> {
+            let repo = Repository::open_bare(path)?;
+            let names = repo
+                .branches(Some(BranchType::Local))?
+                .map(|b| Ok(b?.0.name()?.unwrap().to_owned()))
+                .collect::<Result<Vec<_>, git2::Error>>()?;
+            Ok(names)
+        };
+
+        // listing is not protected
+        let cli = Cli::parse_from(["git-stale", "--ancestor-of", "main"]);
+        let cmd = cli.into_command_with_repo(Repository::open_bare(tmpdir.path())?)?;
+        let selected = selected_names(&cmd)?;
+        assert_eq!(selected, vec!["release/1", "release/2", "wip"]);
+
+        let cli = Cli::parse_from(["git-stale", "--delete", "--ancestor-of", "main"]);
+        cli.into_command_with_repo(Repository::open_bare(tmpdir.path())?)?
+            .run()?;
+        assert_eq!(
+            branches(tmpdir.path())?,
+            vec!["main", "release/1", "release/2"]
+        );
+
+        let cli = Cli::parse_from([
+            "git-stale",
+            "--delete",
+            "--no-protect-tagged",
+            "--protect-tagged",
+            "--ancestor-of",
+            "main",
+        ]);
+        cli.into_command_with_repo(Repository::open_bare(tmpdir.path())?)?
+            .run()?;
+        assert_eq!(
+            branches(tmpdir.path())?,
+            vec!["main", "release/1", "release/2"]
+        );
+
+        let cli = Cli::parse_from(["git-stale", "--delete", "--no-protect-tagged", "release/"]);
+        cli.into_command_with_repo(Repository::open_bare(tmpdir.path())?)?
+            .run()?;
+        assert_eq!(branches(tmpdir.path())?, vec!["main"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_protects_branches_tagged_within_depth() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo = init_repo_with_branches(tmpdir.path(), &[])?;
+        {
+            let author = Signature::now("foo", "foo@example.com")?;
+            let main = repo.find_commit(repo.refname_to_id("refs/heads/main")?)?;
+            let tree = main.tree()?;
+            let c2 = repo.commit(None, &author, &author, "2", &tree, &[&main])?;
+            let c2 = repo.find_commit(c2)?;
+            let c3 = repo.commit(None, &author, &author, "3", &tree, &[&c2])?;
+            let c3 = repo.find_commit(c3)?;
+            repo.tag_lightweight("v1", main.as_object(), false)?;
+            repo.branch("hotfix", &c3, false)?;
+            repo.reference("refs/remotes/origin/hotfix", c3.id(), false, "test")?;
+        }
+
+        // v1 is two commits behind the tip
+        let cli = Cli::parse_from([
+            "git-stale",
+            "--delete",
+            "--protect-tagged-depth",
+            "2",
+            "hotfix",
+        ]);
+        cli.into_command_with_repo(repo)?.run()?;
+        let repo = Repository::open_bare(tmpdir.path())?;
+        assert!(repo.find_branch("hotfix", BranchType::Local).is_ok());
+
+        let cli = Cli::parse_from([
+            "git-stale",
+            "--delete",
+            "--protect-tagged-depth",
+            "1",
+            "hotfix",
+        ]);
+        cli.into_command_with_repo(repo)?.run()?;
+        let repo = Repository::open_bare(tmpdir.path())?;
+        assert!(repo.find_branch("hotfix", BranchType::Local).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_selects_branches_newer_than() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo = Repository::init_bare(tmpdir.path())?;
+        {
+            let tree = repo.treebuilder(None)?;
+            let tree = tree.write()?;
+            let tree = repo.find_tree(tree)?;
+            let now = Signature::now("foo", "foo@example.com")?;
+            let old = Signature::new(
+                "foo",
+                "foo@example.com",
+                &Time::new(now.when().seconds() - 30 * 24 * 60 * 60, 0),
+            )?;
+            let c1 = repo.commit(None, &old, &old, "old", &tree, &[])?;
+            let c1 = repo.find_commit(c1)?;
+            let c2 = repo.commit(None, &now, &now, "new", &tree, &[&c1])?;
+            let c2 = repo.find_commit(c2)?;
+
+            repo.branch("main", &c2, false)?;
+            repo.branch("old", &c1, false)?;
+            repo.branch("recent", &c2, false)?;
+            repo.set_head("refs/heads/main")?;
+        }
+
+        let cli = Cli::parse_from(["git-stale", "--newer-than", "1w"]);
+        let cmd = cli.into_command_with_repo(repo)?;
+        let selected = selected_names(&cmd)?;
+        assert_eq!(selected, vec!["recent"]);
+
+        // inverse of --since
+        let repo = Repository::open_bare(tmpdir.path())?;
+        let cli = Cli::parse_from(["git-stale", "--since", "1w"]);
+        let cmd = cli.into_command_with_repo(repo)?;
+        let selected = selected_names(&cmd)?;
+        assert_eq!(selected, vec!["old"]);
+
+        // never deletes recently touched branches
+        assert!(Cli::try_parse_from(["git-stale", "--newer-than", "1w", "--delete"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_formats_branches() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        init_repo_with_branches(tmpdir.path(), &["feature/a", "fix/b"])?;
+
+        for (args, want) in [
+            (&["git-stale"][..], vec!["feature/a", "fix/b"]),
+            (
+                &["git-stale", "--format", "name-only"][..],
+                vec!["feature/a", "fix/b"],
+            ),
+            (
+                &["git-stale", "--format", "full"][..],
+                vec!["refs/heads/feature/a", "refs/heads/fix/b"],
+            ),
+        ] {
+            let repo = Repository::open_bare(tmpdir.path())?;
+            let cmd = Cli::parse_from(args).into_command_with_repo(repo)?;
+            let got = cmd.for_each(Vec::new(), |mut v, branch| {
+                v.push(cmd.format_branch(&branch)?);
+                Ok(v)
+            })?;
+            assert_eq!(got, want, "{:?}", args);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_lists_status_of_branches() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo = init_repo_with_branches(tmpdir.path(), &["ahead", "untracked"])?;
+        {
+            repo.remote("origin", "https://example.com/repo.git")?;
+            let main = repo.find_branch("main", BranchType::Local)?;
+            let base = main.get().peel_to_commit()?;
+            repo.reference("refs/remotes/origin/ahead", base.id(), false, "fetch")?;
+            let author = Signature::now("foo", "foo@example.com")?;
+            let tree = base.tree()?;
+            let mut parent = base;
+            for message in ["one", "two"] {
+                let oid = repo.commit(
+                    Some("refs/heads/ahead"),
+                    &author,
+                    &author,
+                    message,
+                    &tree,
+                    &[&parent],
+                )?;
+                parent = repo.find_commit(oid)?;
+            }
+            let mut config = repo.config()?.open_level(ConfigLevel::Local)?;
+            config.set_str("branch.ahead.remote", "origin")?;
+            config.set_str("branch.ahead.merge", "refs/heads/ahead")?;
+        }
+
+        let cli = Cli::parse_from(["git-stale", "--status"]);
+        let cmd = cli.into_command_with_repo(repo)?;
+        let rows = cmd.for_each(Vec::new(), |mut rows, branch| {
+            rows.push(cmd.status_row(&branch)?);
+            Ok(rows)
+        })?;
+        let rows: Vec<_> = rows.iter().map(|row| row[..4].join(" ")).collect();
+        // main is skipped as the default branch
+        assert_eq!(rows, vec!["ahead origin/ahead 2 0", "untracked - - -"]);
+
+        assert!(Cli::try_parse_from(["git-stale", "--status", "--delete"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_table() {
+        let rows = [
+            ["feature/a".to_owned(), "origin/feature/a".to_owned()],
+            ["b".to_owned(), "-".to_owned()],
+        ];
+        assert_eq!(
+            format_table(["branch", "upstream"], &rows),
+            vec![
+                "branch     upstream",
+                "feature/a  origin/feature/a",
+                "b          -"
+            ]
+        );
+    }
+
+    #[test]
+    fn command_selects_branches_with_upstream_gone() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo = init_repo_with_branches(tmpdir.path(), &["alive", "gone", "untracked"])?;
+        {
+            repo.remote("origin", "https://example.com/repo.git")?;
+            let main = repo.find_branch("main", BranchType::Local)?;
+            let oid = main.get().peel_to_commit()?.id();
+            // remote tracking branch of "gone" is pruned already
+            repo.reference("refs/remotes/origin/alive", oid, false, "fetch")?;
+            let mut config = repo.config()?.open_level(ConfigLevel::Local)?;
+            for name in ["alive", "gone"] {
+                config.set_str(&format!("branch.{}.remote", name), "origin")?;
+                config.set_str(
+                    &format!("branch.{}.merge", name),
+                    &format!("refs/heads/{}", name),
+                )?;
+            }
+        }
+
+        let cli = Cli::parse_from(["git-stale", "--upstream-gone"]);
+        let cmd = cli.into_command_with_repo(repo)?;
+        let selected = selected_names(&cmd)?;
+        assert_eq!(selected, vec!["gone"]);
+
+        // without --upstream-gone, branches never tracked are also selected
+        let repo = Repository::open_bare(tmpdir.path())?;
+        let cli = Cli::parse_from(["git-stale"]);
+        let cmd = cli.into_command_with_repo(repo)?;
+        let selected = selected_names(&cmd)?;
+        assert_eq!(selected, vec!["gone", "untracked"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_reports_branches_failed_to_delete() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo = Repository::init(tmpdir.path().join("repo"))?;
+        {
+            let author = Signature::now("foo", "foo@example.com")?;
+            let tree = repo.treebuilder(None)?;
+            let tree = tree.write()?;
+            let tree = repo.find_tree(tree)?;
+            let oid = repo.commit(Some("HEAD"), &author, &author, "1", &tree, &[])?;
+            let commit = repo.find_commit(oid)?;
+            repo.branch("a-stale", &commit, false)?;
+            let busy = repo.branch("b-busy", &commit, false)?;
+            repo.branch("c-stale", &commit, false)?;
+
+            // branch checked out in other worktree cannot be deleted
+            repo.worktree(
+                "wt",
+                &tmpdir.path().join("wt"),
+                Some(WorktreeAddOptions::new().reference(Some(busy.get()))),
+            )?;
+        }
+
+        // no remotes, so nothing is pushed
+        let cli = Cli::parse_from(["git-stale", "--delete", "--force"]);
+        let cmd = cli.into_command_with_repo(repo)?;
+        let got = cmd.run();
+        let err = got.expect_err("wanted deletion error");
+        let err = err
+            .downcast_ref::<DeletionError>()
+            .expect("wanted DeletionError");
+        let failed: Vec<_> = err.failures.iter().map(|(b, _)| b.as_str()).collect();
+        assert_eq!(failed, vec!["refs/heads/b-busy"]);
+
+        // other branches are deleted anyway
+        let repo = Repository::open(tmpdir.path().join("repo"))?;
+        assert!(repo.find_branch("a-stale", BranchType::Local).is_err());
+        assert!(repo.find_branch("b-busy", BranchType::Local).is_ok());
+        assert!(repo.find_branch("c-stale", BranchType::Local).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_summarizes_run() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo = Repository::init(tmpdir.path().join("repo"))?;
+        {
+            let author = Signature::now("foo", "foo@example.com")?;
+            let tree = repo.treebuilder(None)?;
+            let tree = tree.write()?;
+            let tree = repo.find_tree(tree)?;
+            let oid = repo.commit(Some("HEAD"), &author, &author, "1", &tree, &[])?;
+            let commit = repo.find_commit(oid)?;
+            repo.branch("feature/a", &commit, false)?;
+            let busy = repo.branch("feature/b", &commit, false)?;
+            repo.branch("fix/c", &commit, false)?;
+            repo.worktree(
+                "wt",
+                &tmpdir.path().join("wt"),
+                Some(WorktreeAddOptions::new().reference(Some(busy.get()))),
+            )?;
+        }
+
+        let cli = Cli::parse_from(["git-stale", "--json-summary", "feature/"]);
+        let cmd = cli.into_command_with_repo(repo)?;
+        let summary = RefCell::new(Summary::default());
+        cmd.run_with_summary(&summary)?;
+        assert_eq!(summary.borrow().matched, vec!["feature/a", "feature/b"]);
+        assert!(summary.borrow().deleted.is_empty());
+
+        let repo = Repository::open(tmpdir.path().join("repo"))?;
+        let cli = Cli::parse_from(["git-stale", "--delete", "--force", "feature/"]);
+        let cmd = cli.into_command_with_repo(repo)?;
+        let summary = RefCell::new(Summary {
+            operation: "delete",
+            scanned: 4,
+            ..Default::default()
+        });
+        assert!(cmd.run_with_summary(&summary).is_err());
+        let summary = summary.into_inner();
+        assert_eq!(
+            summary,
+            Summary {
+                operation: "delete",
+                scanned: 4,
+                matched: vec!["feature/a".to_owned(), "feature/b".to_owned()],
+                deleted: vec!["feature/a".to_owned()],
+                failed: vec!["feature/b".to_owned()],
+            }
+        );
+        assert_eq!(
+            summary.to_json(),
+            r#"{"operation":"delete","scanned":4,"matched":["feature/a","feature/b"],"deleted":["feature/a"],"failed":["feature/b"]}"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_never_deletes_default_branch() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo = init_repo_with_branches(tmpdir.path(), &["develop", "feature/a", "upstream"])?;
+        {
+            let mut config = repo.config()?.open_level(ConfigLevel::Local)?;
+            config.set_str("init.defaultbranch", "main")?;
+            config.set_str("branch.develop.remote", ".")?;
+            config.set_str("branch.develop.merge", "refs/heads/upstream")?;
+            repo.set_head("refs/heads/develop")?;
+            // every branch points to the initial commit, which is already pushed.
+            let tip = repo.refname_to_id("refs/heads/main")?;
+            repo.reference("refs/remotes/origin/main", tip, false, "test")?;
+        }
+
+        // listing is not guarded
+        let cli = Cli::parse_from(["git-stale", "*"]);
+        let cmd = cli.into_command_with_repo(repo)?;
+        let selected = selected_names(&cmd)?;
+        assert_eq!(selected, vec!["feature/a", "main", "upstream"]);
+
+        let repo = Repository::open_bare(tmpdir.path())?;
+        let cli = Cli::parse_from(["git-stale", "--delete", "*"]);
+        cli.into_command_with_repo(repo)?.run()?;
+        let repo = Repository::open_bare(tmpdir.path())?;
+        assert!(repo.find_branch("feature/a", BranchType::Local).is_err());
+        assert!(repo.find_branch("main", BranchType::Local).is_ok());
+        assert!(repo.find_branch("upstream", BranchType::Local).is_ok());
+
+        let cli = Cli::parse_from(["git-stale", "--delete", "--force", "*"]);
+        cli.into_command_with_repo(repo)?.run()?;
+        let repo = Repository::open_bare(tmpdir.path())?;
+        assert!(repo.find_branch("main", BranchType::Local).is_err());
+        assert!(repo.find_branch("upstream", BranchType::Local).is_err());
+        assert!(repo.find_branch("develop", BranchType::Local).is_ok());
+
+        assert!(Cli::try_parse_from(["git-stale", "--force"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_never_deletes_unpushed_branches() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo = init_repo_with_branches(tmpdir.path(), &["feature/merged", "feature/wip"])?;
+        {
+            let main = repo.find_commit(repo.refname_to_id("refs/heads/main")?)?;
+            let author = Signature::now("foo", "foo@example.com")?;
+            let tree = main.tree()?;
+            let wip = repo.commit(
+                Some("refs/heads/feature/wip"),
+                &author,
+                &author,
+                "Work in progress",
+                &tree,
+                &[&main],
+            )?;
+            let pushed = repo.commit(None, &author, &author, "Pushed", &tree, &[&main])?;
+            repo.reference("refs/remotes/origin/main", pushed, false, "test")?;
+            assert_ne!(wip, pushed);
+        }
+
+        let cli = Cli::parse_from(["git-stale", "--delete", "feature/"]);
+        let summary = RefCell::new(Summary::default());
+        cli.into_command_with_repo(repo)?
+            .run_with_summary(&summary)?;
+        assert_eq!(summary.into_inner().deleted, vec!["feature/merged"]);
+        let repo = Repository::open_bare(tmpdir.path())?;
+        assert!(repo
+            .find_branch("feature/merged", BranchType::Local)
+            .is_err());
+        assert!(repo.find_branch("feature/wip", BranchType::Local).is_ok());
+
+        let cli = Cli::parse_from(["git-stale", "--delete", "--force", "feature/"]);
+        cli.into_command_with_repo(repo)?.run()?;
+        let repo = Repository::open_bare(tmpdir.path())?;
+        assert!(repo.find_branch("feature/wip", BranchType::Local).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_deletes_local_and_remote_branches() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let remote_path = tmpdir.path().join("remote.git");
+        init_repo_with_branches(&remote_path, &["feature/a", "feature/b", "feature/c"])?;
+        let mut url = Url::parse("file:///")?;
+        url.set_path(remote_path.canonicalize()?.to_str().unwrap());
+
+        let local_path = tmpdir.path().join("local");
+        let repo = Repository::clone(url.as_str(), &local_path)?;
+        for name in ["feature/a", "feature/b", "feature/c"] {
+            let remote_branch =
+                repo.find_branch(&format!("origin/{}", name), BranchType::Remote)?;
+            let commit = remote_branch.get().peel_to_commit()?;
+            let mut branch = repo.branch(name, &commit, false)?;
+            branch.set_upstream(Some(&format!("origin/{}", name)))?;
+        }
+        // untracked branch is not selected by --push
+        repo.branch("feature/local-only", &repo.head()?.peel_to_commit()?, false)?;
+
+        let cli = Cli::parse_from([
+            "git-stale",
+            "--delete",
+            "--push",
+            "--local",
+            "--ancestor-of",
+            "HEAD",
+            "feature/a",
+            "feature/b",
+        ]);
+        let summary = RefCell::new(Summary::default());
+        cli.into_command_with_repo(repo)?
+            .run_with_summary(&summary)?;
+        // same names as deleting local branches records
+        let mut deleted = summary.into_inner().deleted;
+        deleted.sort();
+        assert_eq!(deleted, vec!["feature/a", "feature/b"]);
+
+        let remote = Repository::open_bare(&remote_path)?;
+        let repo = Repository::open(&local_path)?;
+        for name in ["feature/a", "feature/b"] {
+            assert!(
+                remote.find_branch(name, BranchType::Local).is_err(),
+                "{}",
+                name
+            );
+            assert!(
+                repo.find_branch(name, BranchType::Local).is_err(),
+                "{}",
+                name
+            );
+        }
+        assert!(remote.find_branch("feature/c", BranchType::Local).is_ok());
+        assert!(repo.find_branch("feature/c", BranchType::Local).is_ok());
+        assert!(repo
+            .find_branch("feature/local-only", BranchType::Local)
+            .is_ok());
+
+        // --local is only for --push
+        assert!(Cli::try_parse_from(["git-stale", "--delete", "--local"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn command_deletes_branches_on_the_remote_only() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let origin_path = tmpdir.path().join("origin.git");
+        init_repo_with_branches(&origin_path, &["feature/a", "feature/b"])?;
+        let mut origin_url = Url::parse("file:///")?;
+        origin_url.set_path(origin_path.canonicalize()?.to_str().unwrap());
+        // another remote sharing the history, like a fork
+        let fork_path = tmpdir.path().join("fork.git");
+        Repository::init_bare(&fork_path)?;
+        let mut fork_url = Url::parse("file:///")?;
+        fork_url.set_path(fork_path.canonicalize()?.to_str().unwrap());
+
+        let local_path = tmpdir.path().join("local");
+        let repo = Repository::clone(origin_url.as_str(), &local_path)?;
+        {
+            let mut fork = repo.remote("fork", fork_url.as_str())?;
+            fork.push(
+                &[
+                    "refs/remotes/origin/feature/a:refs/heads/feature/a",
+                    "refs/remotes/origin/feature/b:refs/heads/feature/b",
+                ],
+                None,
+            )?;
+            fork.fetch(&["refs/heads/*:refs/remotes/fork/*"], None, None)?;
+        }
+        for (name, remote) in [("feature/a", "origin"), ("feature/b", "fork")] {
+            let upstream = format!("{}/{}", remote, name);
+            let commit = repo
+                .find_branch(&upstream, BranchType::Remote)?
+                .get()
+                .peel_to_commit()?;
+            repo.branch(name, &commit, false)?
+                .set_upstream(Some(&upstream))?;
+        }
+
+        let cli = Cli::parse_from([
+            "git-stale",
+            "--delete",
+            "--push",
+            "--remote",
+            "origin",
+            "--ancestor-of",
+            "HEAD",
+            "feature/",
+        ]);
+        cli.into_command_with_repo(repo)?.run()?;
+
+        let origin = Repository::open_bare(&origin_path)?;
+        let fork = Repository::open_bare(&fork_path)?;
+        assert!(origin.find_branch("feature/a", BranchType::Local).is_err());
+        assert!(origin.find_branch("feature/b", BranchType::Local).is_ok());
+        assert!(fork.find_branch("feature/a", BranchType::Local).is_ok());
+        assert!(fork.find_branch("feature/b", BranchType::Local).is_ok());
+
+        assert!(Cli::try_parse_from(["git-stale", "--delete", "--remote", "origin"]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_safe() {
+        assert_eq!(display_safe("foo\x1bbar"), "foo\\u{1b}bar");
+        assert_eq!(display_safe("refs/heads/foo\n"), "refs/heads/foo\\n");
+    }
+}
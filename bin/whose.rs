@@ -1,20 +1,163 @@
-use clap::Parser;
+use std::io::{self, BufRead};
+
+use clap::{Parser, ValueEnum};
 use git2::Repository;
-use git_toolbox::app::whose::{Application, ApplicationBuilder};
+use git_toolbox::{
+    app::whose::{Application, ApplicationBuilder, OutputFormat},
+    github::codeowners::MatchOrder,
+};
 
 #[derive(Parser)]
 #[command(
     about = "find GitHub CODEOWNERS for path(s)",
     long_about = None)]
 struct Cli {
+    #[arg(
+        long,
+        help = "Output format; csv prints a header then one path,owner row per owner, and toml prints entries matching each path like --debug",
+        value_enum,
+        default_value_t = OutputFormat::Human
+    )]
+    format: OutputFormat,
+    #[arg(
+        long,
+        help = "Print each owner on its own line, skipping unowned paths",
+        conflicts_with = "format"
+    )]
+    owners_only: bool,
+    #[arg(
+        long,
+        help = "Combined with --owners-only, prefix each line with the path and a tab",
+        requires = "owners_only"
+    )]
+    with_path: bool,
+    #[arg(
+        long,
+        help = "Combined with --owners-only, print unowned paths too",
+        requires = "owners_only"
+    )]
+    include_unowned: bool,
+    #[arg(
+        short,
+        long,
+        help = "Print the effective entry for each path in one line like git check-ignore -v: SOURCE:LINE:PATTERN, owners and the path separated by tabs",
+        conflicts_with_all = ["format", "owners_only", "debug"]
+    )]
+    verbose: bool,
+    #[arg(
+        long,
+        help = "Print each owner followed by their paths indented, and unowned paths under (unowned)",
+        conflicts_with_all = ["format", "owners_only", "debug", "verbose"]
+    )]
+    group_by_owner: bool,
+    #[arg(
+        long,
+        help = "Merge all of .github/CODEOWNERS, CODEOWNERS and docs/CODEOWNERS instead of using the first one found"
+    )]
+    merge: bool,
+    #[arg(
+        long = "match",
+        value_name = "ORDER",
+        help = "Which entry wins when multiple entries match a path; GitHub uses the last one",
+        value_enum,
+        default_value_t = Match::Last
+    )]
+    match_order: Match,
+    #[arg(long, help = "Fail if any of paths doesn't exist")]
+    strict: bool,
+    #[arg(long, value_name = "N", help = "Stop after N matched paths")]
+    limit: Option<usize>,
+    #[arg(
+        long,
+        help = "Report suspicious patterns in CODEOWNERS file with line numbers, instead of finding owners"
+    )]
+    lint: bool,
+    #[arg(
+        long,
+        help = "Exit with non-zero status if CODEOWNERS file has malformed lines, printing them, instead of finding owners",
+        conflicts_with = "lint"
+    )]
+    validate: bool,
+    #[arg(
+        long,
+        help = "Print entries matching each path with their line numbers, and which one is effective, instead of owners"
+    )]
+    debug: bool,
+    #[arg(
+        long,
+        help = "Combined with --debug, print only the effective entry for each path",
+        requires = "debug"
+    )]
+    effective_only: bool,
+    #[arg(
+        long,
+        help = "Print owners of all entries matching each path, not only the effective one, like for required reviews"
+    )]
+    all_owners: bool,
+    #[arg(
+        long,
+        value_name = "REF",
+        help = "Find owners of paths changed between REF and the index only"
+    )]
+    changed_since: Option<String>,
+    #[arg(
+        long,
+        help = "Read NUL-delimited paths from stdin in addition to PATHSPECS, like the output of git ls-files -z"
+    )]
+    stdin0: bool,
     #[arg()]
     pathspecs: Vec<String>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Match {
+    /// The first matching entry in the file wins, as some tools other than GitHub do.
+    First,
+    /// The last matching entry in the file wins, as GitHub does.
+    Last,
+}
+
+/// Read paths delimited by NUL, skipping empty ones.
+fn read_nul_delimited<R: BufRead>(input: R) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut paths = Vec::new();
+    for path in input.split(b'\0') {
+        let path = path?;
+        if !path.is_empty() {
+            paths.push(String::from_utf8(path)?);
+        }
+    }
+    Ok(paths)
+}
+
 impl Cli {
-    fn into_app(self) -> Result<Application, Box<dyn std::error::Error>> {
+    fn into_app(mut self) -> Result<Application, Box<dyn std::error::Error>> {
         let repo = Repository::open_from_env()?;
+        if self.stdin0 {
+            self.pathspecs
+                .extend(read_nul_delimited(io::stdin().lock())?);
+        }
         Ok(ApplicationBuilder::new(repo)
+            .with_format(match (self.owners_only, self.with_path) {
+                (true, true) => OutputFormat::PathOwners,
+                (true, false) => OutputFormat::Owners,
+                _ if self.verbose => OutputFormat::Verbose,
+                _ => self.format,
+            })
+            .with_include_unowned(self.include_unowned)
+            .with_lint(self.lint)
+            .with_validate(self.validate)
+            .with_debug(self.debug)
+            .with_effective_only(self.effective_only)
+            .with_all_owners(self.all_owners)
+            .with_group_by_owner(self.group_by_owner)
+            .with_merge(self.merge)
+            .with_match_order(match self.match_order {
+                Match::First => MatchOrder::First,
+                Match::Last => MatchOrder::Last,
+            })
+            .with_strict(self.strict)
+            .with_limit(self.limit)
+            .with_changed_since(self.changed_since)
             .with_pathspecs(self.pathspecs)?
             .build()?)
     }
@@ -31,3 +174,25 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::read_nul_delimited;
+
+    #[test]
+    fn reads_nul_delimited_paths() -> Result<(), Box<dyn std::error::Error>> {
+        let input: &[u8] = b"src/a.rs\0docs/with space.md\0line\nbreak.txt\0";
+        assert_eq!(
+            read_nul_delimited(input)?,
+            vec!["src/a.rs", "docs/with space.md", "line\nbreak.txt"]
+        );
+
+        // the last path doesn't have to be terminated
+        let input: &[u8] = b"a.rs\0b.rs";
+        assert_eq!(read_nul_delimited(input)?, vec!["a.rs", "b.rs"]);
+
+        assert!(read_nul_delimited(&b"\xff\0"[..]).is_err());
+
+        Ok(())
+    }
+}
@@ -1,23 +1,47 @@
+mod file_config;
+mod jsonlog;
 mod statemachine;
+#[cfg(test)]
+mod testlog;
+mod textlog;
+mod timings;
 
-use crate::git::{GitTime, HeadRef, RemoteRef};
+use crate::git::{escape_control, fnmatch, GitTime, HeadRef, RefnameError, RemoteRef};
 use chrono::{DateTime, FixedOffset};
-use fnmatch_sys::{self, FNM_NOESCAPE};
-use git2::{Branch, ErrorCode, Repository, Sort, Status, StatusOptions, StatusShow};
+pub use file_config::{FileConfig, FileConfigError};
+use git2::{
+    Branch, BranchType, ErrorCode, FileMode, Repository, RepositoryState, Sort, Status,
+    StatusOptions, StatusShow,
+};
 use log::{error, info, warn};
 use regex::Regex;
-use statemachine::StepResult;
-use statemachine::{Action, Collector, Dispatcher};
+use signal_hook::consts::SIGINT;
+pub use statemachine::{
+    run, run_interruptible, run_with_interval, step, Action, ChangeKind, Collector, DirtyPolicy,
+    Dispatcher, StepResult,
+};
 use std::{
-    ffi::{CStr, CString, OsString},
+    ffi::{CString, OsString},
+    fmt,
+    path::PathBuf,
     process::Stdio,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
 };
+pub use timings::Timings;
 use ulid::Ulid;
 
 #[derive(thiserror::Error, Debug)]
 pub enum RepositoryStateError {
     #[error("commit inspection terminated")]
     CommitInspectionTerminated,
+    #[error("user.email is unset; set it so --protect-current-upstream can tell your commits from others'")]
+    UserEmailUnset,
     #[error("{0}")]
     InternalError(#[from] git2::Error),
 }
@@ -39,13 +63,6 @@ fn get_upstream_branch(reference: git2::Reference<'_>) -> Result<Option<Branch<'
     }
 }
 
-fn fnmatch(pat: &CStr, s: &CStr) -> bool {
-    let pat = pat.as_ptr();
-    let s = s.as_ptr();
-
-    unsafe { fnmatch_sys::fnmatch(pat, s, FNM_NOESCAPE) == 0 }
-}
-
 impl Collector for Application {
     type Error = RepositoryStateError;
 
@@ -67,8 +84,10 @@ impl Collector for Application {
 
         if let Some(branch) = head_ref.branch() {
             let config = self.repo.config()?;
-            let config_protected = config.get_string("dah.protectedbranch")
-                .map(Some)
+            let config_protected = match &self.file_config.protected_branches {
+                Some(patterns) => Ok(Some(patterns.join(":"))),
+                None => config.get_string("dah.protectedbranch").map(Some),
+            }
                 .or_else(|e| {
                     if e.code() == ErrorCode::NotFound {
                         warn!("dah.protectedbranch is unset; git-dah guesses the protected branch by this config");
@@ -117,6 +136,17 @@ impl Collector for Application {
         }
     }
 
+    fn ahead_behind(&self) -> Result<Option<(usize, usize)>, Self::Error> {
+        let head = self.repo.head()?;
+        let head_oid = head.peel_to_commit()?.id();
+        if let Some(upstream) = get_upstream_branch(head)? {
+            let upstream_oid = upstream.into_reference().peel_to_commit()?.id();
+            Ok(Some(self.repo.graph_ahead_behind(head_oid, upstream_oid)?))
+        } else {
+            Ok(None)
+        }
+    }
+
     fn is_based_on_remote(&self) -> Result<bool, Self::Error> {
         let head = self.repo.head()?;
         let head_oid = head.peel_to_commit()?.id();
@@ -124,6 +154,9 @@ impl Collector for Application {
             let upstream = upstream.into_reference();
             let upstream_commit = upstream.peel_to_commit()?;
             let upstream_head = upstream_commit.id();
+            let upstream_name = RemoteRef::new(upstream.name().unwrap_or_default())
+                .map(|r| r.display_safe())
+                .unwrap_or_default();
 
             // check oid of head and the remote head first.
             if upstream_head == head_oid {
@@ -136,8 +169,7 @@ impl Collector for Application {
             if self.allow_force_push {
                 info!(
                     "searching {}({}) from reflog of HEAD...",
-                    upstream.name().unwrap_or_default(),
-                    upstream_head
+                    upstream_name, upstream_head
                 );
                 let upstream_head_time = GitTime::from(upstream_commit.time());
                 for ent in self
@@ -168,8 +200,7 @@ impl Collector for Application {
 
             info!(
                 "searching {}({}) from history of HEAD...",
-                upstream.name().unwrap_or_default(),
-                upstream_head
+                upstream_name, upstream_head
             );
 
             let mut count = self.limit;
@@ -201,6 +232,19 @@ impl Collector for Application {
         Ok(false)
     }
 
+    fn has_foreign_upstream_commits(&self) -> Result<bool, Self::Error> {
+        if !self.protect_current_upstream {
+            return Ok(false);
+        }
+
+        let email = self
+            .repo
+            .signature()
+            .ok()
+            .and_then(|s| s.email().map(|e| e.to_owned()));
+        self.has_upstream_commits_by_others(email.as_deref())
+    }
+
     fn status(&self) -> Result<Status, Self::Error> {
         let statuses = self.repo.statuses(Some(
             StatusOptions::default().show(StatusShow::IndexAndWorkdir),
@@ -211,6 +255,46 @@ impl Collector for Application {
             .map(|st| st.status())
             .fold(Status::CURRENT, |a, b| a | b))
     }
+
+    fn allows_push_to_protected(&self) -> Result<bool, Self::Error> {
+        Ok(self.push_to_protected)
+    }
+
+    fn repository_state(&self) -> Result<RepositoryState, Self::Error> {
+        Ok(self.repo.state())
+    }
+
+    fn dirty_policy(&self) -> Result<DirtyPolicy, Self::Error> {
+        Ok(self.dirty_policy)
+    }
+
+    fn push_remote(&self) -> Result<Option<String>, Self::Error> {
+        Ok(self.push_remote.clone())
+    }
+
+    fn conflicted_paths(&self) -> Result<Vec<String>, Self::Error> {
+        let mut paths = Vec::new();
+        for conflict in self.repo.index()?.conflicts()? {
+            let conflict = conflict?;
+            if let Some(entry) = conflict.our.or(conflict.their).or(conflict.ancestor) {
+                paths.push(String::from_utf8_lossy(&entry.path).into_owned());
+            }
+        }
+        Ok(paths)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never,
 }
 
 pub struct Application {
@@ -219,6 +303,28 @@ pub struct Application {
     limit: usize,
     allow_force_push: bool,
     fetch_first: bool,
+    fetch_all: bool,
+    prune: bool,
+    no_verify: bool,
+    builtin_rebase: bool,
+    follow_tags: bool,
+    legacy_checkout: bool,
+    quiet_git: bool,
+    verify_push: bool,
+    auto_message: bool,
+    log_format: LogFormat,
+    protect_current_upstream: bool,
+    push_to_protected: bool,
+    push_remote: Option<String>,
+    color: ColorMode,
+    timings: bool,
+    interval: Duration,
+    warn_large: Option<u64>,
+    pre_stage: Option<String>,
+    trailers: Vec<Trailer>,
+    dirty_policy: DirtyPolicy,
+    no_submodule: bool,
+    file_config: FileConfig,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -232,6 +338,96 @@ pub enum ApplicationError {
     IO(#[from] std::io::Error),
     #[error("internal error: {0}")]
     Git(#[from] git2::Error),
+    #[error("generated branch name collided with existing branch {0} times")]
+    BranchNameCollision(usize),
+    #[error("no remote configured; add one with `git remote add`")]
+    NoRemote,
+    #[error("refused to run inside a submodule of {0:?}")]
+    InSubmodule(PathBuf),
+    #[error("interrupted")]
+    Interrupted,
+    #[error("{name} should not be negative, but got {value}")]
+    NegativeConfig { name: &'static str, value: i64 },
+    #[error("internal error: {0}")]
+    Refname(#[from] RefnameError),
+    #[error("{0}")]
+    RepositoryState(#[from] RepositoryStateError),
+}
+
+/// Trailer added to commit message, given like `Refs=PROJ-123`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trailer {
+    key: String,
+    value: String,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("trailer should be like KEY=VALUE with KEY of alphanumerics and `-`, but got {0:?}")]
+pub struct InvalidTrailer(String);
+
+impl FromStr for Trailer {
+    type Err = InvalidTrailer;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| InvalidTrailer(s.to_owned()))?;
+        let (key, value) = (key.trim(), value.trim());
+        let valid_key =
+            !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid_key || value.is_empty() || value.contains('\n') {
+            return Err(InvalidTrailer(s.to_owned()));
+        }
+        Ok(Trailer {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for Trailer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.value)
+    }
+}
+
+/// Times to generate branch name until it doesn't collide with existing branches.
+const BRANCH_NAME_ATTEMPTS: usize = 3;
+
+/// Default of `dah.branchmaxlen`, the maximum length of slug in generated branch names.
+const DEFAULT_BRANCH_MAX_LEN: usize = 50;
+
+/// Make commit subject into a part of branch name.
+///
+/// Slug longer than `max_len` characters is truncated at a word boundary if possible.
+/// `max_len` of 0 means unlimited.
+fn slugify(mesg: &str, max_len: usize) -> String {
+    let mesg = Regex::new(r#"\s+"#).unwrap().replace_all(mesg, "-");
+    let mesg = Regex::new(r#"[^-\w]"#).unwrap().replace_all(&mesg, "_");
+    let mesg = mesg.to_lowercase();
+    if max_len == 0 || mesg.chars().count() <= max_len {
+        return mesg;
+    }
+
+    let truncated: String = mesg.chars().take(max_len).collect();
+    // cut at the last word boundary unless the first word is already too long.
+    let truncated = match truncated.rfind('-') {
+        Some(i) if i > 0 && mesg[truncated.len()..].starts_with('-') => truncated.as_str(),
+        Some(i) if i > 0 => &truncated[..i],
+        _ => truncated.as_str(),
+    };
+    truncated.trim_end_matches(['-', '_']).to_owned()
+}
+
+/// Summarize changed paths into one line, like "Update a.rs, b.rs and 2 other files".
+fn format_commit_message(paths: &[String]) -> String {
+    match paths {
+        [] => "Update".to_owned(),
+        [a] => format!("Update {}", a),
+        [a, b] => format!("Update {} and {}", a, b),
+        [a, b, c] => format!("Update {}, {} and {}", a, b, c),
+        [a, b, rest @ ..] => format!("Update {}, {} and {} other files", a, b, rest.len()),
+    }
 }
 
 fn get_command_line(command: &std::process::Command) -> OsString {
@@ -251,6 +447,28 @@ impl Application {
             limit: 100,
             allow_force_push: true,
             fetch_first: true,
+            fetch_all: false,
+            prune: true,
+            no_verify: false,
+            builtin_rebase: false,
+            follow_tags: false,
+            legacy_checkout: false,
+            quiet_git: false,
+            verify_push: false,
+            auto_message: false,
+            log_format: LogFormat::Text,
+            protect_current_upstream: false,
+            push_to_protected: false,
+            push_remote: None,
+            color: ColorMode::Auto,
+            timings: false,
+            interval: Duration::ZERO,
+            warn_large: None,
+            pre_stage: None,
+            trailers: Vec::new(),
+            dirty_policy: DirtyPolicy::default(),
+            no_submodule: false,
+            file_config: FileConfig::default(),
         }
     }
 
@@ -276,242 +494,1201 @@ impl Application {
         }
     }
 
-    pub fn run(self) -> Result<(), Box<dyn std::error::Error>> {
-        env_logger::init();
-
-        if self.fetch_first {
-            if let Err(e) = self.run_command(std::process::Command::new("git").arg("fetch")) {
-                error!("fetch failed: {:?}; but we'll continue.", e);
-            }
-        }
+    /// Fetch all remotes, instead of the tracking one and the push remote only.
+    pub fn with_fetch_all(self, fetch_all: bool) -> Self {
+        Self { fetch_all, ..self }
+    }
 
-        loop {
-            let action = Action::new(&self)?;
-            match statemachine::step(action, &self)? {
-                StepResult::Stop => break,
-                StepResult::Continue => {
-                    if self.step {
-                        break;
-                    }
-                }
-            }
-        }
+    pub fn with_prune(self, prune: bool) -> Self {
+        Self { prune, ..self }
+    }
 
-        Ok(())
+    pub fn with_no_verify(self, no_verify: bool) -> Self {
+        Self { no_verify, ..self }
     }
 
-    fn generate_branch_name(&self) -> Result<String, ApplicationError> {
-        let head = self.repo.head()?;
-        let commit = head.peel_to_commit()?;
-        let mut branch_name = self
-            .repo
-            .config()?
-            .get_string("dah.branchprefix")
-            .or_else(|e| {
-                if e.code() == ErrorCode::NotFound {
-                    Ok(String::new())
-                } else {
-                    Err(e)
-                }
-            })?;
-
-        let mesg = commit.message().and_then(|m| m.lines().next());
-        if let Some(mesg) = mesg {
-            let mesg = Regex::new(r#"\s+"#).unwrap().replace_all(mesg, "-");
-            let mesg = Regex::new(r#"[^-\w]"#).unwrap().replace_all(&mesg, "_");
-            let mesg = mesg.to_lowercase();
-            branch_name.push_str(&mesg);
-            branch_name.push_str("-dah");
-        } else {
-            branch_name.push_str("dah");
+    pub fn with_builtin_rebase(self, builtin_rebase: bool) -> Self {
+        Self {
+            builtin_rebase,
+            ..self
         }
+    }
 
-        let mut random = Ulid::new().to_string();
-        random.make_ascii_lowercase();
-        branch_name.push_str(&random);
+    pub fn with_follow_tags(self, follow_tags: bool) -> Self {
+        Self {
+            follow_tags,
+            ..self
+        }
+    }
 
-        Ok(branch_name)
+    pub fn with_legacy_checkout(self, legacy_checkout: bool) -> Self {
+        Self {
+            legacy_checkout,
+            ..self
+        }
     }
 
-    fn new_git_push_command_with_force_options(&self) -> std::process::Command {
-        let mut cmd = std::process::Command::new("git");
-        cmd.arg("push");
+    pub fn with_quiet_git(self, quiet_git: bool) -> Self {
+        Self { quiet_git, ..self }
+    }
 
-        if self.allow_force_push {
-            cmd.arg("--force-with-lease").arg("--force-if-includes");
+    pub fn with_verify_push(self, verify_push: bool) -> Self {
+        Self {
+            verify_push,
+            ..self
         }
+    }
 
-        cmd
+    pub fn with_auto_message(self, auto_message: bool) -> Self {
+        Self {
+            auto_message,
+            ..self
+        }
     }
 
-    fn run_command(&self, command: &mut std::process::Command) -> Result<(), ApplicationError> {
-        let cmdline = get_command_line(command);
-        info!("invoking {:?}", cmdline);
+    pub fn with_log_format(self, log_format: LogFormat) -> Self {
+        Self { log_format, ..self }
+    }
 
-        command
-            .stdin(Stdio::inherit())
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit());
+    pub fn with_protect_current_upstream(self, protect_current_upstream: bool) -> Self {
+        Self {
+            protect_current_upstream,
+            ..self
+        }
+    }
 
-        let status = command.status()?;
+    pub fn with_push_to_protected(self, push_to_protected: bool) -> Self {
+        Self {
+            push_to_protected,
+            ..self
+        }
+    }
 
-        if status.success() {
-            Ok(())
-        } else {
-            Err(ApplicationError::ExitStatus {
-                command: cmdline,
-                code: status.code(),
-            })
+    pub fn with_push_remote(self, push_remote: Option<String>) -> Self {
+        Self {
+            push_remote,
+            ..self
         }
     }
-}
 
-impl Dispatcher for Application {
-    type Error = ApplicationError;
+    pub fn with_color(self, color: ColorMode) -> Self {
+        Self { color, ..self }
+    }
 
-    fn status(&self) -> Result<(), Self::Error> {
-        self.run_command(std::process::Command::new("git").arg("status"))
+    pub fn with_timings(self, timings: bool) -> Self {
+        Self { timings, ..self }
     }
 
-    fn create_branch_and_switch(&self) -> Result<(), Self::Error> {
-        let branch_name = self.generate_branch_name()?;
-        self.run_command(
-            std::process::Command::new("git")
-                .arg("switch")
-                .arg("-c")
-                .arg(branch_name),
-        )
+    pub fn with_interval(self, interval: Duration) -> Self {
+        Self { interval, ..self }
     }
 
-    fn rename_branch_and_switch(&self) -> Result<(), Self::Error> {
-        let branch_name = self.generate_branch_name()?;
-        self.run_command(
-            std::process::Command::new("git")
-                .arg("branch")
-                .arg("-m")
-                .arg(branch_name),
-        )
+    pub fn with_warn_large(self, warn_large: Option<u64>) -> Self {
+        Self { warn_large, ..self }
     }
 
-    fn stage_changes(&self) -> Result<(), Self::Error> {
-        self.run_command(std::process::Command::new("git").arg("add").arg("-u"))
+    pub fn with_pre_stage(self, pre_stage: Option<String>) -> Self {
+        Self { pre_stage, ..self }
     }
 
-    fn commit(&self) -> Result<(), Self::Error> {
-        self.run_command(std::process::Command::new("git").arg("commit"))
+    pub fn with_trailers(self, trailers: Vec<Trailer>) -> Self {
+        Self { trailers, ..self }
     }
 
-    fn pull_with_rebase(&self, upstream_ref: &str) -> Result<(), Self::Error> {
-        // TODO: receive RemoteRef
-        let upstream_ref = RemoteRef::new(upstream_ref).unwrap();
-        self.run_command(
-            std::process::Command::new("git")
-                .arg("pull")
-                .arg("--rebase")
-                .arg(upstream_ref.remote())
-                .arg(upstream_ref.branch()),
-        )
+    pub fn with_dirty_policy(self, dirty_policy: DirtyPolicy) -> Self {
+        Self {
+            dirty_policy,
+            ..self
+        }
     }
 
-    fn push(&self, head_ref: &str, upstream_ref: Option<&str>) -> Result<(), Self::Error> {
-        let head_ref = HeadRef::new(head_ref).unwrap();
-        if let Some(upstream_ref) = upstream_ref {
-            let upstream_ref = RemoteRef::new(upstream_ref).unwrap();
-            self.run_command(
-                self.new_git_push_command_with_force_options()
-                    .arg("-u")
-                    .arg(upstream_ref.remote())
-                    .arg(head_ref.branch().unwrap()),
-            )
-        } else {
-            self.run_command(
-                self.new_git_push_command_with_force_options()
-                    .arg("-u")
-                    .arg("origin")
-                    .arg(head_ref.branch().unwrap()),
-            )
+    pub fn with_no_submodule(self, no_submodule: bool) -> Self {
+        Self {
+            no_submodule,
+            ..self
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
+    /// Use `.dah.toml` for branch naming and protected branches in preference to git config.
+    pub fn with_file_config(self, file_config: FileConfig) -> Self {
+        Self {
+            file_config,
+            ..self
+        }
+    }
 
-    use git2::{
-        build::{CloneLocal, RepoBuilder},
-        ConfigLevel, ObjectType, Repository, Signature,
-    };
+    pub fn run(self) -> Result<(), Box<dyn std::error::Error>> {
+        match self.log_format {
+            LogFormat::Text => textlog::init(self.color),
+            LogFormat::Json => jsonlog::init()?,
+        }
 
-    use tempfile::TempDir;
-    use ulid::Ulid;
-    use url::Url;
+        // the check is only advisory unless --no-submodule, so failing to open the parent
+        // repository, like one owned by another user, should not make git-dah unusable.
+        let superproject = match self.superproject() {
+            Ok(superproject) => superproject,
+            Err(e) if !self.no_submodule => {
+                warn!("cannot tell if working in a submodule: {}; assuming not", e);
+                None
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(superproject) = superproject {
+            warn!(
+                "working in a submodule of {:?}; changes are pushed to the submodule's remote",
+                superproject
+            );
+            if self.no_submodule {
+                return Err(ApplicationError::InSubmodule(superproject).into());
+            }
+        }
 
-    use crate::app::dah::Application;
+        let timings = Timings::new(&self);
+        if self.fetch_first {
+            if let Err(e) = timings.measure("fetch", || {
+                self.run_command(&mut self.new_git_fetch_command()?)
+            }) {
+                error!("fetch failed: {:?}; but we'll continue.", e);
+            }
+        }
 
-    use super::{fnmatch, statemachine::Collector};
+        // first Ctrl-C stops before the next action, and second one exits immediately.
+        // the running git command gets SIGINT from the terminal as well, so it may not finish.
+        let interrupted = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register_conditional_shutdown(SIGINT, 130, Arc::clone(&interrupted))?;
+        signal_hook::flag::register(SIGINT, Arc::clone(&interrupted))?;
+
+        let result = statemachine::run_interruptible::<_, _, Box<dyn std::error::Error>, _>(
+            &timings,
+            &timings,
+            self.step,
+            self.interval,
+            thread::sleep,
+            &interrupted,
+        );
+        if self.timings {
+            timings.log();
+        }
+        // let scripts tell the run was cut short.
+        if result.is_ok() && interrupted.load(Ordering::Relaxed) {
+            return Err(ApplicationError::Interrupted.into());
+        }
+        result
+    }
 
-    #[test]
-    fn test_fnmatch() {
-        let cases = [(c"foo/*", c"foo/bar/baz")];
+    /// Work tree of the superproject, if the repository is checked out as its submodule.
+    pub fn superproject(&self) -> Result<Option<PathBuf>, ApplicationError> {
+        let Some(parent) = self.repo.workdir().and_then(|w| w.parent()) else {
+            return Ok(None);
+        };
+        let superproject = match Repository::discover(parent) {
+            Ok(repo) => repo,
+            Err(e) if e.code() == ErrorCode::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let (Some(workdir), Some(super_workdir)) = (self.repo.workdir(), superproject.workdir())
+        else {
+            return Ok(None);
+        };
+        let Ok(path) = workdir.strip_prefix(super_workdir) else {
+            return Ok(None);
+        };
+        // nested but independent repository is not a submodule.
+        let is_submodule = superproject
+            .submodules()?
+            .iter()
+            .any(|submodule| submodule.path() == path);
+        Ok(is_submodule.then(|| super_workdir.to_path_buf()))
+    }
+
+    /// Whether the remote tracking branch has commits not in HEAD and authored by
+    /// someone other than `email`, the user's email, which must be known.
+    fn has_upstream_commits_by_others(
+        &self,
+        email: Option<&str>,
+    ) -> Result<bool, RepositoryStateError> {
+        let head = self.repo.head()?;
+        let head_oid = head.peel_to_commit()?.id();
+        if let Some(upstream) = get_upstream_branch(head)? {
+            let upstream_oid = upstream.into_reference().peel_to_commit()?.id();
+            let email = email.ok_or(RepositoryStateError::UserEmailUnset)?;
+
+            let mut walk = self.repo.revwalk()?;
+            walk.push(upstream_oid)?;
+            walk.hide(head_oid)?;
 
-        for (pat, s) in cases {
-            assert!(fnmatch(pat, s))
+            for oid in walk.take(self.limit) {
+                let commit = self.repo.find_commit(oid?)?;
+                if commit.author().email() != Some(email) {
+                    info!(
+                        " * {} author={} is not included in HEAD",
+                        commit.id(),
+                        commit.author()
+                    );
+                    return Ok(true);
+                }
+            }
         }
+
+        Ok(false)
     }
 
-    #[test]
-    fn application_generate_branch_name() {
-        let tmpdir = TempDir::new().unwrap();
-        let repo = Repository::init_bare(tmpdir.path()).unwrap();
-        {
-            let author = Signature::now("foo", "foo@example.com").unwrap();
-            let tree = repo.treebuilder(None).unwrap();
-            let tree = tree.write().unwrap();
-            let tree = repo.find_tree(tree).unwrap();
-            repo.commit(
-                Some("refs/heads/main"),
-                &author,
-                &author,
-                "Initial commit",
-                &tree,
-                &[],
-            )
-            .unwrap();
-            repo.set_head("refs/heads/main").unwrap();
+    /// Tracked paths in the work tree with changes counted by the dirty policy.
+    fn paths_to_stage(&self) -> Result<Vec<String>, ApplicationError> {
+        let statuses = self
+            .repo
+            .statuses(Some(StatusOptions::default().show(StatusShow::Workdir)))?;
+        let mut paths = Vec::new();
+        for entry in statuses.iter() {
+            // untracked files are never staged, as `git add -u`.
+            if !self
+                .dirty_policy
+                .has_wt_change(entry.status() - Status::WT_NEW)
+            {
+                continue;
+            }
+            match entry.path() {
+                Some(path) => paths.push(path.to_owned()),
+                None => warn!(
+                    "not staging non-UTF-8 path: {}",
+                    escape_control(&String::from_utf8_lossy(entry.path_bytes()))
+                ),
+            }
         }
+        Ok(paths)
+    }
 
-        let app = Application::new(repo).with_step(true).with_limit(1);
-        let got = app.generate_branch_name().unwrap();
+    /// Changes staged to be committed.
+    fn staged_diff(&self) -> Result<git2::Diff<'_>, ApplicationError> {
+        // HEAD may have no commits yet (unborn branch)
+        let tree = match self.repo.head() {
+            Ok(head) => Some(head.peel_to_tree()?),
+            Err(e) if e.code() == ErrorCode::UnbornBranch || e.code() == ErrorCode::NotFound => {
+                None
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(self.repo.diff_tree_to_index(tree.as_ref(), None, None)?)
+    }
 
-        if let Some(ulid) = got.strip_prefix("initial-commit-dah") {
-            assert!(
-                Ulid::from_string(ulid).is_ok(),
-                "expected {:?} to have ULID suffix",
-                got
-            );
-        } else {
-            unreachable!(
-                "expected {:?} to have prefix {:?}",
-                got, "initial-commit-dah"
-            );
+    /// Staged files larger than `threshold` bytes, with their sizes.
+    fn large_staged_files(&self, threshold: u64) -> Result<Vec<(String, u64)>, ApplicationError> {
+        let diff = self.staged_diff()?;
+        let odb = self.repo.odb()?;
+        let mut files = Vec::new();
+        for delta in diff.deltas() {
+            let file = delta.new_file();
+            // deleted, or a submodule whose commit is not in this repository
+            if file.id().is_zero() || file.mode() == FileMode::Commit {
+                continue;
+            }
+            let (size, _) = odb.read_header(file.id())?;
+            let size = size as u64;
+            if size > threshold {
+                let path = file.path().map(|p| p.to_string_lossy().into_owned());
+                files.push((path.unwrap_or_default(), size));
+            }
         }
+        Ok(files)
     }
 
-    #[test]
-    fn application_generate_branch_name_prefixes_by_git_config_dah_branchprefix() {
-        let tmpdir = TempDir::new().unwrap();
-        let repo = Repository::init_bare(tmpdir.path()).unwrap();
-        repo.config()
-            .unwrap()
-            .open_level(ConfigLevel::Local)
-            .unwrap()
-            .set_str("dah.branchprefix", "feature/")
-            .unwrap();
+    fn generate_commit_message(&self) -> Result<String, ApplicationError> {
+        let diff = self.staged_diff()?;
+        let paths: Vec<String> = diff
+            .deltas()
+            .filter_map(|delta| {
+                delta
+                    .new_file()
+                    .path()
+                    .or(delta.old_file().path())
+                    .map(|p| p.to_string_lossy().into_owned())
+            })
+            .collect();
+        Ok(format_commit_message(&paths))
+    }
 
-        {
-            let author = Signature::now("foo", "foo@example.com").unwrap();
-            let tree = repo.treebuilder(None).unwrap();
-            let tree = tree.write().unwrap();
+    fn generate_branch_name(&self) -> Result<String, ApplicationError> {
+        // HEAD may have no commits yet (unborn branch)
+        let commit = match self.repo.head() {
+            Ok(head) => Some(head.peel_to_commit()?),
+            Err(e) if e.code() == ErrorCode::UnbornBranch || e.code() == ErrorCode::NotFound => {
+                None
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let config = self.repo.config()?;
+        let mut branch_name = match &self.file_config.branch_prefix {
+            Some(prefix) => prefix.clone(),
+            None => config.get_string("dah.branchprefix").or_else(|e| {
+                if e.code() == ErrorCode::NotFound {
+                    Ok(String::new())
+                } else {
+                    Err(e)
+                }
+            })?,
+        };
+        let max_len = match (
+            self.file_config.branch_max_len,
+            config.get_i64("dah.branchmaxlen"),
+        ) {
+            (Some(n), _) => n,
+            (None, Ok(n)) => usize::try_from(n).map_err(|_| ApplicationError::NegativeConfig {
+                name: "dah.branchmaxlen",
+                value: n,
+            })?,
+            (None, Err(e)) if e.code() == ErrorCode::NotFound => DEFAULT_BRANCH_MAX_LEN,
+            (None, Err(e)) => return Err(e.into()),
+        };
+
+        let mesg = commit
+            .as_ref()
+            .and_then(|c| c.message())
+            .and_then(|m| m.lines().next());
+        // slug may be empty, like for a subject of only punctuation truncated,
+        // and then the name should not start with "-" to be taken as an option.
+        match mesg.map(|mesg| slugify(mesg, max_len)) {
+            Some(slug) if !slug.is_empty() => {
+                branch_name.push_str(&slug);
+                branch_name.push_str("-dah");
+            }
+            _ => branch_name.push_str("dah"),
+        }
+
+        let mut random = Ulid::new().to_string();
+        random.make_ascii_lowercase();
+        branch_name.push_str(&random);
+
+        Ok(branch_name)
+    }
+
+    /// Generate branch name by `generate`, retrying while the name is already taken,
+    /// so that `git switch -c` or `git branch -m` won't fail.
+    fn unused_branch_name<F>(&self, mut generate: F) -> Result<String, ApplicationError>
+    where
+        F: FnMut() -> Result<String, ApplicationError>,
+    {
+        for _ in 0..BRANCH_NAME_ATTEMPTS {
+            let branch_name = generate()?;
+            match self.repo.find_branch(&branch_name, BranchType::Local) {
+                Ok(_) => warn!(
+                    "branch {} already exists; generating another name",
+                    branch_name
+                ),
+                Err(e) if e.code() == ErrorCode::NotFound => return Ok(branch_name),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(ApplicationError::BranchNameCollision(BRANCH_NAME_ATTEMPTS))
+    }
+
+    /// git command which works on the same working tree as `self.repo`,
+    /// even if it is a linked worktree or opened from `GIT_DIR`.
+    fn new_git_command(&self) -> std::process::Command {
+        let mut cmd = std::process::Command::new("git");
+        if let Some(workdir) = self.repo.workdir() {
+            cmd.current_dir(workdir);
+        }
+        cmd
+    }
+
+    /// Command creating a branch and switching to it.
+    ///
+    /// `git switch` is missing before git 2.23, so `git checkout -b` is used for legacy git instead.
+    fn new_git_create_branch_command(&self, branch_name: &str) -> std::process::Command {
+        let mut cmd = self.new_git_command();
+        if self.legacy_checkout {
+            cmd.arg("checkout").arg("-b");
+        } else {
+            cmd.arg("switch").arg("-c");
+        }
+        cmd.arg(branch_name);
+        cmd
+    }
+
+    /// Shell command to run at the top of the work tree, like git runs aliases.
+    fn new_shell_command(&self, command: &str) -> std::process::Command {
+        let mut cmd = std::process::Command::new("sh");
+        cmd.arg("-c").arg(command);
+        if let Some(workdir) = self.repo.workdir() {
+            cmd.current_dir(workdir);
+        }
+        cmd
+    }
+
+    /// Command fetching the remotes git-dah works with, or all with `--fetch-all`.
+    ///
+    /// Remotes are given explicitly, so that `fetch.all` in git config doesn't make
+    /// git contact remotes unrelated to HEAD, which may be slow or ask for credentials.
+    fn new_git_fetch_command(&self) -> Result<std::process::Command, ApplicationError> {
+        let mut cmd = self.new_git_command();
+        cmd.arg("fetch");
+        // remote tracking branches deleted on the remote should not be taken as upstream.
+        if self.prune {
+            cmd.arg("--prune");
+        }
+        if self.fetch_all {
+            cmd.arg("--all");
+        } else {
+            let remotes = self.fetch_remotes()?;
+            if remotes.len() > 1 {
+                cmd.arg("--multiple");
+            }
+            cmd.args(remotes);
+        }
+        Ok(cmd)
+    }
+
+    /// The remote HEAD tracks, or `origin` as git-fetch defaults to, and `--push-remote`.
+    fn fetch_remotes(&self) -> Result<Vec<String>, ApplicationError> {
+        // HEAD may be unborn, or its remote tracking branch may be pruned already.
+        let head = self.repo.find_reference("HEAD")?;
+        let tracking = match head.symbolic_target() {
+            Some(name) => match self.repo.branch_upstream_remote(name) {
+                Ok(remote) => remote.as_str().map(|s| s.to_owned()),
+                Err(e) if e.code() == ErrorCode::NotFound => None,
+                Err(e) => return Err(e.into()),
+            },
+            None => None,
+        };
+        let mut remotes = vec![tracking.unwrap_or_else(|| "origin".to_owned())];
+        if let Some(push_remote) = &self.push_remote {
+            if !remotes.contains(push_remote) {
+                remotes.push(push_remote.clone());
+            }
+        }
+        Ok(remotes)
+    }
+
+    fn new_git_commit_command(&self) -> std::process::Command {
+        let mut cmd = self.new_git_command();
+        cmd.arg("commit");
+
+        if self.no_verify {
+            cmd.arg("--no-verify");
+        }
+        for trailer in &self.trailers {
+            cmd.arg("--trailer").arg(trailer.to_string());
+        }
+
+        cmd
+    }
+
+    /// `git push` with options; force options are added only if `force` and force push is allowed.
+    fn new_git_push_command_with_options(&self, force: bool) -> std::process::Command {
+        let mut cmd = self.new_git_command();
+        cmd.arg("push");
+
+        if force && self.allow_force_push {
+            cmd.arg("--force-with-lease").arg("--force-if-includes");
+        }
+        if self.no_verify {
+            cmd.arg("--no-verify");
+        }
+        if self.follow_tags {
+            cmd.arg("--follow-tags");
+        }
+
+        cmd
+    }
+
+    /// Rebase HEAD onto the remote tracking branch with libgit2, instead of `git pull --rebase`.
+    ///
+    /// On conflict, the rebase is left in progress with the conflicted index,
+    /// so that the next action is resolving it.
+    fn rebase_with_libgit2(&self, upstream_ref: &RemoteRef) -> Result<(), ApplicationError> {
+        let head = self.repo.head()?;
+        let branch = self.repo.reference_to_annotated_commit(&head)?;
+        let upstream = self.repo.find_reference(upstream_ref.as_str())?;
+        let upstream = self.repo.reference_to_annotated_commit(&upstream)?;
+        let signature = self.repo.signature()?;
+
+        info!("rebasing onto {} with libgit2", upstream_ref.display_safe());
+        let mut rebase = self
+            .repo
+            .rebase(Some(&branch), Some(&upstream), None, None)?;
+        while let Some(op) = rebase.next() {
+            op?;
+            let index = self.repo.index()?;
+            if index.has_conflicts() {
+                for conflict in index.conflicts()? {
+                    let conflict = conflict?;
+                    if let Some(entry) = conflict.our.or(conflict.their).or(conflict.ancestor) {
+                        warn!("conflict: {}", String::from_utf8_lossy(&entry.path));
+                    }
+                }
+                return Ok(());
+            }
+            match rebase.commit(None, &signature, None) {
+                Ok(_) => {}
+                // the change is already in upstream
+                Err(e) if e.code() == ErrorCode::Applied => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        rebase.finish(Some(&signature))?;
+
+        Ok(())
+    }
+
+    /// Warn if pushing will overwrite commits on the remote branch.
+    fn warn_force_push(&self, upstream_ref: &RemoteRef) -> Result<(), ApplicationError> {
+        if !self.allow_force_push {
+            return Ok(());
+        }
+
+        let head_oid = self.repo.head()?.peel_to_commit()?.id();
+        let upstream_oid = self
+            .repo
+            .find_reference(upstream_ref.as_str())?
+            .peel_to_commit()?
+            .id();
+        if head_oid != upstream_oid && !self.repo.graph_descendant_of(head_oid, upstream_oid)? {
+            warn!(
+                "force pushing; commits on {} not included in HEAD will be overwritten",
+                upstream_ref.display_safe()
+            );
+        }
+
+        Ok(())
+    }
+
+    fn new_git_push_command(
+        &self,
+        head_ref: &HeadRef,
+        upstream_ref: Option<&RemoteRef>,
+        force: bool,
+    ) -> std::process::Command {
+        let mut cmd = self.new_git_push_command_with_options(force);
+        let tracking_remote = upstream_ref.map(|r| r.remote());
+        let remote = self.push_target(upstream_ref);
+
+        // don't overwrite upstream when pushing to the other remote than the tracking one,
+        // so that HEAD keeps tracking (and being rebased onto) the base branch.
+        if tracking_remote.is_none() || tracking_remote == Some(remote) {
+            if let Some(pushed_ref) = head_ref.tracking_ref(remote) {
+                if upstream_ref != Some(&pushed_ref) {
+                    info!(
+                        "{} will track {}",
+                        head_ref.display_safe(),
+                        pushed_ref.display_safe()
+                    );
+                }
+            }
+            cmd.arg("-u");
+        }
+        cmd.arg(remote).arg(head_ref.branch().unwrap());
+
+        cmd
+    }
+
+    /// Whether to capture output of git commit with `--quiet-git`;
+    /// the editor for the commit message needs the terminal unless `--auto-message`.
+    fn captures_commit_output(&self) -> bool {
+        self.quiet_git && self.auto_message
+    }
+
+    /// Remote to push HEAD to; `--push-remote`, the tracking remote or `origin`.
+    fn push_target<'a>(&'a self, upstream_ref: Option<&'a RemoteRef>) -> &'a str {
+        self.push_remote
+            .as_deref()
+            .or(upstream_ref.map(|r| r.remote()))
+            .unwrap_or("origin")
+    }
+
+    /// Check that the remote tracking branch updated by push points to HEAD,
+    /// warning if not, as misconfigured refspecs may make git push nothing successfully.
+    fn verify_pushed(&self, head_ref: &HeadRef, remote: &str) -> Result<bool, ApplicationError> {
+        let Some(pushed_ref) = head_ref.tracking_ref(remote) else {
+            return Ok(true);
+        };
+        let head_oid = self.repo.head()?.peel_to_commit()?.id();
+        let pushed_oid = match self.repo.find_reference(pushed_ref.as_str()) {
+            Ok(r) => Some(r.peel_to_commit()?.id()),
+            Err(e) if e.code() == ErrorCode::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        if pushed_oid == Some(head_oid) {
+            return Ok(true);
+        }
+        warn!(
+            "{} doesn't point to HEAD after push; check remote.{}.push and the output of git push",
+            pushed_ref.display_safe(),
+            remote
+        );
+        Ok(false)
+    }
+
+    fn run_command(&self, command: &mut std::process::Command) -> Result<(), ApplicationError> {
+        self.run_command_capturing(command, self.quiet_git)
+    }
+
+    fn run_command_capturing(
+        &self,
+        command: &mut std::process::Command,
+        capture: bool,
+    ) -> Result<(), ApplicationError> {
+        let cmdline = get_command_line(command);
+        info!("invoking {:?}", cmdline);
+
+        let output = self.spawn_command(command, capture)?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            // captured output is shown only on failure, to tell what went wrong.
+            for captured in [&output.stdout, &output.stderr] {
+                let captured = String::from_utf8_lossy(captured);
+                if !captured.trim().is_empty() {
+                    error!("{}", captured.trim_end());
+                }
+            }
+            Err(ApplicationError::ExitStatus {
+                command: cmdline,
+                code: output.status.code(),
+            })
+        }
+    }
+
+    /// Run the command to completion, capturing its stdout and stderr if `capture`.
+    ///
+    /// Otherwise the command inherits stdio and the captured output is empty.
+    fn spawn_command(
+        &self,
+        command: &mut std::process::Command,
+        capture: bool,
+    ) -> Result<std::process::Output, ApplicationError> {
+        command.stdin(Stdio::inherit());
+        if capture {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        } else {
+            command.stdout(Stdio::inherit()).stderr(Stdio::inherit());
+        }
+        Ok(command.output()?)
+    }
+}
+
+impl Dispatcher for Application {
+    type Error = ApplicationError;
+
+    fn status(&self) -> Result<(), Self::Error> {
+        self.run_command(self.new_git_command().arg("status"))
+    }
+
+    fn create_branch_and_switch(&self) -> Result<(), Self::Error> {
+        let branch_name = self.unused_branch_name(|| self.generate_branch_name())?;
+        self.run_command(&mut self.new_git_create_branch_command(&branch_name))
+    }
+
+    fn rename_branch_and_switch(&self) -> Result<(), Self::Error> {
+        let branch_name = self.unused_branch_name(|| self.generate_branch_name())?;
+        self.run_command(
+            self.new_git_command()
+                .arg("branch")
+                .arg("-m")
+                .arg(branch_name),
+        )
+    }
+
+    fn stage_changes(&self) -> Result<(), Self::Error> {
+        // changes made by the hook are staged together, as git-add reads the work tree afresh.
+        if let Some(pre_stage) = &self.pre_stage {
+            self.run_command(&mut self.new_shell_command(pre_stage))?;
+        }
+        if self.dirty_policy == DirtyPolicy::default() {
+            return self.run_command(self.new_git_command().arg("add").arg("-u"));
+        }
+
+        // stage only the kinds of changes counted, leaving ignored ones like typechanges.
+        let paths = self.paths_to_stage()?;
+        if paths.is_empty() {
+            return Ok(());
+        }
+        self.run_command(
+            self.new_git_command()
+                .env("GIT_LITERAL_PATHSPECS", "1")
+                .arg("add")
+                .arg("-u")
+                .arg("--")
+                .args(paths),
+        )
+    }
+
+    fn commit(&self) -> Result<(), Self::Error> {
+        if let Some(threshold) = self.warn_large {
+            for (path, size) in self.large_staged_files(threshold)? {
+                warn!(
+                    "committing large file: {} ({} bytes); unstage it with `git restore --staged` if unintended",
+                    escape_control(&path),
+                    size
+                );
+            }
+        }
+        let mut cmd = self.new_git_commit_command();
+        if self.auto_message {
+            cmd.arg("-m").arg(self.generate_commit_message()?);
+        }
+        self.run_command_capturing(&mut cmd, self.captures_commit_output())
+    }
+
+    fn pull_with_rebase(&self, upstream_ref: &str) -> Result<(), Self::Error> {
+        // TODO: receive RemoteRef
+        let upstream_ref = RemoteRef::new(upstream_ref)?;
+        if self.builtin_rebase {
+            return self.rebase_with_libgit2(&upstream_ref);
+        }
+        self.run_command(
+            self.new_git_command()
+                .arg("pull")
+                .arg("--rebase")
+                .arg(upstream_ref.remote())
+                .arg(upstream_ref.branch()),
+        )
+    }
+
+    fn push(&self, head_ref: &str, upstream_ref: Option<&str>) -> Result<(), Self::Error> {
+        let head_ref = HeadRef::new(head_ref)?;
+        let upstream_ref = upstream_ref.map(RemoteRef::new).transpose()?;
+        if self.repo.remotes()?.is_empty() {
+            return Err(ApplicationError::NoRemote);
+        }
+        // the default or protected branch is pushed only when it can be fast-forwarded.
+        let protected = Collector::default_branch(self)?.as_deref() == head_ref.branch()
+            || Collector::is_head_protected(self)?;
+        if let Some(upstream_ref) = upstream_ref.as_ref().filter(|_| !protected) {
+            // pushing to the other remote never overwrites the remote tracking branch.
+            let remote = self.push_remote.as_deref();
+            if remote.is_none_or(|remote| remote == upstream_ref.remote()) {
+                self.warn_force_push(upstream_ref)?;
+            }
+        }
+        self.run_command(&mut self.new_git_push_command(
+            &head_ref,
+            upstream_ref.as_ref(),
+            !protected,
+        ))?;
+        if self.verify_push {
+            self.verify_pushed(&head_ref, self.push_target(upstream_ref.as_ref()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::{fs, path::Path};
+
+    use git2::{
+        build::{CloneLocal, RepoBuilder},
+        ConfigLevel, ObjectType, Repository, RepositoryInitOptions, RepositoryState, Signature,
+        Status,
+    };
+
+    use tempfile::TempDir;
+    use ulid::Ulid;
+    use url::Url;
+
+    use crate::{
+        app::dah::{
+            format_commit_message, slugify, Application, ApplicationError, ChangeKind, DirtyPolicy,
+            FileConfig, InvalidTrailer, RepositoryStateError, Trailer,
+        },
+        git::{HeadRef, RefnameError, RemoteRef},
+    };
+
+    use super::statemachine::{Action, Collector, Dispatcher};
+    use super::Timings;
+
+    fn commit_file(repo: &Repository, path: &str, content: &str, message: &str) {
+        fs::write(repo.workdir().unwrap().join(path), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(path)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let author = Signature::now("foo", "foo@example.com").unwrap();
+        let parent = repo.head().ok().map(|h| h.peel_to_commit().unwrap());
+        repo.commit(
+            Some("HEAD"),
+            &author,
+            &author,
+            message,
+            &tree,
+            parent.as_ref().into_iter().collect::<Vec<_>>().as_slice(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn application_detects_submodule() {
+        let dir = TempDir::new().unwrap();
+        let upstream = Repository::init(dir.path().join("upstream")).unwrap();
+        commit_file(&upstream, "a.txt", "a\n", "1");
+        let mut url = Url::parse("file:///").unwrap();
+        url.set_path(upstream.workdir().unwrap().to_str().unwrap());
+
+        let superproject = Repository::init(dir.path().join("super")).unwrap();
+        commit_file(&superproject, "b.txt", "b\n", "1");
+        {
+            let mut submodule = superproject
+                .submodule(url.as_str(), Path::new("sub"), true)
+                .unwrap();
+            submodule.clone(None).unwrap();
+            submodule.add_finalize().unwrap();
+        }
+        // nested but not registered as a submodule
+        Repository::init(dir.path().join("super/nested")).unwrap();
+
+        let app = Application::new(Repository::open(dir.path().join("super/sub")).unwrap());
+        assert_eq!(
+            app.superproject().unwrap(),
+            Some(superproject.workdir().unwrap().to_path_buf())
+        );
+        let app = Application::new(Repository::open(dir.path().join("super/nested")).unwrap());
+        assert_eq!(app.superproject().unwrap(), None);
+        let app = Application::new(superproject);
+        assert_eq!(app.superproject().unwrap(), None);
+    }
+
+    /// Clone upstream having a.txt changed, then replace the change with local one to diverge.
+    fn clone_diverged(upstream_dir: &Path, local_path: &str) -> (TempDir, Repository) {
+        let upstream = Repository::init_opts(
+            upstream_dir,
+            RepositoryInitOptions::new().initial_head("main"),
+        )
+        .unwrap();
+        commit_file(&upstream, "a.txt", "base\n", "1");
+        commit_file(&upstream, "a.txt", "upstream\n", "2");
+
+        let mut url = Url::parse("file:///").unwrap();
+        url.set_path(upstream_dir.canonicalize().unwrap().to_str().unwrap());
+        let dir = TempDir::new().unwrap();
+        let repo = RepoBuilder::new().clone(url.as_str(), dir.path()).unwrap();
+        {
+            let mut config = repo
+                .config()
+                .unwrap()
+                .open_level(ConfigLevel::Local)
+                .unwrap();
+            config.set_str("user.name", "foo").unwrap();
+            config.set_str("user.email", "foo@example.com").unwrap();
+        }
+        {
+            let parent = repo
+                .head()
+                .unwrap()
+                .peel_to_commit()
+                .unwrap()
+                .parent(0)
+                .unwrap();
+            repo.reset(parent.as_object(), git2::ResetType::Hard, None)
+                .unwrap();
+        }
+        commit_file(&repo, local_path, "local\n", "local change");
+
+        (dir, repo)
+    }
+
+    #[test]
+    fn application_runs_pre_stage_hook() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file(&repo, "a.txt", "a\n", "1");
+        commit_file(&repo, "b.txt", "b\n", "2");
+        fs::write(dir.path().join("a.txt"), "unformatted\n").unwrap();
+
+        // the hook modifies the other tracked file too
+        let app = Application::new(repo).with_pre_stage(Some(
+            "tr a-z A-Z < a.txt > a.tmp && mv a.tmp a.txt && echo B > b.txt".to_owned(),
+        ));
+        Dispatcher::stage_changes(&app).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "UNFORMATTED\n"
+        );
+        assert_eq!(Action::new(&app).unwrap(), Action::Commit);
+        let index = app.repo.index().unwrap();
+        for (path, want) in [("a.txt", "UNFORMATTED\n"), ("b.txt", "B\n")] {
+            let entry = index.get_path(Path::new(path), 0).unwrap();
+            let blob = app.repo.find_blob(entry.id).unwrap();
+            assert_eq!(blob.content(), want.as_bytes(), "{}", path);
+        }
+
+        // failing hook aborts staging
+        fs::write(dir.path().join("a.txt"), "changed\n").unwrap();
+        let app = app.with_pre_stage(Some("exit 3".to_owned()));
+        assert!(matches!(
+            Dispatcher::stage_changes(&app),
+            Err(ApplicationError::ExitStatus { code: Some(3), .. })
+        ));
+        assert_eq!(Action::new(&app).unwrap(), Action::StageChanges);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn application_stages_only_counted_changes() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file(&repo, "a.txt", "a\n", "1");
+        commit_file(&repo, "b.txt", "b\n", "2");
+        fs::write(dir.path().join("a.txt"), "modified\n").unwrap();
+        fs::remove_file(dir.path().join("b.txt")).unwrap();
+        std::os::unix::fs::symlink("a.txt", dir.path().join("b.txt")).unwrap();
+
+        {
+            let mut config = repo
+                .config()
+                .unwrap()
+                .open_level(ConfigLevel::Local)
+                .unwrap();
+            config.set_str("user.name", "foo").unwrap();
+            config.set_str("user.email", "foo@example.com").unwrap();
+        }
+
+        let app = Application::new(repo)
+            .with_dirty_policy(DirtyPolicy::default().excluding(ChangeKind::Typechange))
+            .with_auto_message(true);
+        Dispatcher::stage_changes(&app).unwrap();
+        let status = |path: &str| app.repo.status_file(Path::new(path)).unwrap();
+        assert_eq!(status("a.txt"), Status::INDEX_MODIFIED);
+        assert_eq!(status("b.txt"), Status::WT_TYPECHANGE);
+
+        // the typechange is left out of the commit too
+        Dispatcher::commit(&app).unwrap();
+        assert_eq!(status("a.txt"), Status::CURRENT);
+        assert_eq!(status("b.txt"), Status::WT_TYPECHANGE);
+        let tree = app.repo.head().unwrap().peel_to_tree().unwrap();
+        assert_eq!(
+            tree.get_path(Path::new("b.txt")).unwrap().filemode(),
+            i32::from(git2::FileMode::Blob)
+        );
+    }
+
+    #[test]
+    fn application_finds_large_staged_files() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file(&repo, "small.txt", "a\n", "1");
+        commit_file(&repo, "committed.bin", &"x".repeat(4096), "2");
+
+        fs::write(dir.path().join("large.bin"), vec![0u8; 2048]).unwrap();
+        fs::write(dir.path().join("small.txt"), "ab\n").unwrap();
+        fs::write(dir.path().join("unstaged.bin"), vec![0u8; 2048]).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("large.bin")).unwrap();
+        index.add_path(Path::new("small.txt")).unwrap();
+        index.remove_path(Path::new("committed.bin")).unwrap();
+        index.write().unwrap();
+
+        let app = Application::new(repo).with_warn_large(Some(1024));
+        assert_eq!(
+            app.large_staged_files(1024).unwrap(),
+            vec![("large.bin".to_owned(), 2048)]
+        );
+        assert!(app.large_staged_files(4096).unwrap().is_empty());
+    }
+
+    #[test]
+    fn application_skips_staged_submodules_to_find_large_files() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file(&repo, "a.txt", "a\n", "1");
+
+        // gitlink names a commit in the submodule, not in this repository
+        let mut index = repo.index().unwrap();
+        index
+            .add(&git2::IndexEntry {
+                ctime: git2::IndexTime::new(0, 0),
+                mtime: git2::IndexTime::new(0, 0),
+                dev: 0,
+                ino: 0,
+                mode: 0o160000,
+                uid: 0,
+                gid: 0,
+                file_size: 0,
+                id: git2::Oid::from_str("0123456789abcdef0123456789abcdef01234567").unwrap(),
+                flags: 0,
+                flags_extended: 0,
+                path: b"sub".to_vec(),
+            })
+            .unwrap();
+        index.write().unwrap();
+
+        let app = Application::new(repo).with_warn_large(Some(1024));
+        assert!(app.large_staged_files(1024).unwrap().is_empty());
+    }
+
+    #[test]
+    fn timings_records_queries_and_commands() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit_file(&repo, "a.txt", "a\n", "1");
+        let app = Application::new(repo).with_timings(true);
+        let timings = Timings::new(&app);
+
+        assert!(matches!(
+            Action::new(&timings).unwrap(),
+            Action::Push {
+                upstream_ref: None,
+                ..
+            }
+        ));
+        // recorded even if failed
+        assert!(Dispatcher::push(&timings, "refs/heads/main", None).is_err());
+
+        let names: Vec<_> = timings.entries().into_iter().map(|(n, _)| n).collect();
+        for name in [
+            "default_branch",
+            "head_ref",
+            "status",
+            "repository_state",
+            "push",
+        ] {
+            assert!(names.contains(&name), "{} not in {:?}", name, names);
+        }
+    }
+
+    #[test]
+    fn application_protects_upstream_commits_by_others() {
+        let upstream_dir = TempDir::new().unwrap();
+        let (_dir, repo) = clone_diverged(upstream_dir.path(), "b.txt");
+        let app = Application::new(repo).with_protect_current_upstream(true);
+
+        // upstream commits are authored by foo@example.com, as user.email
+        assert!(!app.has_foreign_upstream_commits().unwrap());
+        assert!(!app
+            .has_upstream_commits_by_others(Some("foo@example.com"))
+            .unwrap());
+        assert!(app
+            .has_upstream_commits_by_others(Some("bar@example.com"))
+            .unwrap());
+
+        // refuse rather than guess whose commits they are
+        assert!(matches!(
+            app.has_upstream_commits_by_others(None),
+            Err(RepositoryStateError::UserEmailUnset)
+        ));
+    }
+
+    #[test]
+    fn application_builtin_rebase_stops_at_conflict() {
+        let upstream_dir = TempDir::new().unwrap();
+        let (_dir, repo) = clone_diverged(upstream_dir.path(), "a.txt");
+        let app = Application::new(repo).with_builtin_rebase(true);
+
+        Dispatcher::pull_with_rebase(&app, "refs/remotes/origin/main").unwrap();
+        assert_eq!(
+            Action::new(&app).unwrap(),
+            Action::ResolveConflict {
+                paths: vec!["a.txt".to_owned()]
+            }
+        );
+        assert_eq!(
+            app.repository_state().unwrap(),
+            RepositoryState::RebaseMerge
+        );
+    }
+
+    #[test]
+    fn application_builtin_rebase_finishes_without_conflict() {
+        let upstream_dir = TempDir::new().unwrap();
+        let (_dir, repo) = clone_diverged(upstream_dir.path(), "b.txt");
+        let app = Application::new(repo).with_builtin_rebase(true);
+
+        Dispatcher::pull_with_rebase(&app, "refs/remotes/origin/main").unwrap();
+        assert_eq!(app.repository_state().unwrap(), RepositoryState::Clean);
+        assert_eq!(app.head_ref().unwrap().branch(), Some("main"));
+        assert!(app.is_based_on_remote().unwrap());
+        let head = app.repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.message(), Some("local change"));
+        assert_eq!(head.parent(0).unwrap().message(), Some("2"));
+    }
+
+    #[test]
+    fn application_generate_branch_name() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init_bare(tmpdir.path()).unwrap();
+        {
+            let author = Signature::now("foo", "foo@example.com").unwrap();
+            let tree = repo.treebuilder(None).unwrap();
+            let tree = tree.write().unwrap();
+            let tree = repo.find_tree(tree).unwrap();
+            repo.commit(
+                Some("refs/heads/main"),
+                &author,
+                &author,
+                "Initial commit",
+                &tree,
+                &[],
+            )
+            .unwrap();
+            repo.set_head("refs/heads/main").unwrap();
+        }
+
+        let app = Application::new(repo).with_step(true).with_limit(1);
+        let got = app.generate_branch_name().unwrap();
+
+        if let Some(ulid) = got.strip_prefix("initial-commit-dah") {
+            assert!(
+                Ulid::from_string(ulid).is_ok(),
+                "expected {:?} to have ULID suffix",
+                got
+            );
+        } else {
+            unreachable!(
+                "expected {:?} to have prefix {:?}",
+                got, "initial-commit-dah"
+            );
+        }
+    }
+
+    #[test]
+    fn application_generate_branch_name_prefixes_by_dah_toml() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init(tmpdir.path()).unwrap();
+        repo.config()
+            .unwrap()
+            .open_level(ConfigLevel::Local)
+            .unwrap()
+            .set_str("dah.branchprefix", "feature/")
+            .unwrap();
+        commit_file(&repo, "a.txt", "a\n", "add something");
+        // .dah.toml takes precedence over git config
+        fs::write(
+            tmpdir.path().join(".dah.toml"),
+            "branch-prefix = \"team/\"\nbranch-max-len = 3\n",
+        )
+        .unwrap();
+
+        let file_config = FileConfig::load(&repo).unwrap();
+        let app = Application::new(repo).with_file_config(file_config);
+        let got = app.generate_branch_name().unwrap();
+
+        if let Some(ulid) = got.strip_prefix("team/add-dah") {
+            assert!(
+                Ulid::from_string(ulid).is_ok(),
+                "expected {:?} to have ULID suffix",
+                got
+            );
+        } else {
+            unreachable!("expected {:?} to have prefix {:?}", got, "team/add-dah");
+        }
+    }
+
+    #[test]
+    fn application_generate_branch_name_prefixes_by_git_config_dah_branchprefix() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init_bare(tmpdir.path()).unwrap();
+        repo.config()
+            .unwrap()
+            .open_level(ConfigLevel::Local)
+            .unwrap()
+            .set_str("dah.branchprefix", "feature/")
+            .unwrap();
+
+        {
+            let author = Signature::now("foo", "foo@example.com").unwrap();
+            let tree = repo.treebuilder(None).unwrap();
+            let tree = tree.write().unwrap();
             let tree = repo.find_tree(tree).unwrap();
             repo.commit(
                 Some("refs/heads/main"),
@@ -542,6 +1719,559 @@ mod tests {
         }
     }
 
+    #[test]
+    fn application_generate_branch_name_truncates_long_message() {
+        let mesg = "Refactor the whole application so that every component can be configured";
+        let cases = [
+            (50, "refactor-the-whole-application-so-that-every"),
+            (30, "refactor-the-whole-application"),
+            // boundary right after the limit
+            (8, "refactor"),
+            // first word is longer than the limit
+            (5, "refac"),
+            (
+                0,
+                "refactor-the-whole-application-so-that-every-component-can-be-configured",
+            ),
+        ];
+        for (max_len, want) in cases {
+            let got = slugify(mesg, max_len);
+            assert_eq!(got, want, "for max_len = {}", max_len);
+            assert!(max_len == 0 || got.chars().count() <= max_len);
+        }
+
+        // no trailing separator after truncation
+        assert_eq!(slugify("fix: foo", 4), "fix");
+        assert_eq!(slugify("short one", 50), "short-one");
+
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init_bare(tmpdir.path()).unwrap();
+        {
+            let author = Signature::now("foo", "foo@example.com").unwrap();
+            let tree = repo.treebuilder(None).unwrap();
+            let tree = tree.write().unwrap();
+            let tree = repo.find_tree(tree).unwrap();
+            repo.commit(Some("refs/heads/main"), &author, &author, mesg, &tree, &[])
+                .unwrap();
+            repo.set_head("refs/heads/main").unwrap();
+        }
+
+        let app = Application::new(repo);
+        let got = app.generate_branch_name().unwrap();
+        assert!(
+            got.starts_with("refactor-the-whole-application-so-that-every-dah"),
+            "{:?} should be truncated by default",
+            got
+        );
+
+        app.repo
+            .config()
+            .unwrap()
+            .open_level(ConfigLevel::Local)
+            .unwrap()
+            .set_i64("dah.branchmaxlen", 30)
+            .unwrap();
+        let got = app.generate_branch_name().unwrap();
+        assert!(
+            got.starts_with("refactor-the-whole-application-dah"),
+            "{:?} should be truncated by dah.branchmaxlen",
+            got
+        );
+
+        app.repo
+            .config()
+            .unwrap()
+            .open_level(ConfigLevel::Local)
+            .unwrap()
+            .set_i64("dah.branchmaxlen", -1)
+            .unwrap();
+        assert!(matches!(
+            app.generate_branch_name(),
+            Err(ApplicationError::NegativeConfig { value: -1, .. })
+        ));
+    }
+
+    #[test]
+    fn application_generate_branch_name_from_punctuation_only_message() {
+        assert_eq!(slugify("!!! ???", 3), "");
+
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init_bare(tmpdir.path()).unwrap();
+        {
+            let author = Signature::now("foo", "foo@example.com").unwrap();
+            let tree = repo.treebuilder(None).unwrap();
+            let tree = tree.write().unwrap();
+            let tree = repo.find_tree(tree).unwrap();
+            repo.commit(
+                Some("refs/heads/main"),
+                &author,
+                &author,
+                "!!! ???",
+                &tree,
+                &[],
+            )
+            .unwrap();
+            repo.set_head("refs/heads/main").unwrap();
+            let mut config = repo
+                .config()
+                .unwrap()
+                .open_level(ConfigLevel::Local)
+                .unwrap();
+            config.set_i64("dah.branchmaxlen", 3).unwrap();
+        }
+
+        let app = Application::new(repo);
+        let got = app.generate_branch_name().unwrap();
+        assert!(got.starts_with("dah"), "{:?}", got);
+    }
+
+    #[test]
+    fn application_generate_branch_name_on_detached_head() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init_bare(tmpdir.path()).unwrap();
+        {
+            let author = Signature::now("foo", "foo@example.com").unwrap();
+            let tree = repo.treebuilder(None).unwrap();
+            let tree = tree.write().unwrap();
+            let tree = repo.find_tree(tree).unwrap();
+            let oid = repo
+                .commit(None, &author, &author, "detached work", &tree, &[])
+                .unwrap();
+            repo.set_head_detached(oid).unwrap();
+        }
+
+        let app = Application::new(repo);
+        let got = app.generate_branch_name().unwrap();
+
+        if let Some(ulid) = got.strip_prefix("detached-work-dah") {
+            assert!(
+                Ulid::from_string(ulid).is_ok(),
+                "expected {:?} to have ULID suffix",
+                got
+            );
+        } else {
+            unreachable!(
+                "expected {:?} to have prefix {:?}",
+                got, "detached-work-dah"
+            );
+        }
+    }
+
+    #[test]
+    fn application_generate_branch_name_without_commits() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init_bare(tmpdir.path()).unwrap();
+
+        let app = Application::new(repo);
+        let got = app.generate_branch_name().unwrap();
+
+        if let Some(ulid) = got.strip_prefix("dah") {
+            assert!(
+                Ulid::from_string(ulid).is_ok(),
+                "expected {:?} to have ULID suffix",
+                got
+            );
+        } else {
+            unreachable!("expected {:?} to have prefix {:?}", got, "dah");
+        }
+    }
+
+    #[test]
+    fn application_retries_branch_name_on_collision() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init_bare(tmpdir.path()).unwrap();
+        {
+            let author = Signature::now("foo", "foo@example.com").unwrap();
+            let tree = repo.treebuilder(None).unwrap();
+            let tree = tree.write().unwrap();
+            let tree = repo.find_tree(tree).unwrap();
+            let oid = repo
+                .commit(Some("refs/heads/main"), &author, &author, "1", &tree, &[])
+                .unwrap();
+            let commit = repo.find_commit(oid).unwrap();
+            repo.branch("taken", &commit, false).unwrap();
+        }
+        let app = Application::new(repo);
+
+        let mut names = ["taken", "fresh"].into_iter();
+        let got = app.unused_branch_name(|| Ok(names.next().unwrap().to_owned()));
+        assert_eq!(got.unwrap(), "fresh");
+
+        let got = app.unused_branch_name(|| Ok("taken".to_owned()));
+        assert!(matches!(got, Err(ApplicationError::BranchNameCollision(3))));
+    }
+
+    #[test]
+    fn application_passes_no_verify_to_commit_and_push() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init_bare(tmpdir.path()).unwrap();
+        let app = Application::new(repo).with_no_verify(true);
+
+        let commit = app.new_git_commit_command();
+        let got: Vec<_> = commit.get_args().collect();
+        assert_eq!(got, vec!["commit", "--no-verify"]);
+
+        let push = app.new_git_push_command_with_options(true);
+        let got: Vec<_> = push.get_args().collect();
+        assert_eq!(
+            got,
+            vec![
+                "push",
+                "--force-with-lease",
+                "--force-if-includes",
+                "--no-verify"
+            ]
+        );
+
+        // protected branches are never force pushed
+        let push = app.new_git_push_command_with_options(false);
+        let got: Vec<_> = push.get_args().collect();
+        assert_eq!(got, vec!["push", "--no-verify"]);
+
+        let app = app.with_no_verify(false).with_allow_force_push(false);
+        let commit = app.new_git_commit_command();
+        let got: Vec<_> = commit.get_args().collect();
+        assert_eq!(got, vec!["commit"]);
+
+        let push = app.new_git_push_command_with_options(true);
+        let got: Vec<_> = push.get_args().collect();
+        assert_eq!(got, vec!["push"]);
+    }
+
+    #[test]
+    fn application_passes_trailers_to_commit() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init_bare(tmpdir.path()).unwrap();
+        let trailers = ["Refs=PROJ-123", " Reviewed-by = Alice <alice@example.com> "]
+            .into_iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let app = Application::new(repo)
+            .with_no_verify(true)
+            .with_trailers(trailers);
+
+        let commit = app.new_git_commit_command();
+        let got: Vec<_> = commit.get_args().collect();
+        assert_eq!(
+            got,
+            vec![
+                "commit",
+                "--no-verify",
+                "--trailer",
+                "Refs: PROJ-123",
+                "--trailer",
+                "Reviewed-by: Alice <alice@example.com>"
+            ]
+        );
+
+        for invalid in [
+            "Refs",
+            "=PROJ-123",
+            "Refs=",
+            "Re fs=PROJ-123",
+            "Refs:x=y",
+            "Refs=a\nb",
+        ] {
+            assert_eq!(
+                invalid.parse::<Trailer>(),
+                Err(InvalidTrailer(invalid.to_owned())),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn application_push_fails_without_remote() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init(tmpdir.path()).unwrap();
+        let app = Application::new(repo);
+
+        let got = Dispatcher::push(&app, "refs/heads/topic", None);
+        assert!(matches!(got, Err(ApplicationError::NoRemote)));
+    }
+
+    #[test]
+    fn application_rejects_malformed_refs_on_dispatch() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init(tmpdir.path()).unwrap();
+        let app = Application::new(repo);
+
+        let got = Dispatcher::push(&app, "topic", None);
+        assert!(matches!(
+            got,
+            Err(ApplicationError::Refname(
+                RefnameError::InvalidHeadRefFormat { .. }
+            ))
+        ));
+        let got = Dispatcher::push(&app, "refs/heads/topic", Some("refs/heads/main"));
+        assert!(matches!(
+            got,
+            Err(ApplicationError::Refname(
+                RefnameError::InvalidRemoteRefFormat { .. }
+            ))
+        ));
+        let got = Dispatcher::pull_with_rebase(&app, "origin/main");
+        assert!(matches!(
+            got,
+            Err(ApplicationError::Refname(
+                RefnameError::InvalidRemoteRefFormat { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn application_passes_follow_tags_to_push() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init_bare(tmpdir.path()).unwrap();
+        let app = Application::new(repo)
+            .with_allow_force_push(false)
+            .with_follow_tags(true);
+
+        let head_ref = HeadRef::new("refs/heads/topic").unwrap();
+        let push = app.new_git_push_command(&head_ref, None, true);
+        let got: Vec<_> = push.get_args().collect();
+        assert_eq!(got, vec!["push", "--follow-tags", "-u", "origin", "topic"]);
+
+        let app = app.with_follow_tags(false);
+        let push = app.new_git_push_command(&head_ref, None, true);
+        let got: Vec<_> = push.get_args().collect();
+        assert_eq!(got, vec!["push", "-u", "origin", "topic"]);
+    }
+
+    #[test]
+    fn application_captures_git_output_when_quiet() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init_bare(tmpdir.path()).unwrap();
+
+        let app = Application::new(repo).with_quiet_git(true);
+        let output = app
+            .spawn_command(app.new_git_command().arg("--version"), app.quiet_git)
+            .unwrap();
+        assert!(output.status.success());
+        assert!(output.stdout.starts_with(b"git version"));
+
+        let got = app.run_command(
+            app.new_git_command()
+                .args(["rev-parse", "--verify", "missing"]),
+        );
+        assert!(matches!(got, Err(ApplicationError::ExitStatus { .. })));
+
+        // the editor for commit message needs the terminal
+        assert!(!app.captures_commit_output());
+        let app = app.with_auto_message(true);
+        assert!(app.captures_commit_output());
+
+        let app = app.with_quiet_git(false);
+        let output = app
+            .spawn_command(app.new_git_command().arg("--version"), app.quiet_git)
+            .unwrap();
+        assert!(output.status.success());
+        assert!(output.stdout.is_empty());
+        assert!(!app.captures_commit_output());
+    }
+
+    #[test]
+    fn application_verifies_remote_tracking_branch_after_push() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init(tmpdir.path()).unwrap();
+        commit_file(&repo, "a.txt", "a", "first");
+        let pushed = repo.head().unwrap().peel_to_commit().unwrap().id();
+        commit_file(&repo, "a.txt", "b", "second");
+        let head_ref = HeadRef::new(repo.head().unwrap().name().unwrap()).unwrap();
+        let app = Application::new(repo).with_verify_push(true);
+
+        // push that did nothing leaves the remote tracking branch missing or behind
+        assert!(!app.verify_pushed(&head_ref, "origin").unwrap());
+        let tracking_ref = head_ref.tracking_ref("origin").unwrap();
+        app.repo
+            .reference(tracking_ref.as_str(), pushed, false, "stub push")
+            .unwrap();
+        assert!(!app.verify_pushed(&head_ref, "origin").unwrap());
+
+        let head = app.repo.head().unwrap().peel_to_commit().unwrap().id();
+        app.repo
+            .reference(tracking_ref.as_str(), head, true, "stub push")
+            .unwrap();
+        assert!(app.verify_pushed(&head_ref, "origin").unwrap());
+    }
+
+    #[test]
+    fn application_creates_branch_with_checkout_for_legacy_git() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init_bare(tmpdir.path()).unwrap();
+        let app = Application::new(repo);
+
+        let cmd = app.new_git_create_branch_command("topic");
+        let got: Vec<_> = cmd.get_args().collect();
+        assert_eq!(got, vec!["switch", "-c", "topic"]);
+
+        let app = app.with_legacy_checkout(true);
+        let cmd = app.new_git_create_branch_command("topic");
+        let got: Vec<_> = cmd.get_args().collect();
+        assert_eq!(got, vec!["checkout", "-b", "topic"]);
+    }
+
+    #[test]
+    fn application_generates_commit_message_from_staged_paths() {
+        let paths = |ps: &[&str]| ps.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        let cases = [
+            (paths(&["a.rs"]), "Update a.rs"),
+            (paths(&["a.rs", "b.rs"]), "Update a.rs and b.rs"),
+            (
+                paths(&["a.rs", "b.rs", "c.rs"]),
+                "Update a.rs, b.rs and c.rs",
+            ),
+            (
+                paths(&["a.rs", "b.rs", "c.rs", "d.rs"]),
+                "Update a.rs, b.rs and 2 other files",
+            ),
+        ];
+        for (given, want) in cases {
+            assert_eq!(format_commit_message(&given), want);
+        }
+
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init(tmpdir.path()).unwrap();
+        std::fs::write(tmpdir.path().join("a.rs"), "").unwrap();
+        std::fs::create_dir(tmpdir.path().join("src")).unwrap();
+        std::fs::write(tmpdir.path().join("src/b.rs"), "").unwrap();
+        {
+            let mut index = repo.index().unwrap();
+            index.add_path(std::path::Path::new("a.rs")).unwrap();
+            index.add_path(std::path::Path::new("src/b.rs")).unwrap();
+            index.write().unwrap();
+        }
+        let app = Application::new(repo);
+        assert_eq!(
+            app.generate_commit_message().unwrap(),
+            "Update a.rs and src/b.rs"
+        );
+    }
+
+    #[test]
+    fn application_pushes_to_push_remote() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init_bare(tmpdir.path()).unwrap();
+        let head_ref = HeadRef::new("refs/heads/topic").unwrap();
+        let upstream_ref = RemoteRef::new("refs/remotes/upstream/main").unwrap();
+        let app = Application::new(repo).with_allow_force_push(false);
+
+        // push to the tracking remote
+        let cmd = app.new_git_push_command(&head_ref, Some(&upstream_ref), true);
+        let got: Vec<_> = cmd.get_args().collect();
+        assert_eq!(got, vec!["push", "-u", "upstream", "topic"]);
+
+        // no tracking remote
+        let cmd = app.new_git_push_command(&head_ref, None, true);
+        let got: Vec<_> = cmd.get_args().collect();
+        assert_eq!(got, vec!["push", "-u", "origin", "topic"]);
+
+        let app = app.with_push_remote(Some("myfork".to_owned()));
+
+        // push to fork, keep tracking upstream
+        let cmd = app.new_git_push_command(&head_ref, Some(&upstream_ref), true);
+        let got: Vec<_> = cmd.get_args().collect();
+        assert_eq!(got, vec!["push", "myfork", "topic"]);
+
+        // no tracking remote; track the fork
+        let cmd = app.new_git_push_command(&head_ref, None, true);
+        let got: Vec<_> = cmd.get_args().collect();
+        assert_eq!(got, vec!["push", "-u", "myfork", "topic"]);
+    }
+
+    #[test]
+    fn application_fetches_only_relevant_remotes() {
+        let upstream_dir = TempDir::new().unwrap();
+        let (dir, repo) = clone_diverged(upstream_dir.path(), "b.txt");
+        let missing = dir.path().join("no-such-remote");
+        repo.remote("unrelated", missing.to_str().unwrap()).unwrap();
+        repo.config()
+            .unwrap()
+            .open_level(ConfigLevel::Local)
+            .unwrap()
+            .set_bool("fetch.all", true)
+            .unwrap();
+        let app = Application::new(repo);
+
+        // the unrelated remote, which cannot be fetched, is never contacted
+        let mut fetch = app.new_git_fetch_command().unwrap();
+        let got: Vec<_> = fetch.get_args().collect();
+        assert_eq!(got, vec!["fetch", "--prune", "origin"]);
+        app.run_command(&mut fetch).unwrap();
+
+        let app = app.with_push_remote(Some("unrelated".to_owned()));
+        let fetch = app.new_git_fetch_command().unwrap();
+        let got: Vec<_> = fetch.get_args().collect();
+        assert_eq!(
+            got,
+            vec!["fetch", "--prune", "--multiple", "origin", "unrelated"]
+        );
+
+        let app = app.with_push_remote(None).with_fetch_all(true);
+        let mut fetch = app.new_git_fetch_command().unwrap();
+        let got: Vec<_> = fetch.get_args().collect();
+        assert_eq!(got, vec!["fetch", "--prune", "--all"]);
+        assert!(app.run_command(&mut fetch).is_err());
+    }
+
+    #[test]
+    fn application_fetch_prunes_deleted_upstream() {
+        let upstream_repo = TempDir::new().unwrap();
+        let upstream_repo_path = upstream_repo.path();
+        let upstream_repo = Repository::init_bare(upstream_repo_path).unwrap();
+        {
+            let author = Signature::now("foo", "foo@example.com").unwrap();
+            let tree = upstream_repo.treebuilder(None).unwrap();
+            let tree = tree.write().unwrap();
+            let tree = upstream_repo.find_tree(tree).unwrap();
+            let c1 = upstream_repo
+                .commit(None, &author, &author, "1", &tree, &[])
+                .unwrap();
+            let c1 = upstream_repo.find_commit(c1).unwrap();
+            upstream_repo.branch("main", &c1, true).unwrap();
+            upstream_repo.branch("topic", &c1, true).unwrap();
+            upstream_repo.set_head("refs/heads/main").unwrap();
+        }
+
+        let mut upstream_repo_url = Url::parse("file:///").unwrap();
+        upstream_repo_url.set_path(upstream_repo_path.canonicalize().unwrap().to_str().unwrap());
+
+        let dir = TempDir::new().unwrap();
+        let repo = RepoBuilder::new()
+            .bare(false)
+            .clone(upstream_repo_url.as_str(), dir.path())
+            .unwrap();
+        {
+            let head = repo.head().unwrap().peel_to_commit().unwrap();
+            let mut topic = repo.branch("topic", &head, false).unwrap();
+            topic.set_upstream(Some("origin/topic")).unwrap();
+            repo.set_head("refs/heads/topic").unwrap();
+        }
+
+        // topic branch is deleted on the remote
+        upstream_repo
+            .find_branch("topic", git2::BranchType::Local)
+            .unwrap()
+            .delete()
+            .unwrap();
+
+        let app = Application::new(repo).with_prune(false);
+        app.run_command(&mut app.new_git_fetch_command().unwrap())
+            .unwrap();
+        assert_eq!(
+            app.upstream_ref().unwrap().map(|r| r.as_str().to_owned()),
+            Some("refs/remotes/origin/topic".to_owned())
+        );
+
+        let app = app.with_prune(true);
+        let mut fetch = app.new_git_fetch_command().unwrap();
+        let got: Vec<_> = fetch.get_args().collect();
+        assert_eq!(got, vec!["fetch", "--prune", "origin"]);
+        app.run_command(&mut fetch).unwrap();
+        assert_eq!(app.upstream_ref().unwrap(), None);
+    }
+
     #[test]
     fn application_default_branch_returns_git_config_init_defaultbranch(
     ) -> Result<(), Box<dyn std::error::Error>> {
@@ -766,4 +2496,84 @@ mod tests {
             .is_based_on_remote()
             .unwrap());
     }
+
+    #[test]
+    fn application_collects_state_in_linked_worktree() {
+        let upstream_repo = TempDir::new().unwrap();
+        let upstream_repo_path = upstream_repo.path();
+        let upstream_repo = Repository::init_bare(upstream_repo_path).unwrap();
+        {
+            let author = Signature::now("foo", "foo@example.com").unwrap();
+            let tree = upstream_repo.treebuilder(None).unwrap();
+            let tree = tree.write().unwrap();
+            let tree = upstream_repo.find_tree(tree).unwrap();
+            let c1 = upstream_repo
+                .commit(None, &author, &author, "1", &tree, &[])
+                .unwrap();
+            let c1 = upstream_repo.find_commit(c1).unwrap();
+            upstream_repo.branch("main", &c1, true).unwrap();
+            upstream_repo.set_head("refs/heads/main").unwrap();
+        }
+
+        let mut upstream_repo_url = Url::parse("file:///").unwrap();
+        upstream_repo_url.set_path(upstream_repo_path.canonicalize().unwrap().to_str().unwrap());
+
+        let main_dir = TempDir::new().unwrap();
+        let main_repo = RepoBuilder::new()
+            .bare(false)
+            .clone(upstream_repo_url.as_str(), main_dir.path())
+            .unwrap();
+        main_repo
+            .config()
+            .unwrap()
+            .open_level(ConfigLevel::Local)
+            .unwrap()
+            .set_str("init.defaultbranch", "main")
+            .unwrap();
+
+        // create topic branch tracking origin/main, then check it out in a linked worktree
+        let head = main_repo.head().unwrap().peel_to_commit().unwrap();
+        let mut topic = main_repo.branch("topic", &head, false).unwrap();
+        topic.set_upstream(Some("origin/main")).unwrap();
+        let wt_dir = TempDir::new().unwrap();
+        let wt_path = wt_dir.path().join("wt");
+        let topic_ref = topic.into_reference();
+        main_repo
+            .worktree(
+                "wt",
+                &wt_path,
+                Some(git2::WorktreeAddOptions::new().reference(Some(&topic_ref))),
+            )
+            .unwrap();
+
+        let repo = Repository::open(&wt_path).unwrap();
+        {
+            let author = Signature::now("foo", "foo@example.com").unwrap();
+            let tree = repo.treebuilder(None).unwrap();
+            let tree = tree.write().unwrap();
+            let tree = repo.find_tree(tree).unwrap();
+            let head = repo.head().unwrap().peel_to_commit().unwrap();
+            repo.commit(Some("HEAD"), &author, &author, "wt", &tree, &[&head])
+                .unwrap();
+        }
+
+        let app = Application::new(repo);
+        assert_eq!(app.default_branch().unwrap().as_deref(), Some("main"));
+        assert_eq!(app.head_ref().unwrap().branch(), Some("topic"));
+        assert_eq!(
+            app.upstream_ref().unwrap().map(|r| r.as_str().to_owned()),
+            Some("refs/remotes/origin/main".to_owned())
+        );
+        assert!(!app.is_synchronized().unwrap());
+        assert_eq!(app.ahead_behind().unwrap(), Some((1, 0)));
+        assert!(app.is_based_on_remote().unwrap());
+        assert!(!app.is_head_protected().unwrap());
+
+        // git commands should work on the linked worktree, not on the current directory.
+        let cmd = app.new_git_push_command_with_options(true);
+        assert_eq!(
+            cmd.get_current_dir().map(|p| p.canonicalize().unwrap()),
+            Some(wt_path.canonicalize().unwrap())
+        );
+    }
 }
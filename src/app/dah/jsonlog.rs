@@ -0,0 +1,95 @@
+use std::io::Write;
+
+use env_logger::{Builder, WriteStyle};
+use log::SetLoggerError;
+
+use crate::json::escape;
+
+/// env_logger builder which emits each record as a line of JSON object,
+/// like `{"level":"INFO","target":"...","message":"..."}`.
+///
+/// Filters are taken from `RUST_LOG` with env_logger's syntax, like `info` or
+/// `warn,git_toolbox::app::dah=debug`, and default to `error`.
+pub fn builder() -> Builder {
+    let mut builder = Builder::from_default_env();
+    json_format(&mut builder);
+    builder
+}
+
+/// Make `builder` emit records as JSON without colors.
+fn json_format(builder: &mut Builder) -> &mut Builder {
+    builder.write_style(WriteStyle::Never);
+    builder.format(|buf, record| {
+        writeln!(
+            buf,
+            r#"{{"level":"{}","target":"{}","message":"{}"}}"#,
+            record.level(),
+            escape(record.target()),
+            escape(&record.args().to_string())
+        )
+    })
+}
+
+/// Install the JSON logger writing to stderr.
+pub fn init() -> Result<(), SetLoggerError> {
+    builder().try_init()
+}
+
+#[cfg(test)]
+mod tests {
+    use env_logger::{Builder, Target};
+    use log::{Level, Log, Record};
+
+    use super::json_format;
+    use crate::app::dah::testlog::SharedBuf;
+
+    #[test]
+    fn test_json_logger() {
+        let buf = SharedBuf::default();
+        // not from the environment, where RUST_LOG may add filters
+        let logger = json_format(&mut Builder::new())
+            .parse_filters("warn,git_toolbox::app::dah=info")
+            .target(Target::Pipe(Box::new(buf.clone())))
+            .build();
+
+        logger.log(
+            &Record::builder()
+                .level(Level::Info)
+                .target("git_toolbox::app::dah")
+                .args(format_args!("invoking {:?}", "git push"))
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .level(Level::Warn)
+                .target("git_toolbox::app::dah")
+                .args(format_args!("line1\nline2\t\\"))
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .level(Level::Debug)
+                .target("git_toolbox::app::dah")
+                .args(format_args!("filtered out"))
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .level(Level::Info)
+                .target("git_toolbox::git")
+                .args(format_args!("filtered out by module"))
+                .build(),
+        );
+
+        let got = buf.contents();
+        assert_eq!(
+            got,
+            concat!(
+                r#"{"level":"INFO","target":"git_toolbox::app::dah","message":"invoking \"git push\""}"#,
+                "\n",
+                r#"{"level":"WARN","target":"git_toolbox::app::dah","message":"line1\nline2\t\\"}"#,
+                "\n",
+            )
+        );
+    }
+}
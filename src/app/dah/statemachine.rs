@@ -1,12 +1,22 @@
-use git2::Status;
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use git2::{RepositoryState, Status};
 use log::{info, warn};
 
-use crate::git::{HeadRef, RemoteRef};
+use crate::git::{escape_control, HeadRef, RemoteRef};
 
 #[derive(Debug, PartialEq)]
 pub enum Action {
     None,
-    ResolveConflict,
+    ResolveConflict {
+        paths: Vec<String>,
+    },
+    FinishOperation {
+        state: RepositoryState,
+    },
     CreateBranch,
     RenameBranch,
     StageChanges,
@@ -21,6 +31,90 @@ pub enum Action {
     },
 }
 
+/// Kind of change in index or work tree, which can be ignored by `DirtyPolicy`.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum ChangeKind {
+    New,
+    Modified,
+    Deleted,
+    Renamed,
+    Typechange,
+}
+
+impl ChangeKind {
+    fn wt(self) -> Status {
+        match self {
+            Self::New => Status::WT_NEW,
+            Self::Modified => Status::WT_MODIFIED,
+            Self::Deleted => Status::WT_DELETED,
+            Self::Renamed => Status::WT_RENAMED,
+            Self::Typechange => Status::WT_TYPECHANGE,
+        }
+    }
+
+    fn index(self) -> Status {
+        match self {
+            Self::New => Status::INDEX_NEW,
+            Self::Modified => Status::INDEX_MODIFIED,
+            Self::Deleted => Status::INDEX_DELETED,
+            Self::Renamed => Status::INDEX_RENAMED,
+            Self::Typechange => Status::INDEX_TYPECHANGE,
+        }
+    }
+}
+
+/// Status bits of work tree and index counted as changes to stage or commit.
+///
+/// By default, any new, modified, deleted, renamed or typechanged file counts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirtyPolicy {
+    wt: Status,
+    index: Status,
+}
+
+impl Default for DirtyPolicy {
+    fn default() -> Self {
+        Self {
+            wt: Status::WT_NEW
+                | Status::WT_MODIFIED
+                | Status::WT_DELETED
+                | Status::WT_RENAMED
+                | Status::WT_TYPECHANGE,
+            index: Status::INDEX_NEW
+                | Status::INDEX_MODIFIED
+                | Status::INDEX_DELETED
+                | Status::INDEX_RENAMED
+                | Status::INDEX_TYPECHANGE,
+        }
+    }
+}
+
+impl DirtyPolicy {
+    /// Count `kind` of change in work tree and index.
+    pub fn including(self, kind: ChangeKind) -> Self {
+        Self {
+            wt: self.wt | kind.wt(),
+            index: self.index | kind.index(),
+        }
+    }
+
+    /// Don't count `kind` of change in work tree and index.
+    pub fn excluding(self, kind: ChangeKind) -> Self {
+        Self {
+            wt: self.wt - kind.wt(),
+            index: self.index - kind.index(),
+        }
+    }
+
+    pub fn has_wt_change(&self, status: Status) -> bool {
+        status.intersects(self.wt)
+    }
+
+    pub fn has_index_change(&self, status: Status) -> bool {
+        status.intersects(self.index)
+    }
+}
+
 /// Group of methods to collect repository state,
 /// for deciding the next action.
 pub trait Collector {
@@ -42,14 +136,51 @@ pub trait Collector {
     ///
     /// For HEAD without remote tracking branch, should return `Ok(false)`.
     fn is_synchronized(&self) -> Result<bool, Self::Error>;
+    /// Count commits HEAD is ahead of and behind its remote tracking branch.
+    ///
+    /// For HEAD without remote tracking branch, should return `Ok(None)`.
+    fn ahead_behind(&self) -> Result<Option<(usize, usize)>, Self::Error>;
     /// Check if commits on the branch pointed by head_ref are at the top of
     /// the remote tracking branch (upstream_ref). i.e., HEAD is already
     /// rebased onto upstream_ref.
     ///
     /// For HEAD without remote tracking branch, should return `Ok(false)`.
     fn is_based_on_remote(&self) -> Result<bool, Self::Error>;
+    /// Check if the remote tracking branch has commits authored by others,
+    /// which are not included in HEAD and so will be lost by force push.
+    ///
+    /// For HEAD without remote tracking branch, or when this guard is disabled,
+    /// should return `Ok(false)`.
+    fn has_foreign_upstream_commits(&self) -> Result<bool, Self::Error>;
+    /// Check if local commits on the default or protected branch may be pushed
+    /// to its remote tracking branch, instead of being moved onto a new branch.
+    ///
+    /// Should return `Ok(false)` unless explicitly allowed.
+    fn allows_push_to_protected(&self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
     /// Merged status of current index and work tree.
     fn status(&self) -> Result<Status, Self::Error>;
+    /// Operation in progress like merge, rebase or cherry-pick.
+    ///
+    /// Defaults to `RepositoryState::Clean`.
+    fn repository_state(&self) -> Result<RepositoryState, Self::Error> {
+        Ok(RepositoryState::Clean)
+    }
+    /// Which bits of `status` count as changes to stage or commit.
+    fn dirty_policy(&self) -> Result<DirtyPolicy, Self::Error> {
+        Ok(DirtyPolicy::default())
+    }
+    /// Remote to push to instead of the one of the remote tracking branch.
+    ///
+    /// Should return `Ok(None)` unless configured.
+    fn push_remote(&self) -> Result<Option<String>, Self::Error> {
+        Ok(None)
+    }
+    /// Paths having conflicts in the index, to tell the user which ones to resolve.
+    fn conflicted_paths(&self) -> Result<Vec<String>, Self::Error> {
+        Ok(Vec::new())
+    }
 }
 
 impl Action {
@@ -61,20 +192,20 @@ impl Action {
         let head_ref = collector.head_ref()?;
         let upstream_ref = collector.upstream_ref()?;
         let status = collector.status()?;
+        let policy = collector.dirty_policy()?;
 
-        let has_wt_change = status.is_wt_new()
-            || status.is_wt_modified()
-            || status.is_wt_deleted()
-            || status.is_wt_renamed()
-            || status.is_wt_typechange();
-        let has_index_change = status.is_index_new()
-            || status.is_index_modified()
-            || status.is_index_deleted()
-            || status.is_index_renamed()
-            || status.is_index_typechange();
+        let has_wt_change = policy.has_wt_change(status);
+        let has_index_change = policy.has_index_change(status);
 
         if status.is_conflicted() {
-            return Ok(Self::ResolveConflict);
+            let paths = collector.conflicted_paths()?;
+            return Ok(Self::ResolveConflict { paths });
+        }
+        // staging or committing in the middle of merge etc. would conclude it
+        // with unintended contents, so leave it to the user.
+        let state = collector.repository_state()?;
+        if state != RepositoryState::Clean {
+            return Ok(Self::FinishOperation { state });
         }
         if has_wt_change {
             return Ok(Self::StageChanges);
@@ -84,20 +215,55 @@ impl Action {
         }
 
         if let Some(head_branch) = head_ref.branch() {
+            if let Some(upstream_ref) = &upstream_ref {
+                if let Some((ahead, behind)) = collector.ahead_behind()? {
+                    info!(
+                        "{} is {} commit(s) ahead of and {} commit(s) behind {}",
+                        head_ref.display_safe(),
+                        ahead,
+                        behind,
+                        upstream_ref.display_safe()
+                    );
+                }
+            }
             if collector.is_synchronized()? {
                 return Ok(Self::None);
             }
             if let Some(true) = default_branch.map(|b| head_branch == b) {
                 info!("found local commits on default branch");
-                return Ok(Self::RenameBranch);
+                return Self::rename_or_push(collector, head_ref, upstream_ref);
             }
             if collector.is_head_protected()? {
                 info!("found local commits on default or protected branch");
-                return Ok(Self::RenameBranch);
+                return Self::rename_or_push(collector, head_ref, upstream_ref);
             }
 
             if let Some(upstream_ref) = upstream_ref {
+                // HEAD keeps being rebased onto its remote tracking branch, while pushed to the other remote,
+                // where others' commits on the remote tracking branch are never overwritten.
+                let push_remote = collector.push_remote()?;
+                let pushes_elsewhere = push_remote
+                    .as_deref()
+                    .is_some_and(|remote| remote != upstream_ref.remote());
+                if pushes_elsewhere {
+                    info!(
+                        "{} tracks {} but is pushed to {}; rebase onto the former, push to the latter",
+                        head_ref.display_safe(),
+                        upstream_ref.display_safe(),
+                        push_remote.as_deref().unwrap_or_default()
+                    );
+                }
                 if collector.is_based_on_remote()? {
+                    if !pushes_elsewhere && collector.has_foreign_upstream_commits()? {
+                        warn!(
+                            "{} has commits by others; rebase instead of overwriting them",
+                            upstream_ref.display_safe()
+                        );
+                        return Ok(Self::Rebase {
+                            head_ref,
+                            upstream_ref,
+                        });
+                    }
                     return Ok(Self::Push {
                         head_ref,
                         upstream_ref: Some(upstream_ref),
@@ -118,6 +284,36 @@ impl Action {
         // detached HEAD
         Ok(Self::CreateBranch)
     }
+
+    /// Decide action for local commits on the default or protected branch.
+    ///
+    /// Renaming takes precedence unless pushing to the protected branch is allowed
+    /// and HEAD is strictly ahead of its remote tracking branch, i.e. it can be fast-forwarded.
+    /// `is_based_on_remote` is not enough, as it may follow the reflog over an amend or reset.
+    fn rename_or_push<T>(
+        collector: &T,
+        head_ref: HeadRef,
+        upstream_ref: Option<RemoteRef>,
+    ) -> Result<Self, T::Error>
+    where
+        T: Collector,
+    {
+        if let Some(upstream_ref) = upstream_ref {
+            if collector.allows_push_to_protected()?
+                && matches!(collector.ahead_behind()?, Some((ahead, 0)) if ahead > 0)
+            {
+                info!(
+                    "local commits are on top of {}; push them instead of renaming",
+                    upstream_ref.display_safe()
+                );
+                return Ok(Self::Push {
+                    head_ref,
+                    upstream_ref: Some(upstream_ref),
+                });
+            }
+        }
+        Ok(Self::RenameBranch)
+    }
 }
 
 pub trait Dispatcher {
@@ -132,6 +328,7 @@ pub trait Dispatcher {
     fn push(&self, head_ref: &str, upstream_ref: Option<&str>) -> Result<(), Self::Error>;
 }
 
+#[derive(Debug, PartialEq)]
 pub enum StepResult {
     Stop,
     Continue,
@@ -146,8 +343,16 @@ where
             info!("it's alright. happy hacking!");
             Ok(StepResult::Stop)
         }
-        Action::ResolveConflict => {
+        Action::ResolveConflict { paths } => {
             warn!("resolve conflict first.");
+            for path in paths {
+                warn!("conflict: {}", escape_control(&path));
+            }
+            dispatcher.status()?;
+            Ok(StepResult::Stop)
+        }
+        Action::FinishOperation { state } => {
+            warn!("{:?} is in progress; finish or abort it first.", state);
             dispatcher.status()?;
             Ok(StepResult::Stop)
         }
@@ -156,7 +361,7 @@ where
             Ok(StepResult::Continue)
         }
         Action::RenameBranch => {
-            info!("cleaning local changes on default branch by renaming it");
+            warn!("cleaning local changes on default branch by renaming it");
             dispatcher.rename_branch_and_switch()?;
             Ok(StepResult::Continue)
         }
@@ -185,13 +390,104 @@ where
     }
 }
 
+/// Repeat deciding and dispatching actions until the state machine stops.
+///
+/// With `step_once`, return after the first action even if it could continue.
+/// Stops also when staging left the index unchanged, e.g. the changes are
+/// only in submodules, as committing would fail with nothing to commit.
+pub fn run<C, D, E>(collector: &C, dispatcher: &D, step_once: bool) -> Result<(), E>
+where
+    C: Collector,
+    D: Dispatcher,
+    E: From<C::Error> + From<D::Error>,
+{
+    run_with_interval(collector, dispatcher, step_once, Duration::ZERO, |_| {})
+}
+
+/// Same as `run`, but calls `sleep` with `interval` between actions,
+/// to ease the load on slow filesystems or remotes.
+///
+/// `sleep` is not called if `interval` is zero.
+pub fn run_with_interval<C, D, E, S>(
+    collector: &C,
+    dispatcher: &D,
+    step_once: bool,
+    interval: Duration,
+    sleep: S,
+) -> Result<(), E>
+where
+    C: Collector,
+    D: Dispatcher,
+    E: From<C::Error> + From<D::Error>,
+    S: FnMut(Duration),
+{
+    let interrupted = AtomicBool::new(false);
+    run_interruptible(
+        collector,
+        dispatcher,
+        step_once,
+        interval,
+        sleep,
+        &interrupted,
+    )
+}
+
+/// Same as `run_with_interval`, but stops before the next action once `interrupted` is set,
+/// e.g. by a signal handler for Ctrl-C.
+///
+/// The action being dispatched is not cancelled by dah itself, though commands it runs
+/// may be terminated by the same signal. Returns `Ok(())` when stopped; the caller can
+/// check `interrupted` to tell it apart from a completed run.
+pub fn run_interruptible<C, D, E, S>(
+    collector: &C,
+    dispatcher: &D,
+    step_once: bool,
+    interval: Duration,
+    mut sleep: S,
+    interrupted: &AtomicBool,
+) -> Result<(), E>
+where
+    C: Collector,
+    D: Dispatcher,
+    E: From<C::Error> + From<D::Error>,
+    S: FnMut(Duration),
+{
+    let mut staged = false;
+    loop {
+        if interrupted.load(Ordering::Relaxed) {
+            warn!("interrupted; stopped before the next action.");
+            return Ok(());
+        }
+        let action = Action::new(collector)?;
+        if staged && action == Action::StageChanges {
+            warn!("nothing was staged; skip committing. see git status for the remaining changes.");
+            dispatcher.status()?;
+            return Ok(());
+        }
+        staged = action == Action::StageChanges;
+        match step(action, dispatcher)? {
+            StepResult::Stop => return Ok(()),
+            StepResult::Continue => {
+                if step_once {
+                    return Ok(());
+                }
+                if !interval.is_zero() {
+                    sleep(interval);
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use git2::Status;
+    use std::cell::Cell;
+
+    use git2::{RepositoryState, Status};
 
     use crate::git::{HeadRef, RemoteRef};
 
-    use super::{Action, Collector};
+    use super::{Action, ChangeKind, Collector, DirtyPolicy};
 
     #[derive(Debug, Clone, Default)]
     struct MockState {
@@ -199,7 +495,15 @@ mod tests {
         protected_branches: Vec<String>,
         head_ref: Option<HeadRef>,
         upstream: Option<Option<(RemoteRef, bool, bool)>>,
+        ahead_behind: Option<(usize, usize)>,
+        ahead_behind_queried: Cell<bool>,
+        foreign_upstream_commits: bool,
+        push_to_protected: bool,
         status: Option<Status>,
+        repository_state: Option<RepositoryState>,
+        dirty_policy: DirtyPolicy,
+        push_remote: Option<String>,
+        conflicted_paths: Vec<String>,
     }
 
     impl MockState {
@@ -246,6 +550,27 @@ mod tests {
             }
         }
 
+        fn with_ahead_behind(self, ahead: usize, behind: usize) -> Self {
+            Self {
+                ahead_behind: Some((ahead, behind)),
+                ..self
+            }
+        }
+
+        fn with_foreign_upstream_commits(self) -> Self {
+            Self {
+                foreign_upstream_commits: true,
+                ..self
+            }
+        }
+
+        fn with_push_to_protected(self) -> Self {
+            Self {
+                push_to_protected: true,
+                ..self
+            }
+        }
+
         fn with_no_upstream(self) -> Self {
             Self {
                 upstream: Some(None),
@@ -259,6 +584,34 @@ mod tests {
                 ..self
             }
         }
+
+        fn with_repository_state(self, state: RepositoryState) -> Self {
+            Self {
+                repository_state: Some(state),
+                ..self
+            }
+        }
+
+        fn with_dirty_policy(self, dirty_policy: DirtyPolicy) -> Self {
+            Self {
+                dirty_policy,
+                ..self
+            }
+        }
+
+        fn with_push_remote(self, remote: &str) -> Self {
+            Self {
+                push_remote: Some(remote.to_owned()),
+                ..self
+            }
+        }
+
+        fn with_conflicted_paths(self, paths: &[&str]) -> Self {
+            Self {
+                conflicted_paths: paths.iter().map(|p| p.to_string()).collect(),
+                ..self
+            }
+        }
     }
 
     impl Collector for MockState {
@@ -310,6 +663,15 @@ mod tests {
             }
         }
 
+        fn ahead_behind(&self) -> Result<Option<(usize, usize)>, Self::Error> {
+            self.ahead_behind_queried.set(true);
+            if let Some(Some(_)) = &self.upstream {
+                Ok(self.ahead_behind)
+            } else {
+                Ok(None)
+            }
+        }
+
         fn is_based_on_remote(&self) -> Result<bool, Self::Error> {
             if let Some(Some((_, _, o))) = &self.upstream {
                 Ok(*o)
@@ -318,6 +680,18 @@ mod tests {
             }
         }
 
+        fn has_foreign_upstream_commits(&self) -> Result<bool, Self::Error> {
+            if let Some(Some(_)) = &self.upstream {
+                Ok(self.foreign_upstream_commits)
+            } else {
+                Ok(false)
+            }
+        }
+
+        fn allows_push_to_protected(&self) -> Result<bool, Self::Error> {
+            Ok(self.push_to_protected)
+        }
+
         fn status(&self) -> Result<Status, Self::Error> {
             if let Some(o) = self.status {
                 Ok(o)
@@ -325,6 +699,22 @@ mod tests {
                 Err("status unset")
             }
         }
+
+        fn repository_state(&self) -> Result<RepositoryState, Self::Error> {
+            Ok(self.repository_state.unwrap_or(RepositoryState::Clean))
+        }
+
+        fn dirty_policy(&self) -> Result<DirtyPolicy, Self::Error> {
+            Ok(self.dirty_policy)
+        }
+
+        fn push_remote(&self) -> Result<Option<String>, Self::Error> {
+            Ok(self.push_remote.clone())
+        }
+
+        fn conflicted_paths(&self) -> Result<Vec<String>, Self::Error> {
+            Ok(self.conflicted_paths.clone())
+        }
     }
 
     #[test]
@@ -336,8 +726,45 @@ mod tests {
                     .with_default_branch("main")
                     .with_head_ref("refs/heads/foo")
                     .with_upstream_ref("refs/remotes/origin/foo", true, true)
-                    .with_status(Status::CONFLICTED),
-                Action::ResolveConflict,
+                    .with_status(Status::CONFLICTED)
+                    .with_conflicted_paths(&["src/a.rs", "src/b.rs"]),
+                Action::ResolveConflict {
+                    paths: vec!["src/a.rs".to_owned(), "src/b.rs".to_owned()],
+                },
+            ),
+            // merge in progress, like left MERGE_MSG -> should finish it
+            (
+                MockState::default()
+                    .with_default_branch("main")
+                    .with_head_ref("refs/heads/foo")
+                    .with_upstream_ref("refs/remotes/origin/foo", true, true)
+                    .with_status(Status::INDEX_MODIFIED)
+                    .with_repository_state(RepositoryState::Merge),
+                Action::FinishOperation {
+                    state: RepositoryState::Merge,
+                },
+            ),
+            // interactive rebase stopped for amending -> should finish it
+            (
+                MockState::default()
+                    .with_default_branch("main")
+                    .with_detached_head()
+                    .with_no_upstream()
+                    .with_status(Status::CURRENT)
+                    .with_repository_state(RepositoryState::RebaseInteractive),
+                Action::FinishOperation {
+                    state: RepositoryState::RebaseInteractive,
+                },
+            ),
+            // conflict in merge -> should resolve conflict first
+            (
+                MockState::default()
+                    .with_default_branch("main")
+                    .with_head_ref("refs/heads/foo")
+                    .with_upstream_ref("refs/remotes/origin/foo", true, true)
+                    .with_status(Status::CONFLICTED)
+                    .with_repository_state(RepositoryState::Merge),
+                Action::ResolveConflict { paths: vec![] },
             ),
             // on default branch and synchronized -> nothing to do.
             (
@@ -377,6 +804,70 @@ mod tests {
                     .with_status(Status::CURRENT),
                 Action::RenameBranch,
             ),
+            // on protected branch ahead of its upstream, pushing allowed -> push
+            (
+                MockState::default()
+                    .with_default_branch("main")
+                    .with_head_ref("refs/heads/develop")
+                    .with_upstream_ref("refs/remotes/origin/develop", false, true)
+                    .with_ahead_behind(2, 0)
+                    .with_protected_branch("develop")
+                    .with_push_to_protected()
+                    .with_status(Status::CURRENT),
+                Action::Push {
+                    head_ref: HeadRef::new("refs/heads/develop").unwrap(),
+                    upstream_ref: Some(RemoteRef::new("refs/remotes/origin/develop").unwrap()),
+                },
+            ),
+            // on default branch ahead of its upstream, pushing allowed -> push
+            (
+                MockState::default()
+                    .with_default_branch("main")
+                    .with_head_ref("refs/heads/main")
+                    .with_upstream_ref("refs/remotes/origin/main", false, true)
+                    .with_ahead_behind(1, 0)
+                    .with_push_to_protected()
+                    .with_status(Status::CURRENT),
+                Action::Push {
+                    head_ref: HeadRef::new("refs/heads/main").unwrap(),
+                    upstream_ref: Some(RemoteRef::new("refs/remotes/origin/main").unwrap()),
+                },
+            ),
+            // on protected branch diverged from its upstream, pushing allowed -> rename
+            (
+                MockState::default()
+                    .with_default_branch("main")
+                    .with_head_ref("refs/heads/develop")
+                    .with_upstream_ref("refs/remotes/origin/develop", false, false)
+                    .with_ahead_behind(1, 1)
+                    .with_protected_branch("develop")
+                    .with_push_to_protected()
+                    .with_status(Status::CURRENT),
+                Action::RenameBranch,
+            ),
+            // on default branch diverged from its upstream after amend, though the reflog says
+            // based on remote, pushing allowed -> rename rather than force push
+            (
+                MockState::default()
+                    .with_default_branch("main")
+                    .with_head_ref("refs/heads/main")
+                    .with_upstream_ref("refs/remotes/origin/main", false, true)
+                    .with_ahead_behind(1, 1)
+                    .with_push_to_protected()
+                    .with_status(Status::CURRENT),
+                Action::RenameBranch,
+            ),
+            // on protected branch without upstream, pushing allowed -> rename
+            (
+                MockState::default()
+                    .with_default_branch("main")
+                    .with_head_ref("refs/heads/develop")
+                    .with_no_upstream()
+                    .with_protected_branch("develop")
+                    .with_push_to_protected()
+                    .with_status(Status::CURRENT),
+                Action::RenameBranch,
+            ),
             // on detached head -> should create branch
             (
                 MockState::default()
@@ -410,6 +901,29 @@ mod tests {
                     upstream_ref: Some(RemoteRef::new("refs/remotes/origin/foo").unwrap()),
                 },
             ),
+            // on topic branch and include remote commits, but upstream has others' commits -> rebase
+            (
+                MockState::default()
+                    .with_default_branch("main")
+                    .with_head_ref("refs/heads/foo")
+                    .with_upstream_ref("refs/remotes/origin/foo", false, true)
+                    .with_foreign_upstream_commits()
+                    .with_status(Status::CURRENT),
+                Action::Rebase {
+                    head_ref: HeadRef::new("refs/heads/foo").unwrap(),
+                    upstream_ref: RemoteRef::new("refs/remotes/origin/foo").unwrap(),
+                },
+            ),
+            // synchronized even though upstream has others' commits -> nothing to do
+            (
+                MockState::default()
+                    .with_default_branch("main")
+                    .with_head_ref("refs/heads/foo")
+                    .with_upstream_ref("refs/remotes/origin/foo", true, true)
+                    .with_foreign_upstream_commits()
+                    .with_status(Status::CURRENT),
+                Action::None,
+            ),
             // on topic branch, but it doesn't include remote commits -> rebase
             (
                 MockState::default()
@@ -462,4 +976,124 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_action_from_queries_ahead_behind_only_with_upstream() {
+        let given = MockState::default()
+            .with_default_branch("main")
+            .with_head_ref("refs/heads/foo")
+            .with_upstream_ref("refs/remotes/origin/foo", false, true)
+            .with_ahead_behind(2, 0)
+            .with_status(Status::CURRENT);
+        let got = Action::new(&given);
+        assert_eq!(
+            got,
+            Ok(Action::Push {
+                head_ref: HeadRef::new("refs/heads/foo").unwrap(),
+                upstream_ref: Some(RemoteRef::new("refs/remotes/origin/foo").unwrap()),
+            })
+        );
+        assert!(given.ahead_behind_queried.get());
+
+        let given = MockState::default()
+            .with_default_branch("main")
+            .with_head_ref("refs/heads/foo")
+            .with_no_upstream()
+            .with_status(Status::CURRENT);
+        let got = Action::new(&given);
+        assert_eq!(
+            got,
+            Ok(Action::Push {
+                head_ref: HeadRef::new("refs/heads/foo").unwrap(),
+                upstream_ref: None,
+            })
+        );
+        assert!(!given.ahead_behind_queried.get());
+    }
+
+    #[test]
+    fn test_action_from_follows_dirty_policy() {
+        let given = || {
+            MockState::default()
+                .with_default_branch("main")
+                .with_head_ref("refs/heads/foo")
+                .with_upstream_ref("refs/remotes/origin/foo", true, true)
+        };
+        let ignore_typechange = DirtyPolicy::default().excluding(ChangeKind::Typechange);
+
+        let got = Action::new(&given().with_status(Status::WT_TYPECHANGE));
+        assert_eq!(got, Ok(Action::StageChanges));
+        let got = Action::new(&given().with_status(Status::INDEX_TYPECHANGE));
+        assert_eq!(got, Ok(Action::Commit));
+
+        let got = Action::new(
+            &given()
+                .with_status(Status::WT_TYPECHANGE)
+                .with_dirty_policy(ignore_typechange),
+        );
+        assert_eq!(got, Ok(Action::None));
+        let got = Action::new(
+            &given()
+                .with_status(Status::INDEX_TYPECHANGE)
+                .with_dirty_policy(ignore_typechange),
+        );
+        assert_eq!(got, Ok(Action::None));
+
+        // other changes still count
+        let got = Action::new(
+            &given()
+                .with_status(Status::WT_TYPECHANGE | Status::WT_MODIFIED)
+                .with_dirty_policy(ignore_typechange),
+        );
+        assert_eq!(got, Ok(Action::StageChanges));
+
+        let got = Action::new(
+            &given()
+                .with_status(Status::WT_TYPECHANGE)
+                .with_dirty_policy(ignore_typechange.including(ChangeKind::Typechange)),
+        );
+        assert_eq!(got, Ok(Action::StageChanges));
+    }
+
+    #[test]
+    fn test_action_from_with_push_remote() {
+        let given = |based_on_remote| {
+            MockState::default()
+                .with_default_branch("main")
+                .with_head_ref("refs/heads/foo")
+                .with_upstream_ref("refs/remotes/origin/foo", false, based_on_remote)
+                .with_foreign_upstream_commits()
+                .with_status(Status::CURRENT)
+        };
+        let head_ref = HeadRef::new("refs/heads/foo").unwrap();
+        let upstream_ref = RemoteRef::new("refs/remotes/origin/foo").unwrap();
+
+        // others' commits on origin are not overwritten by pushing to myfork -> push
+        let got = Action::new(&given(true).with_push_remote("myfork"));
+        assert_eq!(
+            got,
+            Ok(Action::Push {
+                head_ref: head_ref.clone(),
+                upstream_ref: Some(upstream_ref.clone()),
+            })
+        );
+        // pushing to the tracking remote still guards them -> rebase
+        let got = Action::new(&given(true).with_push_remote("origin"));
+        assert_eq!(
+            got,
+            Ok(Action::Rebase {
+                head_ref: head_ref.clone(),
+                upstream_ref: upstream_ref.clone(),
+            })
+        );
+        // HEAD is still rebased onto the remote tracking branch
+        let got = Action::new(&given(false).with_push_remote("myfork"));
+        assert_eq!(
+            got,
+            Ok(Action::Rebase {
+                head_ref,
+                upstream_ref,
+            })
+        );
+    }
 }
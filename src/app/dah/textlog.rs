@@ -0,0 +1,106 @@
+use std::io::Write;
+
+use env_logger::{fmt::style::Style, Builder, WriteStyle};
+use log::Level;
+
+use super::ColorMode;
+
+impl From<ColorMode> for WriteStyle {
+    fn from(value: ColorMode) -> Self {
+        match value {
+            ColorMode::Auto => WriteStyle::Auto,
+            ColorMode::Always => WriteStyle::Always,
+            ColorMode::Never => WriteStyle::Never,
+        }
+    }
+}
+
+/// env_logger builder which formats records like env_logger's default,
+/// but colors whole message of warnings and errors to make them stand out.
+pub fn builder(color: ColorMode) -> Builder {
+    let mut builder = Builder::from_default_env();
+    // with auto, respect RUST_LOG_STYLE as env_logger does.
+    if color != ColorMode::Auto {
+        builder.write_style(color.into());
+    }
+    builder.format(|buf, record| {
+        let level_style = buf.default_level_style(record.level());
+        let message_style = match record.level() {
+            Level::Error | Level::Warn => level_style,
+            _ => Style::new(),
+        };
+        writeln!(
+            buf,
+            "[{} {level_style}{:<5}{level_style:#} {}] {message_style}{}{message_style:#}",
+            buf.timestamp(),
+            record.level(),
+            record.target(),
+            record.args()
+        )
+    });
+    builder
+}
+
+/// Install env_logger with the color mode.
+pub fn init(color: ColorMode) {
+    builder(color).init()
+}
+
+#[cfg(test)]
+mod tests {
+    use env_logger::Target;
+    use log::{Level, Log, Record};
+
+    use super::builder;
+    use crate::app::dah::{testlog::SharedBuf, ColorMode};
+
+    fn capture(color: ColorMode) -> String {
+        let buf = SharedBuf::default();
+        let logger = builder(color)
+            .filter_level(log::LevelFilter::Info)
+            .target(Target::Pipe(Box::new(buf.clone())))
+            .build();
+
+        logger.log(
+            &Record::builder()
+                .level(Level::Warn)
+                .target("git_toolbox::app::dah")
+                .args(format_args!("force pushing"))
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .level(Level::Info)
+                .target("git_toolbox::app::dah")
+                .args(format_args!("invoking git push"))
+                .build(),
+        );
+
+        buf.contents()
+    }
+
+    #[test]
+    fn test_color_never_disables_ansi_sequences() {
+        let got = capture(ColorMode::Never);
+        assert!(!got.contains('\x1b'), "got {:?}", got);
+        assert!(got.contains("WARN  git_toolbox::app::dah] force pushing\n"));
+        assert!(got.contains("INFO  git_toolbox::app::dah] invoking git push\n"));
+    }
+
+    #[test]
+    fn test_color_always_emphasizes_warnings() {
+        let got = capture(ColorMode::Always);
+        let warn_line = got.lines().find(|l| l.contains("force pushing")).unwrap();
+        assert!(
+            warn_line.contains("\x1b[33mforce pushing\x1b[0m"),
+            "got {:?}",
+            warn_line
+        );
+        let info_line = got.lines().find(|l| l.contains("invoking")).unwrap();
+        assert!(
+            info_line.ends_with("] invoking git push"),
+            "got {:?}",
+            info_line
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    cell::RefCell,
+    time::{Duration, Instant},
+};
+
+use git2::{RepositoryState, Status};
+use log::info;
+
+use crate::git::{HeadRef, RemoteRef};
+
+use super::{Collector, DirtyPolicy, Dispatcher};
+
+/// Wrapper of `Collector` and `Dispatcher` recording wall-clock time of each call.
+pub struct Timings<'a, T> {
+    inner: &'a T,
+    entries: RefCell<Vec<(&'static str, Duration)>>,
+}
+
+impl<'a, T> Timings<'a, T> {
+    pub fn new(inner: &'a T) -> Self {
+        Self {
+            inner,
+            entries: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Call `f` and record how long it took as `name`, regardless of its result.
+    pub fn measure<R, F: FnOnce() -> R>(&self, name: &'static str, f: F) -> R {
+        let start = Instant::now();
+        let result = f();
+        self.entries.borrow_mut().push((name, start.elapsed()));
+        result
+    }
+
+    /// Recorded timings in the order of calls.
+    pub fn entries(&self) -> Vec<(&'static str, Duration)> {
+        self.entries.borrow().clone()
+    }
+
+    /// Sum up recorded timings by name, in the order of the first call.
+    fn totals(&self) -> Vec<(&'static str, usize, Duration)> {
+        let mut totals: Vec<(&'static str, usize, Duration)> = Vec::new();
+        for (name, elapsed) in self.entries.borrow().iter() {
+            match totals.iter_mut().find(|(n, _, _)| n == name) {
+                Some((_, calls, total)) => {
+                    *calls += 1;
+                    *total += *elapsed;
+                }
+                None => totals.push((name, 1, *elapsed)),
+            }
+        }
+        totals
+    }
+
+    /// Log recorded timings as a table.
+    pub fn log(&self) {
+        info!("{:<28} {:>5} {:>12}", "timings", "calls", "total");
+        for (name, calls, total) in self.totals() {
+            info!("{:<28} {:>5} {:>12.3?}", name, calls, total);
+        }
+    }
+}
+
+impl<T: Collector> Collector for Timings<'_, T> {
+    type Error = T::Error;
+
+    fn default_branch(&self) -> Result<Option<String>, Self::Error> {
+        self.measure("default_branch", || self.inner.default_branch())
+    }
+
+    fn is_head_protected(&self) -> Result<bool, Self::Error> {
+        self.measure("is_head_protected", || self.inner.is_head_protected())
+    }
+
+    fn head_ref(&self) -> Result<HeadRef, Self::Error> {
+        self.measure("head_ref", || self.inner.head_ref())
+    }
+
+    fn upstream_ref(&self) -> Result<Option<RemoteRef>, Self::Error> {
+        self.measure("upstream_ref", || self.inner.upstream_ref())
+    }
+
+    fn is_synchronized(&self) -> Result<bool, Self::Error> {
+        self.measure("is_synchronized", || self.inner.is_synchronized())
+    }
+
+    fn ahead_behind(&self) -> Result<Option<(usize, usize)>, Self::Error> {
+        self.measure("ahead_behind", || self.inner.ahead_behind())
+    }
+
+    fn is_based_on_remote(&self) -> Result<bool, Self::Error> {
+        self.measure("is_based_on_remote", || self.inner.is_based_on_remote())
+    }
+
+    fn has_foreign_upstream_commits(&self) -> Result<bool, Self::Error> {
+        self.measure("has_foreign_upstream_commits", || {
+            self.inner.has_foreign_upstream_commits()
+        })
+    }
+
+    fn allows_push_to_protected(&self) -> Result<bool, Self::Error> {
+        self.measure("allows_push_to_protected", || {
+            self.inner.allows_push_to_protected()
+        })
+    }
+
+    fn status(&self) -> Result<Status, Self::Error> {
+        self.measure("status", || Collector::status(self.inner))
+    }
+
+    fn repository_state(&self) -> Result<RepositoryState, Self::Error> {
+        self.measure("repository_state", || self.inner.repository_state())
+    }
+
+    fn dirty_policy(&self) -> Result<DirtyPolicy, Self::Error> {
+        self.measure("dirty_policy", || self.inner.dirty_policy())
+    }
+
+    fn push_remote(&self) -> Result<Option<String>, Self::Error> {
+        self.measure("push_remote", || self.inner.push_remote())
+    }
+
+    fn conflicted_paths(&self) -> Result<Vec<String>, Self::Error> {
+        self.measure("conflicted_paths", || self.inner.conflicted_paths())
+    }
+}
+
+impl<T: Dispatcher> Dispatcher for Timings<'_, T> {
+    type Error = T::Error;
+
+    fn status(&self) -> Result<(), Self::Error> {
+        self.measure("git status", || Dispatcher::status(self.inner))
+    }
+
+    fn create_branch_and_switch(&self) -> Result<(), Self::Error> {
+        self.measure("create_branch_and_switch", || {
+            self.inner.create_branch_and_switch()
+        })
+    }
+
+    fn rename_branch_and_switch(&self) -> Result<(), Self::Error> {
+        self.measure("rename_branch_and_switch", || {
+            self.inner.rename_branch_and_switch()
+        })
+    }
+
+    fn stage_changes(&self) -> Result<(), Self::Error> {
+        self.measure("stage_changes", || self.inner.stage_changes())
+    }
+
+    fn commit(&self) -> Result<(), Self::Error> {
+        self.measure("commit", || self.inner.commit())
+    }
+
+    fn pull_with_rebase(&self, upstream_ref: &str) -> Result<(), Self::Error> {
+        self.measure("pull_with_rebase", || {
+            self.inner.pull_with_rebase(upstream_ref)
+        })
+    }
+
+    fn push(&self, head_ref: &str, upstream_ref: Option<&str>) -> Result<(), Self::Error> {
+        self.measure("push", || self.inner.push(head_ref, upstream_ref))
+    }
+}
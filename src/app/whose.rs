@@ -1,17 +1,63 @@
-use std::{ffi::OsStr, os::unix::ffi::OsStrExt as _};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    ffi::OsStr,
+    io::{self, Write},
+    os::unix::ffi::OsStrExt as _,
+};
 
-use git2::{Pathspec, PathspecFlags, Repository};
+use git2::{DiffOptions, Oid, Pathspec, PathspecFlags, Repository};
 use log::info;
 
 use crate::{
-    github::codeowners::{CodeOwners, CodeOwnersError},
-    pathname,
+    github::codeowners::{CodeOwners, CodeOwnersDocument, CodeOwnersError, MatchOrder},
+    json, pathname,
 };
 
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Human,
+    Csv,
+    // entries matching each path, as --debug prints.
+    Toml,
+    // one owner per line for piping; selected by --owners-only rather than --format.
+    #[value(skip)]
+    Owners,
+    #[value(skip)]
+    PathOwners,
+    // effective entry in one line per path like `git check-ignore -v`; selected by --verbose.
+    #[value(skip)]
+    Verbose,
+}
+
+impl OutputFormat {
+    fn is_owners_only(&self) -> bool {
+        matches!(self, OutputFormat::Owners | OutputFormat::PathOwners)
+    }
+}
+
 pub struct Application {
     pub repo: Repository,
     pub codeowners: CodeOwners,
     pub pathspecs: Vec<String>,
+    pub format: OutputFormat,
+    pub limit: Option<usize>,
+    /// List unowned paths too, in owners-only formats.
+    pub include_unowned: bool,
+    /// Check CODEOWNERS file instead of finding owners.
+    pub lint: bool,
+    /// Parse CODEOWNERS file strictly instead of finding owners.
+    pub validate: bool,
+    /// Tree to compare the index with, to find owners of changed paths only.
+    pub changed_since: Option<Oid>,
+    /// Print entries matching each path instead of owners.
+    pub debug: bool,
+    /// Print only the entry determining owners in debug output.
+    pub effective_only: bool,
+    /// Union owners of all entries matching each path, instead of the effective one.
+    pub all_owners: bool,
+    /// List paths under each owner instead of owners of each path.
+    pub group_by_owner: bool,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -22,39 +68,317 @@ pub enum ApplicationError {
     PathError(#[from] pathname::NormalizePathError),
     #[error("{0}")]
     CodeOwnersError(#[from] CodeOwnersError),
+    #[error("{0}")]
+    IOError(#[from] io::Error),
+    #[error("found {0} problem(s) in CODEOWNERS")]
+    LintFailed(usize),
+    #[error("found {0} error(s) in CODEOWNERS")]
+    ValidationFailed(usize),
+}
+
+/// Quote CSV field if needed, as described in RFC 4180.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+fn write_header<W: Write>(out: &mut W, format: OutputFormat) -> io::Result<()> {
+    match format {
+        OutputFormat::Human
+        | OutputFormat::Toml
+        | OutputFormat::Owners
+        | OutputFormat::PathOwners
+        | OutputFormat::Verbose => Ok(()),
+        OutputFormat::Csv => writeln!(out, "path,owner"),
+    }
+}
+
+fn write_entry<W: Write>(
+    out: &mut W,
+    format: OutputFormat,
+    path: &str,
+    owners: Option<&[String]>,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Human | OutputFormat::Toml | OutputFormat::Verbose => match owners {
+            Some(owners) => writeln!(out, "{}: {}", path, owners.join(", ")),
+            None => writeln!(out, "{}:", path),
+        },
+        // one row per owner; path without owners yields a row with empty owner.
+        OutputFormat::Csv => match owners {
+            Some(owners) if !owners.is_empty() => {
+                for owner in owners {
+                    writeln!(out, "{},{}", csv_field(path), csv_field(owner))?;
+                }
+                Ok(())
+            }
+            _ => writeln!(out, "{},", csv_field(path)),
+        },
+        // unowned path yields an empty line, or the path with empty owner.
+        OutputFormat::Owners => match owners {
+            Some(owners) if !owners.is_empty() => {
+                for owner in owners {
+                    writeln!(out, "{}", owner)?;
+                }
+                Ok(())
+            }
+            _ => writeln!(out),
+        },
+        OutputFormat::PathOwners => match owners {
+            Some(owners) if !owners.is_empty() => {
+                for owner in owners {
+                    writeln!(out, "{}\t{}", path, owner)?;
+                }
+                Ok(())
+            }
+            _ => writeln!(out, "{}\t", path),
+        },
+    }
 }
 
 impl Application {
     pub fn run(&self) -> Result<(), ApplicationError> {
         env_logger::init();
 
-        let index = self.repo.index()?;
-        let pathspec = Pathspec::new(self.pathspecs.iter())?;
-        let matches = pathspec.match_index(&index, PathspecFlags::default())?;
+        if self.validate {
+            self.validate_to(&mut io::stdout().lock())
+        } else if self.debug || self.format == OutputFormat::Toml {
+            self.debug_to(&mut io::stdout().lock())
+        } else if self.lint {
+            self.lint_to(&mut io::stdout().lock())
+        } else if self.format == OutputFormat::Verbose {
+            self.verbose_to(&mut io::stdout().lock())
+        } else if self.group_by_owner {
+            self.group_by_owner_to(&mut io::stdout().lock())
+        } else {
+            self.write_to(&mut io::stdout().lock())
+        }
+    }
+
+    fn lint_to<W: Write>(&self, out: &mut W) -> Result<(), ApplicationError> {
+        let (path, doc) = CodeOwnersDocument::try_from_repo(&self.repo)?;
+        let warnings = doc.lint();
+        for warning in &warnings {
+            writeln!(out, "{}:{}", path, warning)?;
+        }
+
+        if warnings.is_empty() {
+            Ok(())
+        } else {
+            Err(ApplicationError::LintFailed(warnings.len()))
+        }
+    }
 
-        for entry in matches.entries() {
-            let path = OsStr::from_bytes(entry);
-            if let Some(path) = OsStr::from_bytes(entry).to_str() {
-                match self.codeowners.find_owners(path) {
-                    Some(owners) => {
-                        println!("{}: {}", path, owners.join(", "));
+    fn validate_to<W: Write>(&self, out: &mut W) -> Result<(), ApplicationError> {
+        let (path, errors) = CodeOwners::validate_repo(&self.repo)?;
+        for error in &errors {
+            writeln!(out, "{}:{}", path, error)?;
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ApplicationError::ValidationFailed(errors.len()))
+        }
+    }
+
+    /// Print entries matching each path, in TOML for `--format toml`.
+    fn debug_to<W: Write>(&self, out: &mut W) -> Result<(), ApplicationError> {
+        let paths = self.paths()?;
+        for path in self.take_utf8_paths(&paths) {
+            let effective = self.codeowners.find_match(path);
+            let matches = if self.effective_only {
+                effective.into_iter().collect()
+            } else {
+                self.codeowners.find_matches(path)
+            };
+            for m in matches {
+                let is_effective = effective.as_ref() == Some(&m);
+                if self.format != OutputFormat::Toml {
+                    write!(out, "{}:{}: {}", path, m.line(), m.pattern())?;
+                    for owner in m.owners() {
+                        write!(out, " {}", owner)?;
                     }
-                    None => {
-                        println!("{}:", path);
+                    if is_effective {
+                        write!(out, " (effective)")?;
                     }
+                    writeln!(out)?;
+                    continue;
                 }
-            } else {
-                log::error!("cannot convet {:?} into utf-8 string.", path)
+                // escape sequences of JSON strings and arrays are valid in TOML too.
+                writeln!(out, "[[match]]")?;
+                writeln!(out, "path = \"{}\"", json::escape(path))?;
+                writeln!(out, "line = {}", m.line())?;
+                writeln!(out, "rule = \"{}\"", json::escape(m.pattern()))?;
+                writeln!(out, "owners = {}", json::string_array(m.owners()))?;
+                writeln!(out, "effective = {}", is_effective)?;
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Print the effective entry for each path in one line, like `git check-ignore -v`:
+    /// `SOURCE:LINE:PATTERN<TAB>OWNERS<TAB>PATH`, with empty fields for unowned paths.
+    fn verbose_to<W: Write>(&self, out: &mut W) -> Result<(), ApplicationError> {
+        let paths = self.paths()?;
+        for path in self.take_utf8_paths(&paths) {
+            match self.codeowners.find_match(path) {
+                Some(m) => writeln!(
+                    out,
+                    "{}:{}:{}\t{}\t{}",
+                    m.source().unwrap_or_default(),
+                    m.line(),
+                    m.pattern(),
+                    m.owners().join(" "),
+                    path
+                )?,
+                None => writeln!(out, "::\t\t{}", path)?,
             }
         }
+        Ok(())
+    }
+
+    fn paths(&self) -> Result<Vec<Vec<u8>>, ApplicationError> {
+        match self.changed_since {
+            Some(tree) => self.changed_paths(tree),
+            None => self.indexed_paths(),
+        }
+    }
+
+    /// Paths in UTF-8 up to the limit, skipping others.
+    fn take_utf8_paths<'a>(&self, paths: &'a [Vec<u8>]) -> impl Iterator<Item = &'a str> {
+        paths
+            .iter()
+            .filter_map(|path| {
+                let path = OsStr::from_bytes(path);
+                let utf8_path = path.to_str();
+                if utf8_path.is_none() {
+                    log::error!("cannot convet {:?} into utf-8 string.", path)
+                }
+                utf8_path
+            })
+            .take(self.limit.unwrap_or(usize::MAX))
+    }
 
+    /// Owners of each path, of the effective entry or all matching entries by `all_owners`.
+    fn owners_of<'a>(
+        &'a self,
+        paths: impl Iterator<Item = &'a str>,
+    ) -> Vec<(&'a str, Option<Cow<'a, [String]>>)> {
+        if self.all_owners {
+            paths
+                .map(|path| (path, self.codeowners.find_all_owners(path).map(Cow::Owned)))
+                .collect()
+        } else {
+            self.codeowners
+                .find_owners_bulk(paths)
+                .into_iter()
+                .map(|(path, owners)| (path, owners.map(|o| Cow::Borrowed(o.as_slice()))))
+                .collect()
+        }
+    }
+
+    /// Print each owner followed by their paths indented, then unowned paths as `(unowned)`.
+    fn group_by_owner_to<W: Write>(&self, out: &mut W) -> Result<(), ApplicationError> {
+        let paths = self.paths()?;
+        let mut groups: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        let mut unowned = Vec::new();
+        let entries = self.owners_of(self.take_utf8_paths(&paths));
+        for (path, owners) in &entries {
+            match owners.as_deref() {
+                Some(owners) if !owners.is_empty() => {
+                    for owner in owners {
+                        groups.entry(owner).or_default().push(path);
+                    }
+                }
+                _ => unowned.push(*path),
+            }
+        }
+
+        for (owner, paths) in groups {
+            writeln!(out, "{}", owner)?;
+            for path in paths {
+                writeln!(out, "  {}", path)?;
+            }
+        }
+        if !unowned.is_empty() {
+            writeln!(out, "(unowned)")?;
+            for path in unowned {
+                writeln!(out, "  {}", path)?;
+            }
+        }
         Ok(())
     }
+
+    fn write_to<W: Write>(&self, out: &mut W) -> Result<(), ApplicationError> {
+        let paths = self.paths()?;
+        let entries = self.owners_of(self.take_utf8_paths(&paths));
+
+        write_header(out, self.format)?;
+        for (path, owners) in entries {
+            let owners = owners.as_deref();
+            let unowned = owners.is_none_or(|o| o.is_empty());
+            if unowned && self.format.is_owners_only() && !self.include_unowned {
+                continue;
+            }
+            write_entry(out, self.format, path, owners)?;
+        }
+
+        Ok(())
+    }
+
+    fn indexed_paths(&self) -> Result<Vec<Vec<u8>>, ApplicationError> {
+        let index = self.repo.index()?;
+        let pathspec = Pathspec::new(self.pathspecs.iter())?;
+        let matches = pathspec.match_index(&index, PathspecFlags::default())?;
+        Ok(matches.entries().map(|entry| entry.to_vec()).collect())
+    }
+
+    /// Paths changed between the tree and the index, including deleted and renamed ones.
+    fn changed_paths(&self, tree: Oid) -> Result<Vec<Vec<u8>>, ApplicationError> {
+        let index = self.repo.index()?;
+        let tree = self.repo.find_tree(tree)?;
+        let mut opts = DiffOptions::new();
+        for pathspec in &self.pathspecs {
+            opts.pathspec(pathspec);
+        }
+        let diff = self
+            .repo
+            .diff_tree_to_index(Some(&tree), Some(&index), Some(&mut opts))?;
+
+        let mut paths = Vec::new();
+        for delta in diff.deltas() {
+            let old = delta.old_file().path_bytes();
+            let new = delta.new_file().path_bytes();
+            paths.extend(old.map(|p| p.to_vec()));
+            if new != old {
+                paths.extend(new.map(|p| p.to_vec()));
+            }
+        }
+        Ok(paths)
+    }
 }
 
 pub struct ApplicationBuilder {
     repo: Repository,
     pathspecs: Vec<String>,
+    format: OutputFormat,
+    merge: bool,
+    strict: bool,
+    limit: Option<usize>,
+    include_unowned: bool,
+    lint: bool,
+    validate: bool,
+    changed_since: Option<String>,
+    match_order: MatchOrder,
+    debug: bool,
+    effective_only: bool,
+    all_owners: bool,
+    group_by_owner: bool,
 }
 
 impl ApplicationBuilder {
@@ -62,13 +386,95 @@ impl ApplicationBuilder {
         Self {
             repo,
             pathspecs: Default::default(),
+            format: OutputFormat::Human,
+            merge: false,
+            strict: false,
+            limit: None,
+            include_unowned: false,
+            lint: false,
+            validate: false,
+            changed_since: None,
+            match_order: MatchOrder::default(),
+            debug: false,
+            effective_only: false,
+            all_owners: false,
+            group_by_owner: false,
+        }
+    }
+
+    pub fn with_merge(self, merge: bool) -> Self {
+        Self { merge, ..self }
+    }
+
+    pub fn with_format(self, format: OutputFormat) -> Self {
+        Self { format, ..self }
+    }
+
+    pub fn with_limit(self, limit: Option<usize>) -> Self {
+        Self { limit, ..self }
+    }
+
+    pub fn with_include_unowned(self, include_unowned: bool) -> Self {
+        Self {
+            include_unowned,
+            ..self
+        }
+    }
+
+    pub fn with_lint(self, lint: bool) -> Self {
+        Self { lint, ..self }
+    }
+
+    pub fn with_validate(self, validate: bool) -> Self {
+        Self { validate, ..self }
+    }
+
+    pub fn with_debug(self, debug: bool) -> Self {
+        Self { debug, ..self }
+    }
+
+    pub fn with_effective_only(self, effective_only: bool) -> Self {
+        Self {
+            effective_only,
+            ..self
+        }
+    }
+
+    pub fn with_all_owners(self, all_owners: bool) -> Self {
+        Self { all_owners, ..self }
+    }
+
+    pub fn with_group_by_owner(self, group_by_owner: bool) -> Self {
+        Self {
+            group_by_owner,
+            ..self
         }
     }
 
+    pub fn with_changed_since(self, changed_since: Option<String>) -> Self {
+        Self {
+            changed_since,
+            ..self
+        }
+    }
+
+    pub fn with_match_order(self, match_order: MatchOrder) -> Self {
+        Self {
+            match_order,
+            ..self
+        }
+    }
+
+    pub fn with_strict(self, strict: bool) -> Self {
+        Self { strict, ..self }
+    }
+
     pub fn with_pathspecs(self, pathspecs: Vec<String>) -> Result<Self, ApplicationError> {
         let pathspecs = if self.repo.is_bare() {
             info!("this is bare repository");
             self.pathspecs
+        } else if self.strict {
+            pathname::normalize_paths_strict(&self.repo, pathspecs)?
         } else {
             pathname::normalize_paths(&self.repo, pathspecs)?
         };
@@ -77,11 +483,464 @@ impl ApplicationBuilder {
     }
 
     pub fn build(self) -> Result<Application, ApplicationError> {
-        let codeowners = CodeOwners::try_from_repo(&self.repo)?;
+        let codeowners = if self.merge {
+            CodeOwners::try_from_repo_merged(&self.repo)?
+        } else {
+            CodeOwners::try_from_repo(&self.repo)?
+        }
+        .with_match_order(self.match_order);
+        let changed_since = match &self.changed_since {
+            Some(r) => Some(self.repo.revparse_single(r)?.peel_to_tree()?.id()),
+            None => None,
+        };
         Ok(Application {
             repo: self.repo,
             codeowners,
             pathspecs: self.pathspecs,
+            format: self.format,
+            limit: self.limit,
+            include_unowned: self.include_unowned,
+            lint: self.lint,
+            validate: self.validate,
+            changed_since,
+            debug: self.debug,
+            effective_only: self.effective_only,
+            all_owners: self.all_owners,
+            group_by_owner: self.group_by_owner,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use git2::{Repository, Signature};
+    use tempfile::TempDir;
+
+    use super::{write_entry, write_header, ApplicationBuilder, ApplicationError, OutputFormat};
+
+    fn init_repo_with_files(path: &Path, files: &[(&str, &str)]) -> Repository {
+        let repo = Repository::init(path).unwrap();
+        let mut index = repo.index().unwrap();
+        for (name, content) in files {
+            let file = path.join(name);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, content).unwrap();
+            index.add_path(Path::new(name)).unwrap();
+        }
+        index.write().unwrap();
+        repo
+    }
+
+    #[test]
+    fn test_write_entry_csv() {
+        let owners = ["@foo".to_owned(), "@org/bar,baz".to_owned()];
+        let no_owners: [String; 0] = [];
+        let cases = [
+            (
+                "a.js",
+                Some(&owners[..]),
+                "a.js,@foo\na.js,\"@org/bar,baz\"\n",
+            ),
+            ("a,b.js", None, "\"a,b.js\",\n"),
+            ("a\"b\".js", Some(&no_owners[..]), "\"a\"\"b\"\".js\",\n"),
+            ("a\nb.js", None, "\"a\nb.js\",\n"),
+        ];
+
+        for (idx, (path, owners, want)) in cases.into_iter().enumerate() {
+            let mut out = Vec::new();
+            write_entry(&mut out, OutputFormat::Csv, path, owners).unwrap();
+            let got = String::from_utf8(out).unwrap();
+            assert_eq!(got, want, "#{}: for path {:?}", idx, path);
+        }
+    }
+
+    #[test]
+    fn test_owners_only() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = init_repo_with_files(
+            tmpdir.path(),
+            &[
+                ("CODEOWNERS", "* @owner @org/team\n/docs/\n"),
+                ("a.txt", ""),
+                ("docs/b.md", ""),
+            ],
+        );
+
+        let cases = [
+            (
+                OutputFormat::Owners,
+                false,
+                "@owner\n@org/team\n@owner\n@org/team\n",
+            ),
+            (
+                OutputFormat::PathOwners,
+                false,
+                "CODEOWNERS\t@owner\nCODEOWNERS\t@org/team\na.txt\t@owner\na.txt\t@org/team\n",
+            ),
+            (
+                OutputFormat::Owners,
+                true,
+                "@owner\n@org/team\n@owner\n@org/team\n\n",
+            ),
+            (
+                OutputFormat::PathOwners,
+                true,
+                "CODEOWNERS\t@owner\nCODEOWNERS\t@org/team\na.txt\t@owner\na.txt\t@org/team\ndocs/b.md\t\n",
+            ),
+        ];
+
+        let mut app = ApplicationBuilder::new(repo).build().unwrap();
+        app.pathspecs = vec!["*".to_owned()];
+        for (idx, (format, include_unowned, want)) in cases.into_iter().enumerate() {
+            app.format = format;
+            app.include_unowned = include_unowned;
+            let mut out = Vec::new();
+            app.write_to(&mut out).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), want, "#{}", idx);
+        }
+    }
+
+    #[test]
+    fn test_lint() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = init_repo_with_files(
+            tmpdir.path(),
+            &[(
+                ".github/CODEOWNERS",
+                "* @owner\n./src @dev\nsrc\\lib @dev\n",
+            )],
+        );
+
+        let app = ApplicationBuilder::new(repo)
+            .with_lint(true)
+            .build()
+            .unwrap();
+        let mut out = Vec::new();
+        let got = app.lint_to(&mut out);
+        assert!(matches!(got, Err(ApplicationError::LintFailed(2))));
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(".github/CODEOWNERS:2: ./src: "));
+        assert!(lines[1].starts_with(".github/CODEOWNERS:3: src\\lib: "));
+    }
+
+    #[test]
+    fn test_validate() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = init_repo_with_files(
+            tmpdir.path(),
+            &[("CODEOWNERS", "* @owner\n!*.md @writer\n/docs/ writer\n")],
+        );
+
+        let app = ApplicationBuilder::new(repo)
+            .with_validate(true)
+            .build()
+            .unwrap();
+        let mut out = Vec::new();
+        let got = app.validate_to(&mut out);
+        assert!(matches!(got, Err(ApplicationError::ValidationFailed(2))));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+CODEOWNERS:2: negation `!` is not supported
+CODEOWNERS:3: invalid owner: writer
+"
+        );
+    }
+
+    #[test]
+    fn test_write_header() {
+        let mut out = Vec::new();
+        write_header(&mut out, OutputFormat::Csv).unwrap();
+        assert_eq!(out, b"path,owner\n");
+
+        let mut out = Vec::new();
+        write_header(&mut out, OutputFormat::Human).unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_debug() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = init_repo_with_files(
+            tmpdir.path(),
+            &[
+                ("CODEOWNERS", "* @owner\n*.js @js\n/src/ @src # comment\n"),
+                ("src/a.js", ""),
+                ("b.txt", ""),
+            ],
+        );
+
+        let mut app = ApplicationBuilder::new(repo)
+            .with_debug(true)
+            .build()
+            .unwrap();
+        app.pathspecs = vec!["b.txt".to_owned(), "src/a.js".to_owned()];
+        let mut out = Vec::new();
+        app.debug_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+b.txt:1: * @owner (effective)
+src/a.js:1: * @owner
+src/a.js:2: *.js @js
+src/a.js:3: /src/ @src (effective)
+"
+        );
+
+        // one line per path
+        app.effective_only = true;
+        let mut out = Vec::new();
+        app.debug_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "b.txt:1: * @owner (effective)\nsrc/a.js:3: /src/ @src (effective)\n"
+        );
+    }
+
+    #[test]
+    fn test_toml() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = init_repo_with_files(
+            tmpdir.path(),
+            &[
+                ("CODEOWNERS", "* @owner\n/src/a\"* @js @org/*\n/src/ @src\n"),
+                ("src/a\"\\b\u{7f}.js", ""),
+                ("b.txt", ""),
+            ],
+        );
+
+        let mut app = ApplicationBuilder::new(repo)
+            .with_format(OutputFormat::Toml)
+            .build()
+            .unwrap();
+        app.pathspecs = vec!["b.txt".to_owned(), "src/*".to_owned()];
+        let mut out = Vec::new();
+        app.debug_to(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let doc: toml::Table = out.parse().unwrap();
+        let matches = doc["match"].as_array().unwrap();
+        let got: Vec<_> = matches
+            .iter()
+            .map(|m| {
+                (
+                    m["path"].as_str().unwrap(),
+                    m["line"].as_integer().unwrap(),
+                    m["rule"].as_str().unwrap(),
+                    m["owners"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|o| o.as_str().unwrap())
+                        .collect::<Vec<_>>(),
+                    m["effective"].as_bool().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                ("b.txt", 1, "*", vec!["@owner"], true),
+                ("src/a\"\\b\u{7f}.js", 1, "*", vec!["@owner"], false),
+                (
+                    "src/a\"\\b\u{7f}.js",
+                    2,
+                    "/src/a\"*",
+                    vec!["@js", "@org/*"],
+                    false
+                ),
+                ("src/a\"\\b\u{7f}.js", 3, "/src/", vec!["@src"], true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verbose() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = init_repo_with_files(
+            tmpdir.path(),
+            &[
+                (
+                    ".github/CODEOWNERS",
+                    "*.js @js @org/web
+/src/ @src
+",
+                ),
+                ("src/a.js", ""),
+                ("b.js", ""),
+                ("c.txt", ""),
+            ],
+        );
+
+        let mut app = ApplicationBuilder::new(repo)
+            .with_format(OutputFormat::Verbose)
+            .build()
+            .unwrap();
+        app.pathspecs = vec!["b.js".to_owned(), "c.txt".to_owned(), "src/a.js".to_owned()];
+        let mut out = Vec::new();
+        app.verbose_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+.github/CODEOWNERS:1:*.js\t@js @org/web\tb.js
+::\t\tc.txt
+.github/CODEOWNERS:2:/src/\t@src\tsrc/a.js
+"
+        );
+    }
+
+    #[test]
+    fn test_group_by_owner() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = init_repo_with_files(
+            tmpdir.path(),
+            &[
+                ("CODEOWNERS", "* @owner\n*.js @js @owner\n/docs/\n"),
+                ("src/a.js", ""),
+                ("b.txt", ""),
+                ("docs/c.md", ""),
+            ],
+        );
+
+        let mut app = ApplicationBuilder::new(repo)
+            .with_group_by_owner(true)
+            .build()
+            .unwrap();
+        app.pathspecs = vec![
+            "b.txt".to_owned(),
+            "docs/c.md".to_owned(),
+            "src/a.js".to_owned(),
+        ];
+        let mut out = Vec::new();
+        app.group_by_owner_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\
+@js
+  src/a.js
+@owner
+  b.txt
+  src/a.js
+(unowned)
+  docs/c.md
+"
+        );
+    }
+
+    #[test]
+    fn test_all_owners() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = init_repo_with_files(
+            tmpdir.path(),
+            &[
+                ("CODEOWNERS", "* @owner\n*.js @js @owner\n/src/ @src\n"),
+                ("src/a.js", ""),
+                ("b.txt", ""),
+            ],
+        );
+
+        let mut app = ApplicationBuilder::new(repo).build().unwrap();
+        app.pathspecs = vec!["b.txt".to_owned(), "src/a.js".to_owned()];
+        let mut out = Vec::new();
+        app.write_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "b.txt: @owner\nsrc/a.js: @src\n"
+        );
+
+        app.all_owners = true;
+        let mut out = Vec::new();
+        app.write_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "b.txt: @owner\nsrc/a.js: @owner, @js, @src\n"
+        );
+    }
+
+    #[test]
+    fn test_limit() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = init_repo_with_files(
+            tmpdir.path(),
+            &[
+                ("CODEOWNERS", "* @owner\n"),
+                ("a.txt", ""),
+                ("b.txt", ""),
+                ("c.txt", ""),
+            ],
+        );
+
+        let mut app = ApplicationBuilder::new(repo)
+            .with_limit(Some(2))
+            .build()
+            .unwrap();
+        app.pathspecs = vec!["*".to_owned()];
+        let mut out = Vec::new();
+        app.write_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "CODEOWNERS: @owner\na.txt: @owner\n"
+        );
+    }
+
+    #[test]
+    fn test_changed_since() {
+        let tmpdir = TempDir::new().unwrap();
+        let repo = init_repo_with_files(
+            tmpdir.path(),
+            &[
+                ("CODEOWNERS", "* @owner\n*.md @writer\n"),
+                ("a.txt", ""),
+                ("b.md", ""),
+            ],
+        );
+        let author = Signature::now("foo", "foo@example.com").unwrap();
+        let commit = |repo: &Repository, message: &str| {
+            let mut index = repo.index().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents = match repo.head() {
+                Ok(head) => vec![head.peel_to_commit().unwrap()],
+                Err(_) => vec![],
+            };
+            let parents: Vec<_> = parents.iter().collect();
+            repo.commit(Some("HEAD"), &author, &author, message, &tree, &parents)
+                .unwrap();
+        };
+        commit(&repo, "1");
+
+        fs::write(tmpdir.path().join("b.md"), "changed").unwrap();
+        fs::write(tmpdir.path().join("c.txt"), "").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("b.md")).unwrap();
+        index.add_path(Path::new("c.txt")).unwrap();
+        index.write().unwrap();
+        commit(&repo, "2");
+
+        // staged but not committed yet
+        index.remove_path(Path::new("a.txt")).unwrap();
+        index.write().unwrap();
+
+        let mut app = ApplicationBuilder::new(repo)
+            .with_changed_since(Some("HEAD~1".to_owned()))
+            .build()
+            .unwrap();
+        let mut out = Vec::new();
+        app.write_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "a.txt: @owner\nb.md: @writer\nc.txt: @owner\n"
+        );
+
+        // only uncommitted change
+        let head = app.repo.revparse_single("HEAD").unwrap();
+        app.changed_since = Some(head.peel_to_tree().unwrap().id());
+        let mut out = Vec::new();
+        app.write_to(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "a.txt: @owner\n");
+    }
+}
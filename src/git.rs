@@ -1,7 +1,11 @@
 mod consts;
+mod credentials;
 mod gittime;
+mod glob;
 mod refname;
 
 pub use consts::IndexStage;
+pub use credentials::CredentialCallback;
 pub use gittime::GitTime;
-pub use refname::{HeadRef, RefnameError, RemoteRef};
+pub use glob::fnmatch;
+pub use refname::{escape_control, HeadRef, PullRef, PullRefKind, RefnameError, RemoteRef};
@@ -0,0 +1,121 @@
+use git2::{Config, Cred, CredentialType, RemoteCallbacks};
+use log::info;
+
+/// Answers credential requests from libgit2 by trying, in order:
+/// ssh-agent, git credential helper, then the default credential (NTLM/Negotiate).
+///
+/// Each kind of credential is tried at most once, so that libgit2 won't loop forever
+/// on authentication failure.
+pub struct CredentialCallback {
+    config: Config,
+    tried: CredentialType,
+}
+
+impl CredentialCallback {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            tried: CredentialType::empty(),
+        }
+    }
+
+    fn credentials(
+        &mut self,
+        url: &str,
+        username_from_url: Option<&str>,
+        allowed: CredentialType,
+    ) -> Result<Cred, git2::Error> {
+        let username = username_from_url.unwrap_or("git");
+
+        if self.try_once(allowed, CredentialType::USERNAME) {
+            return Cred::username(username);
+        }
+        if self.try_once(allowed, CredentialType::SSH_KEY) {
+            info!("trying ssh-agent for {}", url);
+            return Cred::ssh_key_from_agent(username);
+        }
+        if self.try_once(allowed, CredentialType::USER_PASS_PLAINTEXT) {
+            info!("trying credential helper for {}", url);
+            return Cred::credential_helper(&self.config, url, username_from_url);
+        }
+        if self.try_once(allowed, CredentialType::DEFAULT) {
+            return Cred::default();
+        }
+
+        Err(git2::Error::from_str(&format!(
+            "no more credentials to try for {}",
+            url
+        )))
+    }
+
+    fn try_once(&mut self, allowed: CredentialType, ty: CredentialType) -> bool {
+        if allowed.contains(ty) && !self.tried.contains(ty) {
+            self.tried.insert(ty);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Build RemoteCallbacks with this credential chain.
+    pub fn into_remote_callbacks(mut self) -> RemoteCallbacks<'static> {
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.credentials(move |url, username_from_url, allowed| {
+            self.credentials(url, username_from_url, allowed)
+        });
+        callbacks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::{CredentialType, FetchOptions, Repository, Signature};
+    use tempfile::TempDir;
+    use url::Url;
+
+    use super::CredentialCallback;
+
+    #[test]
+    fn credential_callback_tries_each_credential_once() {
+        let config = git2::Config::new().unwrap();
+        let mut cb = CredentialCallback::new(config);
+
+        assert!(cb
+            .credentials("ssh://example.com/repo", None, CredentialType::USERNAME)
+            .is_ok());
+        assert!(cb
+            .credentials("ssh://example.com/repo", None, CredentialType::USERNAME)
+            .is_err());
+    }
+
+    #[test]
+    fn credential_callback_fetches_through_file_url() {
+        let upstream = TempDir::new().unwrap();
+        let upstream_repo = Repository::init_bare(upstream.path()).unwrap();
+        {
+            let author = Signature::now("foo", "foo@example.com").unwrap();
+            let tree = upstream_repo.treebuilder(None).unwrap();
+            let tree = tree.write().unwrap();
+            let tree = upstream_repo.find_tree(tree).unwrap();
+            upstream_repo
+                .commit(Some("refs/heads/main"), &author, &author, "1", &tree, &[])
+                .unwrap();
+        }
+
+        let mut upstream_url = Url::parse("file:///").unwrap();
+        upstream_url.set_path(upstream.path().canonicalize().unwrap().to_str().unwrap());
+
+        let local = TempDir::new().unwrap();
+        let repo = Repository::init_bare(local.path()).unwrap();
+        let mut remote = repo.remote("origin", upstream_url.as_str()).unwrap();
+
+        let callbacks = CredentialCallback::new(repo.config().unwrap()).into_remote_callbacks();
+        let mut fetch_options = FetchOptions::new();
+        fetch_options.remote_callbacks(callbacks);
+        remote
+            .fetch(&["refs/heads/main"], Some(&mut fetch_options), None)
+            .unwrap();
+
+        assert!(repo.find_reference("refs/remotes/origin/main").is_ok());
+    }
+}
@@ -32,6 +32,16 @@ impl GitTime {
     pub fn now() -> Self {
         Self::from(chrono::Local::now())
     }
+
+    /// Current time in the specified fixed offset
+    pub fn now_in(tz: FixedOffset) -> Self {
+        Self::from(chrono::Utc::now().with_timezone(&tz))
+    }
+
+    /// Current time in UTC
+    pub fn now_utc() -> Self {
+        Self::from(chrono::Utc::now())
+    }
 }
 
 impl PartialEq for GitTime {
@@ -99,6 +109,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_gittime_now_in() {
+        let minus7 = FixedOffset::west_opt(7 * 3600).unwrap();
+        let plus0530 = FixedOffset::east_opt(5 * 3600 + 30 * 60).unwrap();
+
+        for tz in [minus7, plus0530] {
+            let dt: DateTime<FixedOffset> = GitTime::now_in(tz).into();
+            assert_eq!(dt.offset(), &tz);
+        }
+
+        let dt: DateTime<FixedOffset> = GitTime::now_utc().into();
+        assert_eq!(dt.offset(), &FixedOffset::east_opt(0).unwrap());
+
+        // same instant regardless of offset
+        let a = GitTime::now_in(minus7);
+        let b = GitTime::now_utc();
+        assert!((b.0.seconds() - a.0.seconds()).abs() <= 1);
+    }
+
     #[test]
     fn test_gittime_ord() {
         #[allow(clippy::type_complexity)]
@@ -0,0 +1,25 @@
+use std::ffi::CStr;
+
+use fnmatch_sys::{self, FNM_NOESCAPE};
+
+/// Match string against shell-style glob pattern, as fnmatch(3) does.
+pub fn fnmatch(pat: &CStr, s: &CStr) -> bool {
+    let pat = pat.as_ptr();
+    let s = s.as_ptr();
+
+    unsafe { fnmatch_sys::fnmatch(pat, s, FNM_NOESCAPE) == 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fnmatch;
+
+    #[test]
+    fn test_fnmatch() {
+        let cases = [(c"foo/*", c"foo/bar/baz")];
+
+        for (pat, s) in cases {
+            assert!(fnmatch(pat, s))
+        }
+    }
+}
@@ -13,12 +13,31 @@ pub struct RemoteRef {
     remote_len: usize,
 }
 
+/// Which ref of pull request GitHub provides.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PullRefKind {
+    /// Tip of the pull request branch.
+    Head,
+    /// Test merge of the pull request into its base branch.
+    Merge,
+}
+
+/// Ref of GitHub pull request, like `refs/pull/123/head`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PullRef {
+    full: String,
+    number: u64,
+    kind: PullRefKind,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum RefnameError {
     #[error("head ref name should be like refs/heads/BRANCH or just HEAD, but got {refname}")]
     InvalidHeadRefFormat { refname: String },
     #[error("remote ref name should be like refs/remotes/REMOTE/BRANCH, but got {refname}")]
     InvalidRemoteRefFormat { refname: String },
+    #[error("pull request ref name should be like refs/pull/NUMBER/head or refs/pull/NUMBER/merge, but got {refname}")]
+    InvalidPullRefFormat { refname: String },
 }
 
 impl HeadRef {
@@ -41,6 +60,31 @@ impl HeadRef {
     pub fn branch(&self) -> Option<&str> {
         self.0.branch()
     }
+
+    /// `/`-separated components of the branch name, or nothing for detached HEAD.
+    pub fn segments(&self) -> impl Iterator<Item = &str> {
+        self.branch().into_iter().flat_map(|b| b.split('/'))
+    }
+
+    /// Ref name with control characters escaped, for printing to terminal or logs.
+    pub fn display_safe(&self) -> String {
+        escape_control(self.as_str())
+    }
+
+    /// Remote tracking branch conventionally set when pushing this branch to the remote,
+    /// like `refs/remotes/origin/foo` for `refs/heads/foo`.
+    ///
+    /// Returns `None` for detached HEAD, or remote name which is empty or has `/`.
+    pub fn tracking_ref(&self, remote: &str) -> Option<RemoteRef> {
+        let branch = self.branch()?;
+        if remote.is_empty() || remote.contains('/') {
+            return None;
+        }
+        Some(RemoteRef {
+            full: format!("{}{}/{}", RemoteRef::PREFIX, remote, branch),
+            remote_len: remote.len(),
+        })
+    }
 }
 
 impl HeadRefImpl {
@@ -100,6 +144,24 @@ impl RemoteRef {
         Err(RefnameError::InvalidRemoteRefFormat { refname })
     }
 
+    /// Parse remote ref name, also accepting short-hand form like `origin/main`.
+    ///
+    /// Inputs starting with `refs/` are taken as full ref name, so `refs/heads/foo`
+    /// won't be interpreted as `refs/remotes/refs/heads/foo`.
+    pub fn parse_shorthand<S: Into<String>>(refname: S) -> Result<RemoteRef, RefnameError> {
+        let refname: String = refname.into();
+        if refname.starts_with("refs/") {
+            return Self::new(refname);
+        }
+
+        match refname.split_once('/') {
+            Some((remote, branch)) if !remote.is_empty() && !branch.is_empty() => {
+                Self::new(format!("{}{}", Self::PREFIX, refname))
+            }
+            _ => Err(RefnameError::InvalidRemoteRefFormat { refname }),
+        }
+    }
+
     pub fn as_str(&self) -> &str {
         self.full.as_str()
     }
@@ -114,13 +176,84 @@ impl RemoteRef {
         let i = Self::PREFIX.len() + self.remote_len + "/".len();
         &self.full[i..]
     }
+
+    /// `/`-separated components of the branch name, excluding the remote name.
+    pub fn branch_segments(&self) -> impl Iterator<Item = &str> {
+        self.branch().split('/')
+    }
+
+    /// Ref name with control characters escaped, for printing to terminal or logs.
+    pub fn display_safe(&self) -> String {
+        escape_control(self.as_str())
+    }
+}
+
+impl PullRef {
+    const PREFIX: &'static str = "refs/pull/";
+
+    pub fn new<S: Into<String>>(refname: S) -> Result<PullRef, RefnameError> {
+        let refname: String = refname.into();
+        let parsed = refname
+            .strip_prefix(Self::PREFIX)
+            .and_then(|rest| rest.split_once('/'))
+            .and_then(|(number, kind)| {
+                // reject signs and leading zeros, which are not how GitHub names the ref.
+                if !number.bytes().all(|b| b.is_ascii_digit()) || number.starts_with('0') {
+                    return None;
+                }
+                let kind = match kind {
+                    "head" => PullRefKind::Head,
+                    "merge" => PullRefKind::Merge,
+                    _ => return None,
+                };
+                Some((number.parse().ok()?, kind))
+            });
+
+        match parsed {
+            Some((number, kind)) => Ok(PullRef {
+                full: refname,
+                number,
+                kind,
+            }),
+            None => Err(RefnameError::InvalidPullRefFormat { refname }),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.full.as_str()
+    }
+
+    /// Number of the pull request.
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    pub fn kind(&self) -> PullRefKind {
+        self.kind
+    }
+}
+
+/// Text with control characters escaped, for printing names like branches or paths
+/// to terminal or logs.
+///
+/// Backslash is also escaped so that escaped text is not ambiguous.
+pub fn escape_control(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_control() || c == '\\' {
+                c.escape_default().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use crate::git::refname::HeadRef;
 
-    use super::RemoteRef;
+    use super::{PullRef, PullRefKind, RemoteRef};
 
     #[test]
     fn test_valid_head_ref() {
@@ -136,6 +269,65 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_head_ref_segments() {
+        let cases: [(&str, &[&str]); 3] = [
+            ("refs/heads/foo", &["foo"]),
+            ("refs/heads/feature/foo/bar", &["feature", "foo", "bar"]),
+            ("HEAD", &[]),
+        ];
+
+        for (given, want) in cases {
+            let got = HeadRef::new(given).unwrap();
+            assert_eq!(got.segments().collect::<Vec<_>>(), want, "{}", given);
+        }
+    }
+
+    #[test]
+    fn test_head_ref_tracking_ref() {
+        let cases = [
+            ("refs/heads/foo", "origin", Some("refs/remotes/origin/foo")),
+            (
+                "refs/heads/feature/foo",
+                "fork",
+                Some("refs/remotes/fork/feature/foo"),
+            ),
+            ("HEAD", "origin", None),
+            ("refs/heads/foo", "", None),
+            ("refs/heads/foo", "a/b", None),
+        ];
+
+        for (given, remote, want) in cases {
+            let got = HeadRef::new(given).unwrap().tracking_ref(remote);
+            assert_eq!(
+                got,
+                want.map(|r| RemoteRef::new(r).unwrap()),
+                "{} on {}",
+                given,
+                remote
+            );
+        }
+
+        let got = HeadRef::new("refs/heads/feature/foo")
+            .unwrap()
+            .tracking_ref("origin")
+            .unwrap();
+        assert_eq!(got.remote(), "origin");
+        assert_eq!(got.branch(), "feature/foo");
+    }
+
+    #[test]
+    fn test_display_safe() {
+        let got = HeadRef::new("refs/heads/foo\x1b[2Jbar\n").unwrap();
+        assert_eq!(got.display_safe(), "refs/heads/foo\\u{1b}[2Jbar\\n");
+
+        let got = RemoteRef::new("refs/remotes/origin/a\\b\rc").unwrap();
+        assert_eq!(got.display_safe(), "refs/remotes/origin/a\\\\b\\rc");
+
+        let got = RemoteRef::new("refs/remotes/origin/日本語").unwrap();
+        assert_eq!(got.display_safe(), "refs/remotes/origin/日本語");
+    }
+
     #[test]
     fn test_invalid_head_ref() {
         let cases = ["foo", "foo/bar", "refs/tags/v0", "refs/remotes/origin/foo"];
@@ -161,6 +353,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_remote_ref_branch_segments() {
+        let cases: [(&str, &[&str]); 2] = [
+            ("refs/remotes/origin/foo", &["foo"]),
+            (
+                "refs/remotes/origin/feature/foo/bar",
+                &["feature", "foo", "bar"],
+            ),
+        ];
+
+        for (given, want) in cases {
+            let got = RemoteRef::new(given).unwrap();
+            assert_eq!(got.branch_segments().collect::<Vec<_>>(), want, "{}", given);
+        }
+    }
+
     #[test]
     fn test_invalid_remote_ref() {
         let cases = [
@@ -175,4 +383,68 @@ mod tests {
             assert!(got.is_err())
         }
     }
+
+    #[test]
+    fn test_remote_ref_parse_shorthand() {
+        let cases = [
+            ("origin/main", "refs/remotes/origin/main"),
+            ("refs/remotes/origin/main", "refs/remotes/origin/main"),
+            ("origin/foo/bar", "refs/remotes/origin/foo/bar"),
+        ];
+
+        for (given, want) in cases {
+            let got = RemoteRef::parse_shorthand(given).unwrap();
+            assert_eq!(got, RemoteRef::new(want).unwrap(), "given {:?}", given);
+        }
+
+        let cases = [
+            "main",
+            "origin/",
+            "/main",
+            "refs/heads/foo/bar",
+            "refs/remotes/origin",
+        ];
+
+        for given in cases {
+            let got = RemoteRef::parse_shorthand(given);
+            assert!(got.is_err(), "given {:?}", given)
+        }
+    }
+
+    #[test]
+    fn test_valid_pull_ref() {
+        let cases = [
+            ("refs/pull/123/head", 123, PullRefKind::Head),
+            ("refs/pull/1/merge", 1, PullRefKind::Merge),
+        ];
+
+        for (given, want_number, want_kind) in cases {
+            let got = PullRef::new(given).unwrap();
+            assert_eq!(got.as_str(), given);
+            assert_eq!(got.number(), want_number, "{}", given);
+            assert_eq!(got.kind(), want_kind, "{}", given);
+        }
+    }
+
+    #[test]
+    fn test_invalid_pull_ref() {
+        let cases = [
+            "refs/pull/123",
+            "refs/pull/123/",
+            "refs/pull/123/base",
+            "refs/pull/123/head/foo",
+            "refs/pull/abc/head",
+            "refs/pull/+1/head",
+            "refs/pull/0123/head",
+            "refs/pull//head",
+            "refs/pull/99999999999999999999999/head",
+            "refs/heads/pull/123/head",
+            "pull/123/head",
+        ];
+
+        for given in cases {
+            let got = PullRef::new(given);
+            assert!(got.is_err(), "given {:?}", given)
+        }
+    }
 }
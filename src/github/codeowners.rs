@@ -1,14 +1,26 @@
-use std::{io::BufRead, path::Path};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+    io::{BufRead, Read},
+    ops::Range,
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+};
 
 use git2::Repository;
 use log::warn;
+use regex::RegexSet;
 
 use crate::git::IndexStage;
 
 use self::pattern::{Pattern, PatternError};
 
+mod document;
 mod pattern;
 
+pub use self::document::{CodeOwnersDocument, DocumentEntry, DocumentLine, LintKind, LintWarning};
+
 #[derive(Debug, PartialEq)]
 struct Record {
     pattern: String,
@@ -21,6 +33,10 @@ enum CodeOwnersEntryError {
     PatternMissing,
     #[error("{0}")]
     PatternError(String),
+    #[error("{0} is not supported")]
+    Unsupported(&'static str),
+    #[error("invalid owner: {0}")]
+    InvalidOwner(String),
 }
 
 impl From<PatternError> for CodeOwnersEntryError {
@@ -33,10 +49,9 @@ impl TryFrom<String> for Record {
     type Error = CodeOwnersEntryError;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        let value = if let Some((i, _)) = value.chars().enumerate().find(|(_, c)| c == &'#') {
-            &value[0..i]
-        } else {
-            &value[..]
+        let value = match find_comment(&value) {
+            Some(i) => &value[..i],
+            None => &value[..],
         };
 
         let mut iter = value.split_whitespace();
@@ -53,9 +68,449 @@ impl TryFrom<String> for Record {
     }
 }
 
+/// Byte offset of `#` starting comment in the line.
+///
+/// `#` is a comment only at the start of the line or after whitespace,
+/// so that `a#b` is taken as a pattern with literal `#`.
+fn find_comment(line: &str) -> Option<usize> {
+    let mut prev: Option<char> = None;
+    for (i, c) in line.char_indices() {
+        if c == '#' && prev.is_none_or(char::is_whitespace) {
+            return Some(i);
+        }
+        prev = Some(c);
+    }
+    None
+}
+
+/// Owner written in CODEOWNERS file, classified by its form.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Owner {
+    /// `@user`
+    User(String),
+    /// `@org/team`
+    Team { org: String, team: String },
+    /// `@org/*`, meaning any team in the organization.
+    ///
+    /// GitHub doesn't expand it, but some tools do.
+    TeamWildcard(String),
+    /// `user@example.com`
+    Email(String),
+}
+
+impl Owner {
+    /// Classify the owner, or returns `None` if it is malformed.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use git_toolbox::github::codeowners::Owner;
+    ///
+    /// assert_eq!(Owner::parse("@org/*"), Some(Owner::TeamWildcard("org".to_owned())));
+    /// assert_eq!(Owner::parse("@org/"), None);
+    /// ```
+    pub fn parse(owner: &str) -> Option<Owner> {
+        match owner.strip_prefix('@') {
+            Some(name) => match name.split_once('/') {
+                Some(("", _)) => None,
+                Some((org, "*")) => Some(Owner::TeamWildcard(org.to_owned())),
+                Some((org, team)) if !team.is_empty() && !team.contains('/') => Some(Owner::Team {
+                    org: org.to_owned(),
+                    team: team.to_owned(),
+                }),
+                Some(_) => None,
+                None if !name.is_empty() && !name.contains('@') => {
+                    Some(Owner::User(name.to_owned()))
+                }
+                None => None,
+            },
+            None => owner
+                .split_once('@')
+                .filter(|(local, domain)| !local.is_empty() && domain.contains('.'))
+                .map(|_| Owner::Email(owner.to_owned())),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{CodeOwnersEntryError, Record};
+    use super::{
+        CodeOwners, CodeOwnersEntryError, MatchOrder, Owner, OwnershipChange, ParseError, Record,
+    };
+
+    #[test]
+    fn is_owner() {
+        let data = "\
+* @everyone
+*.js @alice @org/Frontend
+/docs/ # unowned
+";
+        let codeowners = CodeOwners::try_from_bufread(data.as_bytes()).unwrap();
+
+        let test_cases = [
+            ("foo.js", "@alice", true),
+            ("foo.js", "@org/frontend", true),
+            ("foo.js", "@everyone", false),
+            ("foo.rs", "@everyone", true),
+            ("foo.rs", "@alice", false),
+            // unowned by the last matching pattern
+            ("docs/README.md", "@everyone", false),
+        ];
+        for (i, (path, owner, want)) in test_cases.into_iter().enumerate() {
+            assert_eq!(
+                codeowners.is_owner(path, owner),
+                want,
+                "#{}: is {} owner of {}",
+                i,
+                owner,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn reviewers_for() {
+        let codeowners: CodeOwners = "\
+* @everyone
+*.js @alice @org/frontend
+/docs/ @writer @alice
+/vendor/
+"
+        .parse()
+        .unwrap();
+
+        let got = codeowners.reviewers_for([
+            "src/a.js",
+            "docs/guide.md",
+            "vendor/lib.js",
+            "Cargo.toml",
+            "src/b.js",
+        ]);
+        let got: Vec<_> = got.iter().map(|(k, v)| (k.as_str(), v.clone())).collect();
+        assert_eq!(
+            got,
+            vec![
+                ("@alice", vec!["src/a.js", "docs/guide.md", "src/b.js"]),
+                ("@everyone", vec!["Cargo.toml"]),
+                ("@org/frontend", vec!["src/a.js", "src/b.js"]),
+                ("@writer", vec!["docs/guide.md"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn owners_under() {
+        let codeowners: CodeOwners = "\
+* @everyone
+*.js @frontend
+/src/ @backend
+/src/api/ @api
+/src/api/v1/legacy.rs @legacy
+/src/app/ @app
+/src/*/generated/
+/docs/ @writer
+"
+        .parse()
+        .unwrap();
+
+        let cases = [
+            (
+                "src/api",
+                vec![
+                    ("*", vec!["@everyone"]),
+                    ("*.js", vec!["@frontend"]),
+                    ("/src/", vec!["@backend"]),
+                    ("/src/api/", vec!["@api"]),
+                    ("/src/api/v1/legacy.rs", vec!["@legacy"]),
+                    ("/src/*/generated/", vec![]),
+                ],
+            ),
+            (
+                "src/api/v2/",
+                vec![
+                    ("*", vec!["@everyone"]),
+                    ("*.js", vec!["@frontend"]),
+                    ("/src/", vec!["@backend"]),
+                    ("/src/api/", vec!["@api"]),
+                    ("/src/*/generated/", vec![]),
+                ],
+            ),
+            (
+                "docs",
+                vec![
+                    ("*", vec!["@everyone"]),
+                    ("*.js", vec!["@frontend"]),
+                    ("/docs/", vec!["@writer"]),
+                ],
+            ),
+        ];
+        for (idx, (dir, want)) in cases.into_iter().enumerate() {
+            let got: Vec<_> = codeowners
+                .owners_under(dir)
+                .into_iter()
+                .map(|(pattern, owners)| (pattern, owners.iter().map(|o| o.as_str()).collect()))
+                .collect();
+            assert_eq!(got, want, "#{}: under {}", idx, dir);
+        }
+    }
+
+    #[test]
+    fn match_order() {
+        let data = "\
+*.js @a
+* @b
+/docs/
+";
+
+        let cases = [
+            (MatchOrder::Last, "a.js", vec!["@b"]),
+            (MatchOrder::Last, "a.rs", vec!["@b"]),
+            (MatchOrder::Last, "docs/a.js", vec![]),
+            (MatchOrder::First, "a.js", vec!["@a"]),
+            (MatchOrder::First, "a.rs", vec!["@b"]),
+            (MatchOrder::First, "docs/a.js", vec!["@a"]),
+        ];
+        for (order, path, want) in cases {
+            let codeowners = data.parse::<CodeOwners>().unwrap().with_match_order(order);
+            let want: Vec<String> = want.into_iter().map(|s| s.to_owned()).collect();
+            assert_eq!(
+                codeowners.find_owners(path),
+                Some(&want),
+                "{:?}: {}",
+                order,
+                path
+            );
+            assert_eq!(
+                codeowners.find_owners_bulk([path])[0].1,
+                Some(&want),
+                "{:?}: {} in bulk",
+                order,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn find_all_owners() {
+        let data = "\
+* @a
+*.js @b @A
+/docs/
+/docs/api/ @c
+";
+        let codeowners = data.parse::<CodeOwners>().unwrap();
+
+        let cases = [
+            ("a.rs", Some(vec!["@a"])),
+            ("a.js", Some(vec!["@a", "@b"])),
+            ("docs/a.md", Some(vec!["@a"])),
+            ("docs/api/a.js", Some(vec!["@a", "@b", "@c"])),
+        ];
+        for (path, want) in cases {
+            let want: Option<Vec<String>> =
+                want.map(|w| w.into_iter().map(|s| s.to_owned()).collect());
+            assert_eq!(codeowners.find_all_owners(path), want, "{}", path);
+        }
+
+        let codeowners = "*.js @b\n".parse::<CodeOwners>().unwrap();
+        assert_eq!(codeowners.find_all_owners("a.rs"), None);
+    }
+
+    #[test]
+    fn diff() {
+        let before: CodeOwners = "\
+* @everyone
+*.js @frontend
+/docs/ @writer
+/apps/ @apps
+"
+        .parse()
+        .unwrap();
+        let after: CodeOwners = "\
+* @everyone
+*.js @frontend
+/docs/ @Writer
+/apps/ @apps
+/apps/legacy/ @legacy
+/tools/
+"
+        .parse()
+        .unwrap();
+        let owners = |o: &[&str]| Some(o.iter().map(|o| o.to_string()).collect::<Vec<_>>());
+
+        assert_eq!(
+            before.diff(&after),
+            vec![
+                OwnershipChange {
+                    path: "apps/legacy/x".to_owned(),
+                    before: owners(&["@apps"]),
+                    after: owners(&["@legacy"]),
+                },
+                OwnershipChange {
+                    path: "tools/x".to_owned(),
+                    before: owners(&["@everyone"]),
+                    after: owners(&[]),
+                },
+            ]
+        );
+        assert!(before.diff(&before).is_empty());
+
+        let unowned: CodeOwners = "*.js @frontend\n".parse().unwrap();
+        assert_eq!(
+            unowned.diff_paths(&before, ["a.js", "a.rs"]),
+            vec![OwnershipChange {
+                path: "a.rs".to_owned(),
+                before: None,
+                after: owners(&["@everyone"]),
+            }]
+        );
+    }
+
+    #[test]
+    fn find_owners_bytes() {
+        let codeowners: CodeOwners = "* @everyone\n*.js @frontend\n/docs/ @writer\n"
+            .parse()
+            .unwrap();
+        let owners = |o: &str| Some(vec![o.to_owned()]);
+
+        assert_eq!(
+            codeowners.find_owners_bytes(b"\xff.js"),
+            owners("@frontend").as_ref()
+        );
+        assert_eq!(
+            codeowners.find_owners_bytes(b"docs/\xff.js"),
+            owners("@writer").as_ref()
+        );
+        assert_eq!(
+            codeowners.find_owners_bytes(b"\xff/a.rs"),
+            owners("@everyone").as_ref()
+        );
+        assert_eq!(
+            codeowners.find_match_bytes(b"docs/\xfe").map(|m| m.line()),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn crlf() {
+        let data = "* @everyone\r\n*.js @alice @bob\r\n/docs/\r\n/apps/ @carol\r";
+        let codeowners = CodeOwners::try_from_bufread(data.as_bytes()).unwrap();
+
+        assert_eq!(
+            codeowners.find_owners("foo.js"),
+            Some(&vec!["@alice".to_owned(), "@bob".to_owned()])
+        );
+        assert_eq!(codeowners.find_owners("docs/README.md"), Some(&vec![]));
+        assert_eq!(
+            codeowners.find_owners("apps/main.rs"),
+            Some(&vec!["@carol".to_owned()])
+        );
+        assert!(CodeOwners::validate_bufread(data.as_bytes()).is_empty());
+    }
+
+    #[test]
+    fn byte_ranges() {
+        let data: &[u8] = b"# comment\n* @everyone\r\n\n*.js @alice\n\xff @nobody\n/docs/ @bob";
+        let codeowners = CodeOwners::try_from_bufread(data).unwrap();
+        let got: Vec<_> = codeowners
+            .entries()
+            .map(|m| (m.pattern(), m.line(), m.byte_range()))
+            .collect();
+
+        assert_eq!(
+            got,
+            vec![("*", 2, 10..21), ("*.js", 4, 24..35), ("/docs/", 6, 46..57)]
+        );
+        for (_, _, range) in got {
+            assert!(!data[range].contains(&b'\n'));
+        }
+        assert_eq!(&data[24..35], b"*.js @alice");
+    }
+
+    #[test]
+    fn from_str() {
+        let codeowners: CodeOwners = "\
+# comment
+* @everyone
+
+/docs/ @writer # docs
+"
+        .parse()
+        .unwrap();
+        assert_eq!(
+            codeowners.find_owners("docs/README.md"),
+            Some(&vec!["@writer".to_owned()])
+        );
+        assert_eq!(
+            codeowners.find_owners("src/lib.rs"),
+            Some(&vec!["@everyone".to_owned()])
+        );
+
+        let codeowners = CodeOwners::try_from("*.rs @rustacean").unwrap();
+        assert!(codeowners.is_owner("src/lib.rs", "@rustacean"));
+    }
+
+    #[test]
+    fn owner_parse() {
+        let cases = [
+            ("@alice", Some(Owner::User("alice".to_owned()))),
+            (
+                "@org/team",
+                Some(Owner::Team {
+                    org: "org".to_owned(),
+                    team: "team".to_owned(),
+                }),
+            ),
+            ("@org/*", Some(Owner::TeamWildcard("org".to_owned()))),
+            (
+                "alice@example.com",
+                Some(Owner::Email("alice@example.com".to_owned())),
+            ),
+            ("alice", None),
+            ("@", None),
+            ("@org/", None),
+            ("@/*", None),
+            ("@org/*/a", None),
+            ("@bad@name", None),
+        ];
+        for (owner, want) in cases {
+            assert_eq!(Owner::parse(owner), want, "{}", owner);
+        }
+
+        let data = "* @org/*\n";
+        assert!(CodeOwners::validate_bufread(data.as_bytes()).is_empty());
+    }
+
+    #[test]
+    fn validate() {
+        let data = b"\
+# comment
+* @everyone
+*.js alice @org/Frontend
+!*.md @writer
+docs/[ab]/ @writer
+/apps/ docs@example.com @org/ @bad@name
+/ok/ @org/team user@example.com # fine
+/bin/ @ops \xff
+";
+        let got = CodeOwners::validate_bufread(&data[..]);
+        let want = [
+            (3, "invalid owner: alice"),
+            (4, "negation `!` is not supported"),
+            (5, "character range `[ ]` is not supported"),
+            (6, "invalid owner: @org/"),
+        ];
+        assert_eq!(
+            got[..4],
+            want.map(|(line, message)| ParseError {
+                line,
+                message: message.to_owned()
+            })
+        );
+        // invalid UTF-8
+        assert_eq!(got.len(), 5);
+        assert_eq!(got[4].line, 8);
+    }
 
     #[test]
     fn parse() {
@@ -89,6 +544,28 @@ mod tests {
                     owners: vec!["@foo".to_string(), "@bar".to_string()],
                 }),
             ),
+            // `#` in the middle of token is not a comment
+            (
+                "a#b @owner",
+                Ok(Record {
+                    pattern: "a#b".to_string(),
+                    owners: vec!["@owner".to_string()],
+                }),
+            ),
+            (
+                "a #comment",
+                Ok(Record {
+                    pattern: "a".to_string(),
+                    owners: vec![],
+                }),
+            ),
+            (
+                "日本#語 @foo\t# @bar",
+                Ok(Record {
+                    pattern: "日本#語".to_string(),
+                    owners: vec!["@foo".to_string()],
+                }),
+            ),
         ];
 
         for (i, (input, want)) in test_cases.into_iter().enumerate() {
@@ -105,17 +582,128 @@ mod tests {
     }
 }
 
+/// Lines of the blob like `BufRead::lines`, with 1-based line numbers and byte ranges.
+///
+/// Reading stops at the first I/O error, so that the byte ranges stay accurate.
+fn lines_with_ranges<T: BufRead>(
+    mut blob: T,
+) -> impl Iterator<Item = (usize, Range<usize>, io::Result<String>)> {
+    let mut line = 0;
+    let mut offset = 0;
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let mut buf = Vec::new();
+        let read = match blob.read_until(b'\n', &mut buf) {
+            Ok(0) => return None,
+            Ok(read) => read,
+            Err(e) => {
+                done = true;
+                return Some((line + 1, offset..offset, Err(e)));
+            }
+        };
+        line += 1;
+        let start = offset;
+        offset += read;
+        if buf.last() == Some(&b'\n') {
+            buf.pop();
+            if buf.last() == Some(&b'\r') {
+                buf.pop();
+            }
+        }
+        let bytes = start..start + buf.len();
+        let text =
+            String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+        Some((line, bytes, text))
+    })
+}
+
+/// Path whose winning owners differ between two versions of CODEOWNERS file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OwnershipChange {
+    pub path: String,
+    /// Owners by the old version, or `None` if unowned.
+    pub before: Option<Vec<String>>,
+    /// Owners by the new version, or `None` if unowned.
+    pub after: Option<Vec<String>>,
+}
+
 #[derive(Debug)]
 pub struct CodeOwners {
     // CODEOWNERS file entries, in reversed order.
-    // Winning owners are from last-match entry in the file.
+    // Winning owners are from last-match entry in the file, unless `order` says otherwise.
     entries: Vec<CodeOwnersEntry>,
+    order: MatchOrder,
+}
+
+/// Which entry wins when multiple entries match a path.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MatchOrder {
+    /// The first matching entry in the file wins, as some tools other than GitHub do.
+    First,
+    /// The last matching entry in the file wins, as GitHub does.
+    #[default]
+    Last,
 }
 
 #[derive(Debug)]
 struct CodeOwnersEntry {
     pattern: Pattern,
     owners: Vec<String>,
+    // 1-based line number in CODEOWNERS file, or 0 if unknown.
+    line: usize,
+    // byte range of the line in CODEOWNERS file excluding line ending, or empty if unknown.
+    bytes: Range<usize>,
+    // path of CODEOWNERS file in the repository, if read from one.
+    source: Option<Arc<str>>,
+}
+
+/// CODEOWNERS entry which determined owners of a path.
+#[derive(Debug, Clone, Copy)]
+pub struct Match<'a> {
+    entry: &'a CodeOwnersEntry,
+}
+
+// Matches are equal when they are of the same entry.
+impl PartialEq for Match<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.entry, other.entry)
+    }
+}
+
+impl<'a> Match<'a> {
+    /// Owners of the path.
+    pub fn owners(&self) -> &'a Vec<String> {
+        &self.entry.owners
+    }
+
+    /// Pattern of the matched entry, as written in CODEOWNERS file.
+    pub fn pattern(&self) -> &'a str {
+        self.entry.pattern.as_str()
+    }
+
+    /// 1-based line number of the matched entry in CODEOWNERS file it came from.
+    pub fn line(&self) -> usize {
+        self.entry.line
+    }
+
+    /// Byte range of the matched entry's line in CODEOWNERS file it came from,
+    /// excluding the line ending.
+    pub fn byte_range(&self) -> Range<usize> {
+        self.entry.bytes.clone()
+    }
+
+    /// Path of CODEOWNERS file the matched entry came from, if read from a repository.
+    pub fn source(&self) -> Option<&'a str> {
+        self.entry.source.as_deref()
+    }
+
+    /// Check if the matched entry is the catch-all default, i.e. the pattern is lone `*`.
+    pub fn is_default(&self) -> bool {
+        self.pattern() == "*"
+    }
 }
 
 impl TryFrom<Record> for CodeOwnersEntry {
@@ -127,6 +715,9 @@ impl TryFrom<Record> for CodeOwnersEntry {
         Ok(CodeOwnersEntry {
             pattern: Pattern::new(pattern)?,
             owners,
+            line: 0,
+            bytes: 0..0,
+            source: None,
         })
     }
 }
@@ -139,12 +730,32 @@ impl TryFrom<String> for CodeOwnersEntry {
     }
 }
 
+/// Malformed line found by `CodeOwners::validate_bufread`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// 1-based line number.
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.line, self.message)
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum CodeOwnersError {
     #[error(
         "CODEOWNERS file is not indexed in the repository; did you already commit or stage it?"
     )]
     NotIndexed,
+    #[error("CODEOWNERS file is not found in the working tree")]
+    NotFound,
+    #[error("{path} is larger than the limit of {limit} bytes")]
+    TooLarge { path: String, limit: u64 },
+    #[error("malformed line in {path}: {error}")]
+    Malformed { path: String, error: ParseError },
     #[error("libgit2 API error: {0}")]
     GitError(#[from] git2::Error),
     #[error("i/o error: {0}")]
@@ -168,54 +779,690 @@ impl CodeOwners {
     /// assert_eq!(codeowners.find_owners("foo/bar.js"), Some(&vec![String::from("frontend-developer")]));
     /// ```
     pub fn try_from_bufread<T: BufRead>(blob: T) -> Result<CodeOwners, CodeOwnersError> {
+        CodeOwnersBuilder::new().from_bufread(blob)
+    }
+
+    fn parse_bufread<T: BufRead>(
+        blob: T,
+        source: Option<&str>,
+        strict: bool,
+    ) -> Result<CodeOwners, CodeOwnersError> {
         // Forgetting errors in parsing is reasonable the repository barely contains invalid code owner records,
         // as GitHub enforces CODEOWNERS file being valid.
         // (and we are reading CODEOWNERS from index)
-        let mut entries: Vec<CodeOwnersEntry> = blob
-            .lines()
-            .enumerate()
-            .filter_map(|(idx, ln)| match ln {
-                Ok(s) => match CodeOwnersEntry::try_from(s) {
-                    Ok(entry) => Some(entry),
-                    Err(CodeOwnersEntryError::PatternMissing) => None,
-                    Err(e) => {
-                        warn!("line {} at CODEOWNERS: {}", idx + 1, e);
-                        None
-                    }
-                },
-                Err(e) => {
-                    warn!("line {} at CODEOWNERS: {}", idx + 1, e);
-                    None
+        // CRLF line endings need no care; `lines_with_ranges()` strips them
+        // and stray `\r` is taken as a whitespace separating tokens.
+        let source: Option<Arc<str>> = source.map(Arc::from);
+        let mut entries = Vec::new();
+        for (line, bytes, ln) in lines_with_ranges(blob) {
+            let message = match ln.map(CodeOwnersEntry::try_from) {
+                Ok(Ok(entry)) => {
+                    entries.push(CodeOwnersEntry {
+                        line,
+                        bytes,
+                        source: source.clone(),
+                        ..entry
+                    });
+                    continue;
                 }
-            })
-            .collect();
+                Ok(Err(CodeOwnersEntryError::PatternMissing)) => continue,
+                Ok(Err(e)) => e.to_string(),
+                Err(e) => e.to_string(),
+            };
+            if strict {
+                return Err(CodeOwnersError::Malformed {
+                    path: source.as_deref().unwrap_or("CODEOWNERS").to_owned(),
+                    error: ParseError { line, message },
+                });
+            }
+            warn!("line {} at CODEOWNERS: {}", line, message);
+        }
         entries.reverse();
 
-        Ok(CodeOwners { entries })
+        Ok(CodeOwners {
+            entries,
+            order: MatchOrder::default(),
+        })
     }
 
-    /// Read CODEOWNERS file from repository's index.
-    pub fn try_from_repo(repo: &Repository) -> Result<CodeOwners, CodeOwnersError> {
-        let path = Path::new(".github/CODEOWNERS");
+    /// Check CODEOWNERS file data in buffer strictly, rather than skipping malformed lines.
+    ///
+    /// In addition to lines `try_from_bufread` warns, syntax GitHub doesn't support
+    /// (negation and character ranges) and owners not in `@user`, `@org/team`
+    /// or email form are reported.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use git_toolbox::github::codeowners::CodeOwners;
+    ///
+    /// let data = "* @owner\n!*.md @writer\n";
+    /// let errors = CodeOwners::validate_bufread(data.as_bytes());
+    ///
+    /// assert_eq!(errors.len(), 1);
+    /// assert_eq!(errors[0].line, 2);
+    /// ```
+    pub fn validate_bufread<T: BufRead>(blob: T) -> Vec<ParseError> {
+        blob.lines()
+            .enumerate()
+            .filter_map(|(idx, ln)| {
+                let message = match ln {
+                    Ok(s) => Self::validate_line(s).err()?.to_string(),
+                    Err(e) => e.to_string(),
+                };
+                Some(ParseError {
+                    line: idx + 1,
+                    message,
+                })
+            })
+            .collect()
+    }
+
+    fn validate_line(line: String) -> Result<(), CodeOwnersEntryError> {
+        let record = match Record::try_from(line) {
+            Err(CodeOwnersEntryError::PatternMissing) => return Ok(()),
+            r => r?,
+        };
+        if record.pattern.starts_with('!') {
+            return Err(CodeOwnersEntryError::Unsupported("negation `!`"));
+        }
+        if record.pattern.contains('[') {
+            return Err(CodeOwnersEntryError::Unsupported("character range `[ ]`"));
+        }
+        if let Some(owner) = record.owners.iter().find(|o| Owner::parse(o).is_none()) {
+            return Err(CodeOwnersEntryError::InvalidOwner(owner.clone()));
+        }
+        CodeOwnersEntry::try_from(record).map(|_| ())
+    }
+
+    /// Check CODEOWNERS file used by `try_from_repo` strictly.
+    ///
+    /// Returns the path of the file together.
+    pub fn validate_repo(repo: &Repository) -> Result<(String, Vec<ParseError>), CodeOwnersError> {
+        let index = repo.index()?;
+        for path in Self::locations(repo)? {
+            if let Some(content) = Self::read_index_blob(repo, &index, &path)? {
+                return Ok((path, Self::validate_bufread(content.as_slice())));
+            }
+        }
+
+        Err(CodeOwnersError::NotIndexed)
+    }
+
+    /// Locations of CODEOWNERS file in the order GitHub searches.
+    pub const LOCATIONS: [&'static str; 3] =
+        [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
 
-        if let Some(entry) = repo.index()?.get_path(path, IndexStage::Normal.into()) {
+    fn read_index_blob(
+        repo: &Repository,
+        index: &git2::Index,
+        path: &str,
+    ) -> Result<Option<Vec<u8>>, CodeOwnersError> {
+        if let Some(entry) = index.get_path(Path::new(path), IndexStage::Normal.into()) {
             let blob = repo
                 .find_object(entry.id, Some(git2::ObjectType::Blob))?
                 .into_blob()
                 .unwrap();
-            Ok(Self::try_from_bufread(blob.content())?)
+            Ok(Some(blob.content().to_vec()))
         } else {
-            Err(CodeOwnersError::NotIndexed)
+            Ok(None)
+        }
+    }
+
+    /// Locations to search CODEOWNERS file in priority order;
+    /// paths in `whose.codeownerspath` config followed by `LOCATIONS`.
+    fn locations(repo: &Repository) -> Result<Vec<String>, CodeOwnersError> {
+        let mut locations = Vec::new();
+        repo.config()?
+            .multivar("whose.codeownerspath", None)?
+            .for_each(|entry| {
+                if let Some(path) = entry.value() {
+                    locations.push(path.to_owned());
+                }
+            })?;
+        locations.extend(Self::LOCATIONS.iter().map(|s| s.to_string()));
+
+        Ok(locations)
+    }
+
+    /// Read CODEOWNERS file from repository's index.
+    ///
+    /// Like GitHub does, only the first file found is used.
+    /// Paths in `whose.codeownerspath` config are searched before `LOCATIONS`.
+    pub fn try_from_repo(repo: &Repository) -> Result<CodeOwners, CodeOwnersError> {
+        CodeOwnersBuilder::new().from_repo(repo)
+    }
+
+    /// Read all CODEOWNERS files found from repository's index, and merge them.
+    ///
+    /// Rules in the file searched earlier take precedence,
+    /// as if the files were concatenated in the reversed order of search.
+    pub fn try_from_repo_merged(repo: &Repository) -> Result<CodeOwners, CodeOwnersError> {
+        CodeOwnersBuilder::new().with_merge(true).from_repo(repo)
+    }
+
+    /// Change which entry wins when multiple entries match a path.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use git_toolbox::github::codeowners::{CodeOwners, MatchOrder};
+    ///
+    /// let data = r#"
+    /// *.js @alice
+    /// * @bob
+    /// "#;
+    /// let codeowners: CodeOwners = data.parse().unwrap();
+    /// assert_eq!(codeowners.find_owners("a.js").unwrap(), &vec!["@bob"]);
+    ///
+    /// let codeowners = codeowners.with_match_order(MatchOrder::First);
+    /// assert_eq!(codeowners.find_owners("a.js").unwrap(), &vec!["@alice"]);
+    /// ```
+    pub fn with_match_order(self, order: MatchOrder) -> Self {
+        Self { order, ..self }
+    }
+
+    /// All entries in the order written in CODEOWNERS file.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use git_toolbox::github::codeowners::CodeOwners;
+    ///
+    /// let codeowners: CodeOwners = "# web\n*.js @alice\n".parse().unwrap();
+    /// let entry = codeowners.entries().next().unwrap();
+    /// assert_eq!(entry.line(), 2);
+    /// assert_eq!(entry.byte_range(), 6..17);
+    /// ```
+    pub fn entries(&self) -> impl Iterator<Item = Match<'_>> {
+        self.entries.iter().rev().map(|entry| Match { entry })
+    }
+
+    /// Entries in the order to try matching, i.e. the winning one first.
+    fn entries_by_priority(&self) -> Box<dyn Iterator<Item = &CodeOwnersEntry> + '_> {
+        match self.order {
+            MatchOrder::Last => Box::new(self.entries.iter()),
+            MatchOrder::First => Box::new(self.entries.iter().rev()),
         }
     }
 
     /// Find owners for matching path.
     pub fn find_owners(&self, path: &str) -> Option<&Vec<String>> {
-        let entry = self
+        self.find_match(path).map(|m| m.owners())
+    }
+
+    /// Same as `find_owners`, but for paths not necessarily in UTF-8.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use git_toolbox::github::codeowners::CodeOwners;
+    ///
+    /// let codeowners: CodeOwners = "*.js @frontend\n".parse().unwrap();
+    /// assert_eq!(codeowners.find_owners_bytes(b"src/\xff.js"), Some(&vec!["@frontend".to_owned()]));
+    /// ```
+    pub fn find_owners_bytes(&self, path: &[u8]) -> Option<&Vec<String>> {
+        self.find_match_bytes(path).map(|m| m.owners())
+    }
+
+    /// Check if the owner is one of owners of the path.
+    ///
+    /// Owners are compared ignoring ASCII case, as GitHub users, teams and
+    /// emails are. Unowned path has no owner.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use git_toolbox::github::codeowners::CodeOwners;
+    ///
+    /// let data = r#"
+    /// *.js @alice @org/frontend
+    /// "#;
+    /// let codeowners: CodeOwners = data.parse().unwrap();
+    ///
+    /// assert!(codeowners.is_owner("foo.js", "@Alice"));
+    /// assert!(!codeowners.is_owner("foo.js", "@bob"));
+    /// assert!(!codeowners.is_owner("foo.rs", "@alice"));
+    /// ```
+    pub fn is_owner(&self, path: &str, owner: &str) -> bool {
+        self.find_owners(path)
+            .is_some_and(|owners| owners.iter().any(|o| o.eq_ignore_ascii_case(owner)))
+    }
+
+    /// Find the entry which determines owners of the path.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use git_toolbox::github::codeowners::CodeOwners;
+    ///
+    /// let data = r#"
+    /// * @org/everyone
+    /// *.js frontend-developer
+    /// "#;
+    /// let codeowners: CodeOwners = data.parse().unwrap();
+    ///
+    /// assert!(codeowners.find_match("foo.rs").unwrap().is_default());
+    /// assert!(!codeowners.find_match("foo.js").unwrap().is_default());
+    /// ```
+    pub fn find_match(&self, path: &str) -> Option<Match<'_>> {
+        self.entries_by_priority()
+            .find(|&entry| entry.pattern.is_match(path))
+            .map(|entry| Match { entry })
+    }
+
+    /// Same as `find_match`, but for paths not necessarily in UTF-8.
+    pub fn find_match_bytes(&self, path: &[u8]) -> Option<Match<'_>> {
+        self.entries_by_priority()
+            .find(|&entry| entry.pattern.is_match_bytes(path))
+            .map(|entry| Match { entry })
+    }
+
+    /// Find all entries matching the path, in the order of the file.
+    ///
+    /// Only one of them determines owners; see `find_match`.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use git_toolbox::github::codeowners::CodeOwners;
+    ///
+    /// let data = r#"
+    /// * @org/everyone
+    /// *.js frontend-developer
+    /// "#;
+    /// let codeowners: CodeOwners = data.parse().unwrap();
+    /// let lines: Vec<_> = codeowners.find_matches("foo.js").iter().map(|m| m.line()).collect();
+    ///
+    /// assert_eq!(lines, vec![2, 3]);
+    /// ```
+    pub fn find_matches(&self, path: &str) -> Vec<Match<'_>> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|&entry| entry.pattern.is_match(path))
+            .map(|entry| Match { entry })
+            .collect()
+    }
+
+    /// Find owners of all entries matching the path, not only the effective one.
+    ///
+    /// This is useful to know everyone who may be concerned with the path.
+    /// Owners are listed in the order of the file without duplicates, ignoring ASCII case.
+    /// Returns `None` if no entry matches.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use git_toolbox::github::codeowners::CodeOwners;
+    ///
+    /// let data = r#"
+    /// * @org/everyone
+    /// *.js @alice @org/frontend
+    /// /src/ @Alice @bob
+    /// "#;
+    /// let codeowners: CodeOwners = data.parse().unwrap();
+    ///
+    /// assert_eq!(
+    ///     codeowners.find_all_owners("src/foo.js"),
+    ///     Some(vec!["@org/everyone".to_owned(), "@alice".to_owned(), "@org/frontend".to_owned(), "@bob".to_owned()])
+    /// );
+    /// assert_eq!(codeowners.find_owners("src/foo.js"), Some(&vec!["@Alice".to_owned(), "@bob".to_owned()]));
+    /// ```
+    pub fn find_all_owners(&self, path: &str) -> Option<Vec<String>> {
+        let matches = self.find_matches(path);
+        if matches.is_empty() {
+            return None;
+        }
+
+        let mut owners: Vec<String> = Vec::new();
+        for owner in matches.iter().flat_map(|m| m.owners()) {
+            if !owners.iter().any(|o| o.eq_ignore_ascii_case(owner)) {
+                owners.push(owner.clone());
+            }
+        }
+        Some(owners)
+    }
+
+    /// Find owners for many paths at once.
+    ///
+    /// All patterns are compiled into one `RegexSet` and shared across the given paths,
+    /// so this is cheaper than calling `find_owners` for each path.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use git_toolbox::github::codeowners::CodeOwners;
+    ///
+    /// let data = r#"
+    /// * @everyone
+    /// *.js frontend-developer
+    /// "#;
+    /// let codeowners: CodeOwners = data.parse().unwrap();
+    /// let got = codeowners.find_owners_bulk(["foo.js", "foo.rs"]);
+    ///
+    /// assert_eq!(got[0], ("foo.js", Some(&vec![String::from("frontend-developer")])));
+    /// assert_eq!(got[1], ("foo.rs", Some(&vec![String::from("@everyone")])));
+    /// ```
+    pub fn find_owners_bulk<'a>(
+        &'a self,
+        paths: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<(&'a str, Option<&'a Vec<String>>)> {
+        let set = RegexSet::new(self.entries.iter().map(|e| e.pattern.as_regex_str()));
+        match set {
+            Ok(set) => paths
+                .into_iter()
+                .map(|path| {
+                    // entries are reversed, so the lowest index is the last one in the file.
+                    let matches = set.matches(path);
+                    let winner = match self.order {
+                        MatchOrder::Last => matches.iter().next(),
+                        MatchOrder::First => matches.iter().next_back(),
+                    };
+                    let owners = winner.map(|idx| &self.entries[idx].owners);
+                    (path, owners)
+                })
+                .collect(),
+            Err(e) => {
+                warn!("cannot build RegexSet from CODEOWNERS: {}", e);
+                paths
+                    .into_iter()
+                    .map(|path| (path, self.find_owners(path)))
+                    .collect()
+            }
+        }
+    }
+
+    /// Collect owners of the paths, as reviewers of the change touching them.
+    ///
+    /// Each owner is mapped to the paths which make them a reviewer, in the given order.
+    /// Unowned paths are skipped.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use git_toolbox::github::codeowners::CodeOwners;
+    ///
+    /// let data = r#"
+    /// * @everyone
+    /// *.js @alice @bob
+    /// /docs/ @bob
+    /// "#;
+    /// let codeowners: CodeOwners = data.parse().unwrap();
+    /// let got = codeowners.reviewers_for(["a.js", "docs/b.md", "c.rs"]);
+    ///
+    /// assert_eq!(got["@alice"], vec!["a.js"]);
+    /// assert_eq!(got["@bob"], vec!["a.js", "docs/b.md"]);
+    /// assert_eq!(got["@everyone"], vec!["c.rs"]);
+    /// ```
+    pub fn reviewers_for<'a>(
+        &self,
+        paths: impl IntoIterator<Item = &'a str>,
+    ) -> BTreeMap<String, Vec<&'a str>> {
+        let paths: Vec<&'a str> = paths.into_iter().collect();
+        let mut reviewers: BTreeMap<String, Vec<&'a str>> = BTreeMap::new();
+        // zip with the given paths, to keep their lifetime apart from self.
+        let found = self.find_owners_bulk(paths.iter().copied());
+        for (path, (_, owners)) in paths.iter().zip(found) {
+            for owner in owners.into_iter().flatten() {
+                reviewers.entry(owner.clone()).or_default().push(path);
+            }
+        }
+        reviewers
+    }
+
+    /// Find paths whose winning owners differ from the other (newer) version of CODEOWNERS file.
+    ///
+    /// As enumerating all paths is impossible, sample paths made up from
+    /// patterns of both versions are compared, in sorted order.
+    /// Owners are compared ignoring order and ASCII case.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use git_toolbox::github::codeowners::CodeOwners;
+    ///
+    /// let before: CodeOwners = "* @everyone\n/docs/ @writer\n".parse().unwrap();
+    /// let after: CodeOwners = "* @everyone\n/docs/ @writer @editor\n".parse().unwrap();
+    /// let changes = before.diff(&after);
+    ///
+    /// assert_eq!(changes.len(), 1);
+    /// assert_eq!(changes[0].path, "docs/x");
+    /// ```
+    pub fn diff(&self, other: &CodeOwners) -> Vec<OwnershipChange> {
+        let probes: BTreeSet<String> = self
             .entries
             .iter()
-            .find(|&entry| entry.pattern.is_match(path));
+            .chain(other.entries.iter())
+            .flat_map(|entry| entry.pattern.probe_paths())
+            .collect();
+        self.diff_paths(other, probes.iter().map(String::as_str))
+    }
+
+    /// Find paths whose winning owners differ from the other version, among given paths.
+    pub fn diff_paths<'a, I>(&self, other: &CodeOwners, paths: I) -> Vec<OwnershipChange>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let normalize = |owners: Option<&Vec<String>>| {
+            owners.map(|owners| {
+                owners
+                    .iter()
+                    .map(|o| o.to_ascii_lowercase())
+                    .collect::<BTreeSet<_>>()
+            })
+        };
+        paths
+            .into_iter()
+            .filter_map(|path| {
+                let before = self.find_owners(path);
+                let after = other.find_owners(path);
+                (normalize(before) != normalize(after)).then(|| OwnershipChange {
+                    path: path.to_owned(),
+                    before: before.cloned(),
+                    after: after.cloned(),
+                })
+            })
+            .collect()
+    }
+
+    /// Find entries which may determine owners of paths under the directory, in file order.
+    ///
+    /// This judges from the patterns as written, without enumerating files, so
+    /// the result is an over-approximation: unanchored patterns like `*.js` are
+    /// always included, and entries may be shadowed by later ones for every actual path.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use git_toolbox::github::codeowners::CodeOwners;
+    ///
+    /// let data = r#"
+    /// * @everyone
+    /// /src/ @backend
+    /// /src/api/ @api
+    /// /docs/ @writer
+    /// "#;
+    /// let codeowners: CodeOwners = data.parse().unwrap();
+    /// let got: Vec<_> = codeowners
+    ///     .owners_under("src/api/")
+    ///     .into_iter()
+    ///     .map(|(pattern, _)| pattern)
+    ///     .collect();
+    ///
+    /// assert_eq!(got, vec!["*", "/src/", "/src/api/"]);
+    /// ```
+    pub fn owners_under(&self, dir: &str) -> Vec<(&str, &Vec<String>)> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|entry| entry.pattern.may_match_under(dir))
+            .map(|entry| (entry.pattern.as_str(), &entry.owners))
+            .collect()
+    }
+}
+
+/// Settings to read CODEOWNERS file, in one place.
+///
+/// `CodeOwners::try_from_*` are shorthands of this with the default settings.
+///
+/// Examples
+///
+/// ```
+/// use git_toolbox::github::codeowners::{CodeOwnersBuilder, CodeOwnersError, MatchOrder};
+///
+/// let data = "*.js @alice\n* @bob\n";
+/// let codeowners = CodeOwnersBuilder::new()
+///     .with_match_order(MatchOrder::First)
+///     .from_bufread(data.as_bytes())
+///     .unwrap();
+/// assert_eq!(codeowners.find_owners("a.js").unwrap(), &vec!["@alice"]);
+///
+/// let result = CodeOwnersBuilder::new()
+///     .with_size_limit(Some(8))
+///     .from_bufread(data.as_bytes());
+/// assert!(matches!(result, Err(CodeOwnersError::TooLarge { limit: 8, .. })));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CodeOwnersBuilder {
+    locations: Option<Vec<String>>,
+    merge: bool,
+    size_limit: Option<u64>,
+    strict: bool,
+    match_order: MatchOrder,
+}
+
+// `from_*` read CODEOWNERS file with the settings, like `CodeOwners::try_from_*` do.
+#[allow(clippy::wrong_self_convention)]
+impl CodeOwnersBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Paths to search CODEOWNERS file in priority order,
+    /// instead of ones in `whose.codeownerspath` config followed by `CodeOwners::LOCATIONS`.
+    pub fn with_locations(self, locations: Option<Vec<String>>) -> Self {
+        Self { locations, ..self }
+    }
+
+    /// Merge all CODEOWNERS files found like `CodeOwners::try_from_repo_merged`,
+    /// instead of using the first one.
+    pub fn with_merge(self, merge: bool) -> Self {
+        Self { merge, ..self }
+    }
+
+    /// Refuse CODEOWNERS file larger than the limit in bytes.
+    /// GitHub ignores CODEOWNERS file over 3 MB.
+    pub fn with_size_limit(self, size_limit: Option<u64>) -> Self {
+        Self { size_limit, ..self }
+    }
+
+    /// Fail at malformed lines, instead of skipping them with warnings.
+    pub fn with_strict(self, strict: bool) -> Self {
+        Self { strict, ..self }
+    }
+
+    pub fn with_match_order(self, match_order: MatchOrder) -> Self {
+        Self {
+            match_order,
+            ..self
+        }
+    }
+
+    /// Parse CODEOWNERS file data in buffer.
+    pub fn from_bufread<T: BufRead>(&self, blob: T) -> Result<CodeOwners, CodeOwnersError> {
+        let codeowners = match self.size_limit {
+            Some(limit) => {
+                let mut content = Vec::new();
+                blob.take(limit.saturating_add(1))
+                    .read_to_end(&mut content)?;
+                self.parse(&content, None)?
+            }
+            None => CodeOwners::parse_bufread(blob, None, self.strict)?,
+        };
+        Ok(codeowners.with_match_order(self.match_order))
+    }
+
+    /// Read CODEOWNERS file from repository's index.
+    pub fn from_repo(&self, repo: &Repository) -> Result<CodeOwners, CodeOwnersError> {
+        let index = repo.index()?;
+        self.read_locations(repo, |path| CodeOwners::read_index_blob(repo, &index, path))?
+            .ok_or(CodeOwnersError::NotIndexed)
+    }
+
+    /// Read CODEOWNERS file from repository's working tree, which may not be staged yet.
+    pub fn from_workdir(&self, repo: &Repository) -> Result<CodeOwners, CodeOwnersError> {
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| git2::Error::from_str("cannot read CODEOWNERS in bare repository"))?;
+        self.read_locations(repo, |path| match std::fs::read(workdir.join(path)) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        })?
+        .ok_or(CodeOwnersError::NotFound)
+    }
+
+    /// Read and parse the first file found, or all of them if merging.
+    ///
+    /// Rules in the file searched earlier take precedence,
+    /// as if the files were concatenated in the reversed order of search.
+    fn read_locations<F>(
+        &self,
+        repo: &Repository,
+        mut read: F,
+    ) -> Result<Option<CodeOwners>, CodeOwnersError>
+    where
+        F: FnMut(&str) -> Result<Option<Vec<u8>>, CodeOwnersError>,
+    {
+        let locations = match &self.locations {
+            Some(locations) => locations.clone(),
+            None => CodeOwners::locations(repo)?,
+        };
+        let mut found: Option<CodeOwners> = None;
+        for path in locations {
+            let Some(content) = read(&path)? else {
+                continue;
+            };
+            let codeowners = self.parse(&content, Some(&path))?;
+            match &mut found {
+                Some(merged) => merged.entries.extend(codeowners.entries),
+                None => found = Some(codeowners),
+            }
+            if !self.merge {
+                break;
+            }
+        }
+
+        Ok(found.map(|codeowners| codeowners.with_match_order(self.match_order)))
+    }
+
+    fn parse(&self, content: &[u8], source: Option<&str>) -> Result<CodeOwners, CodeOwnersError> {
+        if let Some(limit) = self.size_limit {
+            if content.len() as u64 > limit {
+                return Err(CodeOwnersError::TooLarge {
+                    path: source.unwrap_or("CODEOWNERS").to_owned(),
+                    limit,
+                });
+            }
+        }
+        CodeOwners::parse_bufread(content, source, self.strict)
+    }
+}
+
+impl FromStr for CodeOwners {
+    type Err = CodeOwnersError;
+
+    /// Parse CODEOWNERS file data in string, same as `try_from_bufread`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from_bufread(s.as_bytes())
+    }
+}
+
+impl TryFrom<&str> for CodeOwners {
+    type Error = CodeOwnersError;
 
-        entry.map(|entry| &entry.owners)
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
     }
 }
@@ -0,0 +1,402 @@
+use std::{collections::HashMap, fmt};
+
+use git2::Repository;
+
+use super::{find_comment, pattern::Pattern, CodeOwners, CodeOwnersError};
+
+/// CODEOWNERS file kept line by line, for tools rewriting it.
+///
+/// Unlike `CodeOwners`, this keeps comments, blank lines and line endings,
+/// so that serializing unmodified document gives the original text back.
+///
+/// Examples
+///
+/// ```
+/// use git_toolbox::github::codeowners::{CodeOwnersDocument, DocumentLine};
+///
+/// let data = "# frontend\n*.js @alice # web\n";
+/// let mut doc = CodeOwnersDocument::parse(data);
+/// assert_eq!(doc.to_string(), data);
+///
+/// if let Some(DocumentLine::Entry(entry)) = doc.lines_mut().nth(1) {
+///     entry.add_owner("@bob");
+/// }
+/// assert_eq!(doc.to_string(), "# frontend\n*.js @alice @bob # web\n");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeOwnersDocument {
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Item {
+    line: DocumentLine,
+    // "\n", "\r\n" or "" for the last line without newline.
+    eol: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentLine {
+    Entry(DocumentEntry),
+    /// Line having only a comment, including leading whitespaces and `#`.
+    Comment(String),
+    /// Line having only whitespaces.
+    Blank(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentEntry {
+    // original text, or re-rendered one after modification.
+    text: String,
+    pattern: String,
+    owners: Vec<String>,
+    comment: Option<String>,
+}
+
+impl CodeOwnersDocument {
+    pub fn parse(s: &str) -> CodeOwnersDocument {
+        let items = s
+            .split_inclusive('\n')
+            .map(|ln| {
+                let (text, eol) = if let Some(text) = ln.strip_suffix("\r\n") {
+                    (text, "\r\n")
+                } else if let Some(text) = ln.strip_suffix('\n') {
+                    (text, "\n")
+                } else {
+                    (ln, "")
+                };
+                Item {
+                    line: DocumentLine::parse(text),
+                    eol,
+                }
+            })
+            .collect();
+
+        CodeOwnersDocument { items }
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &DocumentLine> {
+        self.items.iter().map(|item| &item.line)
+    }
+
+    pub fn lines_mut(&mut self) -> impl Iterator<Item = &mut DocumentLine> {
+        self.items.iter_mut().map(|item| &mut item.line)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &DocumentEntry> {
+        self.lines().filter_map(|line| match line {
+            DocumentLine::Entry(entry) => Some(entry),
+            _ => None,
+        })
+    }
+
+    pub fn entries_mut(&mut self) -> impl Iterator<Item = &mut DocumentEntry> {
+        self.lines_mut().filter_map(|line| match line {
+            DocumentLine::Entry(entry) => Some(entry),
+            _ => None,
+        })
+    }
+}
+
+impl CodeOwnersDocument {
+    /// Read the CODEOWNERS file used by `CodeOwners::try_from_repo` from repository's index.
+    ///
+    /// Returns the path of the file together.
+    pub fn try_from_repo(
+        repo: &Repository,
+    ) -> Result<(String, CodeOwnersDocument), CodeOwnersError> {
+        let index = repo.index()?;
+        for path in CodeOwners::locations(repo)? {
+            if let Some(content) = CodeOwners::read_index_blob(repo, &index, &path)? {
+                let doc = Self::parse(&String::from_utf8_lossy(&content));
+                return Ok((path, doc));
+            }
+        }
+        Err(CodeOwnersError::NotIndexed)
+    }
+
+    /// Find suspicious patterns, which are likely typos or never take effect.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use git_toolbox::github::codeowners::{CodeOwnersDocument, LintKind};
+    ///
+    /// let doc = CodeOwnersDocument::parse("./docs @writer\n");
+    /// let got = doc.lint();
+    ///
+    /// assert_eq!(got[0].line, 1);
+    /// assert_eq!(got[0].kind, LintKind::DotSlash);
+    /// ```
+    pub fn lint(&self) -> Vec<LintWarning> {
+        let mut warnings = Vec::new();
+        let mut seen: HashMap<Pattern, usize> = HashMap::new();
+
+        for (idx, line) in self.lines().enumerate() {
+            let DocumentLine::Entry(entry) = line else {
+                continue;
+            };
+            let line = idx + 1;
+            let mut warn = |kind| {
+                warnings.push(LintWarning {
+                    line,
+                    pattern: entry.pattern.clone(),
+                    kind,
+                })
+            };
+
+            if entry.pattern.contains('\\') {
+                warn(LintKind::Backslash);
+            }
+            if entry.pattern.starts_with("./") || entry.pattern.starts_with("../") {
+                warn(LintKind::DotSlash);
+            }
+            if entry.pattern.contains("//") {
+                warn(LintKind::DoubleSlash);
+            }
+            match Pattern::new(entry.pattern.clone()) {
+                Ok(pattern) => {
+                    if let Some(first_line) = seen.insert(pattern, line) {
+                        warn(LintKind::Duplicate { first_line });
+                    }
+                }
+                Err(e) => warn(LintKind::InvalidPattern(e.to_string())),
+            }
+        }
+
+        warnings
+    }
+}
+
+/// Suspicious pattern found by `CodeOwnersDocument::lint`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintWarning {
+    /// 1-based line number.
+    pub line: usize,
+    pub pattern: String,
+    pub kind: LintKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LintKind {
+    Backslash,
+    DotSlash,
+    DoubleSlash,
+    InvalidPattern(String),
+    Duplicate { first_line: usize },
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintKind::Backslash => write!(f, "backslash is not a path separator; use `/`"),
+            LintKind::DotSlash => write!(
+                f,
+                "relative path is not supported; use `/` to anchor at the repository root"
+            ),
+            LintKind::DoubleSlash => write!(f, "`//` is redundant"),
+            LintKind::InvalidPattern(e) => write!(f, "{}", e),
+            LintKind::Duplicate { first_line } => write!(
+                f,
+                "same as the pattern at line {}, which never takes effect",
+                first_line
+            ),
+        }
+    }
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.line, self.pattern, self.kind)
+    }
+}
+
+impl fmt::Display for CodeOwnersDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in &self.items {
+            write!(f, "{}{}", item.line, item.eol)?;
+        }
+        Ok(())
+    }
+}
+
+impl DocumentLine {
+    fn parse(text: &str) -> DocumentLine {
+        let (body, comment) = match find_comment(text) {
+            Some(i) => (&text[..i], Some(&text[i..])),
+            None => (text, None),
+        };
+
+        let mut iter = body.split_whitespace();
+        match iter.next() {
+            Some(pattern) => DocumentLine::Entry(DocumentEntry {
+                text: text.to_owned(),
+                pattern: pattern.to_owned(),
+                owners: iter.map(|s| s.to_owned()).collect(),
+                comment: comment.map(|s| s.to_owned()),
+            }),
+            None if comment.is_some() => DocumentLine::Comment(text.to_owned()),
+            None => DocumentLine::Blank(text.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for DocumentLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentLine::Entry(entry) => f.write_str(&entry.text),
+            DocumentLine::Comment(text) | DocumentLine::Blank(text) => f.write_str(text),
+        }
+    }
+}
+
+impl DocumentEntry {
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn owners(&self) -> &[String] {
+        &self.owners
+    }
+
+    /// Trailing comment, including `#`.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Add owner at the end, unless already listed.
+    pub fn add_owner(&mut self, owner: &str) {
+        if !self.owners.iter().any(|o| o == owner) {
+            self.owners.push(owner.to_owned());
+            self.render();
+        }
+    }
+
+    /// Remove owner if listed.
+    pub fn remove_owner(&mut self, owner: &str) {
+        let len = self.owners.len();
+        self.owners.retain(|o| o != owner);
+        if self.owners.len() != len {
+            self.render();
+        }
+    }
+
+    // original spacing is lost, while the trailing comment is kept.
+    fn render(&mut self) {
+        let mut text = self.pattern.clone();
+        for owner in &self.owners {
+            text.push(' ');
+            text.push_str(owner);
+        }
+        if let Some(comment) = &self.comment {
+            text.push(' ');
+            text.push_str(comment);
+        }
+        self.text = text;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CodeOwnersDocument, DocumentLine, LintKind};
+
+    const DATA: &str = "\
+# This is a comment.
+
+*       @global-owner1 @global-owner2
+*.js    @js-owner #This is an inline comment.
+   # indented comment
+\t
+/build/logs/ @doctocat\r
+docs/*  docs@example.com
+/apps/github";
+
+    #[test]
+    fn round_trip() {
+        let doc = CodeOwnersDocument::parse(DATA);
+        assert_eq!(doc.to_string(), DATA);
+
+        let kinds: Vec<_> = doc
+            .lines()
+            .map(|line| match line {
+                DocumentLine::Entry(_) => "entry",
+                DocumentLine::Comment(_) => "comment",
+                DocumentLine::Blank(_) => "blank",
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "comment", "blank", "entry", "entry", "comment", "blank", "entry", "entry",
+                "entry",
+            ]
+        );
+
+        let entries: Vec<_> = doc
+            .entries()
+            .map(|e| (e.pattern(), e.owners().len(), e.comment()))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("*", 2, None),
+                ("*.js", 1, Some("#This is an inline comment.")),
+                ("/build/logs/", 1, None),
+                ("docs/*", 1, None),
+                ("/apps/github", 0, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn modify_owners() {
+        let mut doc = CodeOwnersDocument::parse(DATA);
+        for entry in doc.entries_mut() {
+            match entry.pattern() {
+                "*.js" => entry.add_owner("@ts-owner"),
+                "*" => entry.remove_owner("@global-owner2"),
+                "/build/logs/" => entry.add_owner("@doctocat"),
+                _ => {}
+            }
+        }
+
+        assert_eq!(
+            doc.to_string(),
+            "\
+# This is a comment.
+
+* @global-owner1
+*.js @js-owner @ts-owner #This is an inline comment.
+   # indented comment
+\t
+/build/logs/ @doctocat\r
+docs/*  docs@example.com
+/apps/github"
+        );
+    }
+
+    #[test]
+    fn lint() {
+        let doc = CodeOwnersDocument::parse(
+            "\
+# comment
+docs\\api @writer
+./src @dev
+/apps//web @web
+*.js @js
+/build/ @ops
+*.js @js2
+",
+        );
+        let got: Vec<_> = doc.lint().into_iter().map(|w| (w.line, w.kind)).collect();
+        assert_eq!(
+            got,
+            vec![
+                (2, LintKind::Backslash),
+                (3, LintKind::DotSlash),
+                (4, LintKind::DoubleSlash),
+                (7, LintKind::Duplicate { first_line: 5 }),
+            ]
+        );
+    }
+}
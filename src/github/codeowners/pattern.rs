@@ -1,10 +1,30 @@
-use std::io::Write;
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
 
-use regex::Regex;
+use regex::{bytes, Regex};
 
 #[derive(Debug)]
 pub struct Pattern {
+    source: String,
     re: Regex,
+    // same expression as `re` without Unicode mode, so wildcards match any bytes.
+    re_bytes: bytes::Regex,
+}
+
+// Patterns are equal when they compile to the same regular expression,
+// so redundant spellings like "apps//" and "apps/" are treated as duplicates.
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.re.as_str() == other.re.as_str()
+    }
+}
+
+impl Eq for Pattern {}
+
+impl Hash for Pattern {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.re.as_str().hash(state);
+    }
 }
 
 #[derive(thiserror::Error, Debug, PartialEq)]
@@ -21,51 +41,131 @@ pub enum PatternError {
 impl Pattern {
     pub fn new(pattern: String) -> Result<Pattern, PatternError> {
         let pat = Self::compile(&pattern)?;
-        let re = Regex::new(&pat).map_err(|error| PatternError::CompileError { pattern, error })?;
-        Ok(Pattern { re })
+        let re = match Regex::new(&pat) {
+            Ok(re) => re,
+            Err(error) => return Err(PatternError::CompileError { pattern, error }),
+        };
+        let re_bytes = match bytes::RegexBuilder::new(&pat).unicode(false).build() {
+            Ok(re) => re,
+            Err(error) => return Err(PatternError::CompileError { pattern, error }),
+        };
+        Ok(Pattern {
+            source: pattern,
+            re,
+            re_bytes,
+        })
+    }
+
+    /// Pattern as written in CODEOWNERS file.
+    pub fn as_str(&self) -> &str {
+        &self.source
     }
 
     pub fn is_match(&self, path: &str) -> bool {
         self.re.is_match(path)
     }
 
+    /// Same as `is_match`, but for paths not necessarily in UTF-8.
+    ///
+    /// Wildcards match any bytes in paths not in UTF-8, like `?` matches a byte rather than a character.
+    pub fn is_match_bytes(&self, path: &[u8]) -> bool {
+        match std::str::from_utf8(path) {
+            Ok(path) => self.is_match(path),
+            Err(_) => self.re_bytes.is_match(path),
+        }
+    }
+
+    /// Compiled regular expression of this pattern.
+    pub fn as_regex_str(&self) -> &str {
+        self.re.as_str()
+    }
+
+    /// Check if this pattern may match some path under the directory, judging from the source.
+    ///
+    /// This is an over-approximation: only anchored patterns whose literal
+    /// components differ from the directory are ruled out.
+    pub fn may_match_under(&self, dir: &str) -> bool {
+        let Some(anchored) = self.source.strip_prefix('/') else {
+            // matches at any depth
+            return true;
+        };
+
+        let pattern = anchored.split('/').filter(|c| !c.is_empty());
+        let dir = dir.split('/').filter(|c| !c.is_empty());
+        for (pattern, dir) in pattern.zip(dir) {
+            if pattern.contains(['*', '?', '[']) {
+                return true;
+            }
+            if pattern != dir {
+                return false;
+            }
+        }
+        // the pattern covers the directory as a whole, or points to somewhere under it.
+        true
+    }
+
+    /// Sample paths this pattern is meant to match, made up from the source.
+    ///
+    /// Wildcards and character classes are replaced with a literal character,
+    /// and a file under the directory is given too, as the pattern may name a directory.
+    /// This is a heuristic: the samples don't necessarily match the pattern.
+    pub fn probe_paths(&self) -> Vec<String> {
+        let mut probe = String::new();
+        let mut chars = self.source.trim_start_matches('/').chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' | '?' => {
+                    while chars.next_if_eq(&'*').is_some() {}
+                    probe.push('x');
+                }
+                '\\' => probe.extend(chars.next()),
+                '[' => {
+                    let class: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                    probe.push(
+                        class
+                            .trim_start_matches(['!', '^'])
+                            .chars()
+                            .next()
+                            .unwrap_or('x'),
+                    );
+                }
+                c => probe.push(c),
+            }
+        }
+
+        if probe.is_empty() || probe.ends_with('/') {
+            probe.push('x');
+            vec![probe]
+        } else {
+            let under = format!("{}/x", probe);
+            vec![probe, under]
+        }
+    }
+
     fn compile(pattern: &str) -> Result<String, PatternError> {
         // re_out is a buffer where to output "compiled" pattern.
         enum State {
-            Head {
-                re_out: Vec<u8>,
-            },
-            HeadAsterisk {
-                re_out: Vec<u8>,
-            },
+            Head { re_out: String },
+            HeadAsterisk { re_out: String },
             // must_escape is temporary storage to buffer a part of literal string
             // taken from pattern. this must be escaped before it concatinated to re_out.
-            Default {
-                re_out: Vec<u8>,
-                must_escape: Vec<u8>,
-            },
-            Asterisk {
-                re_out: Vec<u8>,
-            },
-            DoubleAsterisk {
-                re_out: Vec<u8>,
-            },
-            DoubleAsteriskSlash {
-                re_out: Vec<u8>,
-            },
-            Slash {
-                re_out: Vec<u8>,
-            },
+            Default { re_out: String, must_escape: String },
+            Asterisk { re_out: String },
+            DoubleAsterisk { re_out: String },
+            DoubleAsteriskSlash { re_out: String },
+            Slash { re_out: String },
         }
-        let state = pattern
-            .chars()
-            .fold(State::Head { re_out: Vec::new() }, |st, c| match st {
+        let state = pattern.chars().fold(
+            State::Head {
+                re_out: String::new(),
+            },
+            |st, c| match st {
                 State::Head { mut re_out } => {
                     if c == '/' {
                         write!(&mut re_out, r"\A").unwrap();
                         State::Default {
                             re_out,
-                            must_escape: Vec::new(),
+                            must_escape: String::new(),
                         }
                     } else if c == '*' {
                         write!(&mut re_out, r"(?:\A|/)").unwrap();
@@ -74,12 +174,12 @@ impl Pattern {
                         write!(&mut re_out, r"(?:\A|/)[^/]").unwrap();
                         State::Default {
                             re_out,
-                            must_escape: Vec::new(),
+                            must_escape: String::new(),
                         }
                     } else {
-                        let mut must_escape = Vec::new();
+                        let mut must_escape = String::new();
                         write!(&mut re_out, r"(?:\A|/)").unwrap();
-                        write!(&mut must_escape, "{}", c).unwrap();
+                        must_escape.push(c);
                         State::Default {
                             re_out,
                             must_escape,
@@ -91,22 +191,19 @@ impl Pattern {
                     mut must_escape,
                 } => {
                     if c == '/' {
-                        let s = unsafe { String::from_utf8_unchecked(must_escape) };
-                        write!(&mut re_out, "{}", regex::escape(&s)).unwrap();
+                        write!(&mut re_out, "{}", regex::escape(&must_escape)).unwrap();
                         State::Slash { re_out }
                     } else if c == '*' {
-                        let s = unsafe { String::from_utf8_unchecked(must_escape) };
-                        write!(&mut re_out, "{}", regex::escape(&s)).unwrap();
+                        write!(&mut re_out, "{}", regex::escape(&must_escape)).unwrap();
                         State::Asterisk { re_out }
                     } else if c == '?' {
-                        let s = unsafe { String::from_utf8_unchecked(must_escape) };
-                        write!(&mut re_out, r"{}[^/]", regex::escape(&s)).unwrap();
+                        write!(&mut re_out, r"{}[^/]", regex::escape(&must_escape)).unwrap();
                         State::Default {
                             re_out,
-                            must_escape: Vec::new(),
+                            must_escape: String::new(),
                         }
                     } else {
-                        write!(&mut must_escape, "{}", c).unwrap();
+                        must_escape.push(c);
                         State::Default {
                             re_out,
                             must_escape,
@@ -123,12 +220,12 @@ impl Pattern {
                         write!(&mut re_out, r"[^/]+").unwrap();
                         State::Default {
                             re_out,
-                            must_escape: Vec::new(),
+                            must_escape: String::new(),
                         }
                     } else {
-                        let mut must_escape = Vec::new();
+                        let mut must_escape = String::new();
                         write!(&mut re_out, r"[^/]*").unwrap();
-                        write!(&mut must_escape, "{}", c).unwrap();
+                        must_escape.push(c);
                         State::Default {
                             re_out,
                             must_escape,
@@ -145,12 +242,12 @@ impl Pattern {
                         write!(&mut re_out, r"[^/]+").unwrap();
                         State::Default {
                             re_out,
-                            must_escape: Vec::new(),
+                            must_escape: String::new(),
                         }
                     } else {
-                        let mut must_escape = Vec::new();
+                        let mut must_escape = String::new();
                         write!(&mut re_out, r"[^/]*").unwrap();
-                        write!(&mut must_escape, "{}", c).unwrap();
+                        must_escape.push(c);
                         State::Default {
                             re_out,
                             must_escape,
@@ -166,11 +263,11 @@ impl Pattern {
                         write!(&mut re_out, r"[^/]").unwrap();
                         State::Default {
                             re_out,
-                            must_escape: Vec::new(),
+                            must_escape: String::new(),
                         }
                     } else {
-                        let mut must_escape = Vec::new();
-                        write!(&mut must_escape, "{}", c).unwrap();
+                        let mut must_escape = String::new();
+                        must_escape.push(c);
                         State::Default {
                             re_out,
                             must_escape,
@@ -187,19 +284,20 @@ impl Pattern {
                         write!(&mut re_out, r"/[^/]").unwrap();
                         State::Default {
                             re_out,
-                            must_escape: Vec::new(),
+                            must_escape: String::new(),
                         }
                     } else {
-                        let mut must_escape = Vec::new();
+                        let mut must_escape = String::new();
                         write!(&mut re_out, r"/").unwrap();
-                        write!(&mut must_escape, "{}", c).unwrap();
+                        must_escape.push(c);
                         State::Default {
                             re_out,
                             must_escape,
                         }
                     }
                 }
-            });
+            },
+        );
 
         match state {
             State::Head { .. } => Err(PatternError::Empty)?,
@@ -210,31 +308,28 @@ impl Pattern {
                 // add [/\z] to pattern and path for preventing partial match.
                 // Pattern `path/to/foo` should only maches directory or file named `foo` under
                 // `path/to` directory. `path/to/foobar` shouldn't match.
-                let s = unsafe { String::from_utf8_unchecked(must_escape) };
-                write!(&mut re_out, r"{}(?:/|\z)", regex::escape(&s)).unwrap();
-                Ok(unsafe { String::from_utf8_unchecked(re_out) })
+                write!(&mut re_out, r"{}(?:/|\z)", regex::escape(&must_escape)).unwrap();
+                Ok(re_out)
             }
             State::Asterisk { mut re_out } => {
                 // trailing asterisk doesn't match further nested path
                 write!(&mut re_out, r"[^/]*\z").unwrap();
-                Ok(unsafe { String::from_utf8_unchecked(re_out) })
+                Ok(re_out)
             }
             State::HeadAsterisk { mut re_out } => {
                 // lone single asterisk should match everything
                 write!(&mut re_out, r"").unwrap();
-                Ok(unsafe { String::from_utf8_unchecked(re_out) })
+                Ok(re_out)
             }
             State::DoubleAsterisk { mut re_out } => {
                 write!(&mut re_out, r".*").unwrap();
-                Ok(unsafe { String::from_utf8_unchecked(re_out) })
-            }
-            State::DoubleAsteriskSlash { re_out } => {
-                Ok(unsafe { String::from_utf8_unchecked(re_out) })
+                Ok(re_out)
             }
+            State::DoubleAsteriskSlash { re_out } => Ok(re_out),
             State::Slash { mut re_out } => {
                 // Pattern `app/` should match
                 write!(&mut re_out, r"/").unwrap();
-                Ok(unsafe { String::from_utf8_unchecked(re_out) })
+                Ok(re_out)
             }
         }
     }
@@ -242,6 +337,10 @@ impl Pattern {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
+    use proptest::prelude::*;
+
     use super::{Pattern, PatternError};
 
     #[test]
@@ -289,6 +388,76 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_may_match_under() {
+        let test_case = [
+            ("*", "src/api", true),
+            ("*.js", "src/api", true),
+            ("docs/", "src/api", true),
+            ("/src/", "src/api", true),
+            ("/src", "src/api/", true),
+            ("/src/api/v1/", "src/api", true),
+            ("/src/api/", "/src/api/", true),
+            ("/src/*/v1", "src/api", true),
+            ("/**/logs", "src/api", true),
+            ("/docs/", "src/api", false),
+            ("/src/api.rs", "src/api", false),
+            ("/src/app/", "src/api", false),
+            ("/docs/", "", true),
+        ];
+
+        for (idx, (pat_s, dir, want)) in test_case.into_iter().enumerate() {
+            let pat = Pattern::new(pat_s.to_string()).unwrap();
+            assert_eq!(
+                pat.may_match_under(dir),
+                want,
+                "#{}: pat:{:?} dir:{:?}",
+                idx,
+                pat_s,
+                dir
+            );
+        }
+    }
+
+    #[test]
+    fn test_probe_paths() {
+        let test_case = [
+            ("*", vec!["x", "x/x"]),
+            ("/**", vec!["x", "x/x"]),
+            ("*.js", vec!["x.js", "x.js/x"]),
+            ("/docs/", vec!["docs/x"]),
+            ("/apps", vec!["apps", "apps/x"]),
+            (
+                "src/**/test?.rs",
+                vec!["src/x/testx.rs", "src/x/testx.rs/x"],
+            ),
+            ("\\#notes", vec!["#notes", "#notes/x"]),
+            ("log[0-9].txt", vec!["log0.txt", "log0.txt/x"]),
+        ];
+
+        for (idx, (pat_s, want)) in test_case.into_iter().enumerate() {
+            let pat = Pattern::new(pat_s.to_string()).unwrap();
+            assert_eq!(pat.probe_paths(), want, "#{}: pat:{:?}", idx, pat_s);
+        }
+    }
+
+    #[test]
+    fn test_eq() {
+        let js = Pattern::new("*.js".to_string()).unwrap();
+        assert_eq!(js, Pattern::new("*.js".to_string()).unwrap());
+        assert_ne!(js, Pattern::new("*.ts".to_string()).unwrap());
+        assert_eq!(
+            Pattern::new("apps//".to_string()).unwrap(),
+            Pattern::new("apps/".to_string()).unwrap()
+        );
+
+        let set: HashSet<Pattern> = ["*.js", "*.ts", "*.js"]
+            .into_iter()
+            .map(|p| Pattern::new(p.to_string()).unwrap())
+            .collect();
+        assert_eq!(set.len(), 2);
+    }
+
     #[test]
     fn test_match() {
         let test_case = [
@@ -406,6 +575,58 @@ mod tests {
                 "#{}: wanted {} but got {}; pat = {:?} for given pat:{:?} path:{:?}",
                 idx, want, got, pat, pat_s, path
             );
+            assert_eq!(pat.is_match_bytes(path.as_bytes()), want, "#{}: bytes", idx);
+        }
+    }
+
+    #[test]
+    fn test_match_bytes() {
+        let test_case: [(&str, &[u8], bool); 9] = [
+            ("*.js", b"\xff.js", true),
+            ("*.js", b"src/\xfe\xff/a.js", true),
+            ("/src/", b"src/\xff", true),
+            ("/docs/", b"src/\xff", false),
+            ("?.rs", b"\xff.rs", true),
+            ("?.rs", b"\xff\xfe.rs", false),
+            ("/caf\u{e9}/", b"caf\xc3\xa9/\xff", true),
+            ("/caf\u{e9}/", b"caf\xe9/\xff", false),
+            // valid UTF-8 is matched by characters
+            ("?.rs", "\u{e9}.rs".as_bytes(), true),
+        ];
+
+        for (idx, (pat_s, path, want)) in test_case.into_iter().enumerate() {
+            let pat = Pattern::new(pat_s.to_string()).unwrap();
+            assert_eq!(
+                pat.is_match_bytes(path),
+                want,
+                "#{}: pat:{:?} path:{:?}",
+                idx,
+                pat_s,
+                path
+            );
+        }
+    }
+
+    proptest! {
+        // whatever written in CODEOWNERS, compilation should end up with
+        // a valid regular expression or PatternError::Empty, never a panic.
+        #[test]
+        fn test_new_never_panics(
+            pattern in prop_oneof![
+                r"[/*?a.\\\[\]{}()|^$+é 　]{0,24}",
+                any::<String>(),
+            ],
+            path in "[/*a.é]{0,16}",
+        ) {
+            match Pattern::new(pattern.clone()) {
+                Ok(p) => {
+                    prop_assert_eq!(p.as_str(), pattern.as_str());
+                    p.is_match(&path);
+                    p.may_match_under(&path);
+                }
+                Err(PatternError::Empty) => prop_assert!(pattern.is_empty()),
+                Err(e) => prop_assert!(false, "{:?} failed to compile: {}", pattern, e),
+            }
         }
     }
 }
@@ -0,0 +1,37 @@
+//! Minimal helpers to emit JSON without serializer dependencies.
+
+use std::fmt::Write as _;
+
+/// Escape string to be put between double quotes in JSON.
+///
+/// Examples
+///
+/// ```
+/// use git_toolbox::json::escape;
+///
+/// assert_eq!(escape("say \"hi\"\n"), r#"say \"hi\"\n"#);
+/// ```
+pub fn escape(s: &str) -> String {
+    let mut buf = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => buf.push_str(r#"\""#),
+            '\\' => buf.push_str(r"\\"),
+            '\n' => buf.push_str(r"\n"),
+            '\r' => buf.push_str(r"\r"),
+            '\t' => buf.push_str(r"\t"),
+            c if c.is_control() => write!(&mut buf, r"\u{:04x}", c as u32).unwrap(),
+            c => buf.push(c),
+        }
+    }
+    buf
+}
+
+/// Render strings as JSON array.
+pub fn string_array<S: AsRef<str>>(items: &[S]) -> String {
+    let items: Vec<_> = items
+        .iter()
+        .map(|s| format!("\"{}\"", escape(s.as_ref())))
+        .collect();
+    format!("[{}]", items.join(","))
+}
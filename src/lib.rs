@@ -1,5 +1,6 @@
 pub mod app;
 pub mod git;
 pub mod github;
+pub mod json;
 pub mod pathname;
 pub mod reltime;
@@ -9,6 +9,10 @@ use git2::Repository;
 pub enum NormalizePathError {
     #[error("path {0} points to the out side of repository")]
     OutSideOfRepo(PathBuf),
+    #[error("path {0} does not exist in repository")]
+    NotFound(PathBuf),
+    #[error("pathspec magic in {0} is not supported except for :/")]
+    UnsupportedMagic(String),
     #[error("{0}")]
     RuntimeError(&'static str),
     #[error("{0}")]
@@ -40,9 +44,46 @@ fn canonicalize(path: PathBuf) -> PathBuf {
     buf
 }
 
+/// Normalize paths into relative ones from the repository root.
+///
+/// This works lexically, so paths are not required to exist.
 pub fn normalize_paths(
     repo: &Repository,
     paths: Vec<String>,
+) -> Result<Vec<String>, NormalizePathError> {
+    normalize_paths_impl(repo, paths, false)
+}
+
+/// Check if the path points inside of the repository, relative to the current directory.
+///
+/// Like `normalize_paths`, this works lexically; nonexistent paths can be inside.
+/// Returns false if the current directory is not available.
+pub fn is_inside_repo<P: AsRef<Path>>(repo: &Repository, path: P) -> bool {
+    let repo_root = repo.path().parent().unwrap();
+    match env::current_dir() {
+        Ok(cwd) => is_inside(&cwd, repo_root, path.as_ref()),
+        Err(_) => false,
+    }
+}
+
+fn is_inside(cwd: &Path, repo_root: &Path, path: &Path) -> bool {
+    !path.as_os_str().is_empty() && canonicalize(cwd.join(path)).starts_with(repo_root)
+}
+
+/// Same as `normalize_paths`, but also checks if the paths exist.
+///
+/// Paths containing glob characters are not checked as they are not literal paths.
+pub fn normalize_paths_strict(
+    repo: &Repository,
+    paths: Vec<String>,
+) -> Result<Vec<String>, NormalizePathError> {
+    normalize_paths_impl(repo, paths, true)
+}
+
+fn normalize_paths_impl(
+    repo: &Repository,
+    paths: Vec<String>,
+    strict: bool,
 ) -> Result<Vec<String>, NormalizePathError> {
     let repo_root = repo.path().parent().unwrap();
     let mut workdir_paths = Vec::new();
@@ -53,12 +94,46 @@ pub fn normalize_paths(
             repo_root,
             path,
         )?;
+        if strict {
+            ensure_exists(repo_root, path, &abs_path)?;
+        }
         workdir_paths.push(abs_path)
     }
     Ok(workdir_paths)
 }
 
+fn ensure_exists(
+    repo_root: &Path,
+    path: &Path,
+    normalized: &str,
+) -> Result<(), NormalizePathError> {
+    if normalized.contains(['*', '?', '[']) {
+        return Ok(());
+    }
+
+    match repo_root.join(normalized).try_exists() {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(NormalizePathError::NotFound(path.to_owned())),
+        Err(e) => Err(NormalizePathError::IOError(e.to_string())),
+    }
+}
+
 fn normalize_path(cwd: &Path, repo_root: &Path, path: &Path) -> Result<String, NormalizePathError> {
+    // `:/path` is relative to the repository root, like git does regardless of the current directory.
+    // Other magic like `:!exclude` or `:(icase)` is refused rather than taken as a literal path.
+    if let Some(s) = path.to_str() {
+        if let Some(rest) = s.strip_prefix(":/") {
+            let rest = rest.trim_start_matches('/');
+            let rest = if rest.is_empty() { "." } else { rest };
+            return normalize_path(repo_root, repo_root, Path::new(rest));
+        }
+        if s.strip_prefix(':')
+            .is_some_and(|magic| magic.starts_with(['!', '^', '(']))
+        {
+            return Err(NormalizePathError::UnsupportedMagic(s.to_owned()));
+        }
+    }
+
     let mut components = path.components();
     match components.next() {
         Some(Component::CurDir)
@@ -94,7 +169,7 @@ mod tests {
 
     use tempfile::TempDir;
 
-    use crate::pathname::normalize_path;
+    use crate::pathname::{ensure_exists, is_inside, normalize_path, NormalizePathError};
 
     #[test]
     #[cfg(unix)]
@@ -168,4 +243,113 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_normalize_path_from_root() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo_root = tmpdir.path();
+        fs::create_dir_all(repo_root.join("foo").join("bar"))?;
+
+        for cwd in [
+            repo_root.to_path_buf(),
+            repo_root.join("foo"),
+            repo_root.join("foo").join("bar"),
+        ] {
+            for (path, want) in [
+                (":/foo", "foo"),
+                (":/src/a.rs", "src/a.rs"),
+                (":/foo/../b", "b"),
+                (":/", ""),
+            ] {
+                let got = normalize_path(&cwd, repo_root, Path::new(path));
+                assert_eq!(got, Ok(want.to_owned()), "{:?} in {:?}", path, cwd);
+            }
+        }
+
+        let cwd = repo_root.join("foo");
+        assert_eq!(
+            normalize_path(&cwd, repo_root, Path::new(":/../a")),
+            Err(NormalizePathError::OutSideOfRepo(PathBuf::from("../a")))
+        );
+        for path in [":!a.js", ":^a.js", ":(icase)A.js"] {
+            assert_eq!(
+                normalize_path(&cwd, repo_root, Path::new(path)),
+                Err(NormalizePathError::UnsupportedMagic(path.to_owned()))
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_inside() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo_root = tmpdir.path().join("repo");
+        fs::create_dir_all(repo_root.join("foo"))?;
+        fs::write(repo_root.join("foo").join("a.js"), b"")?;
+
+        let cwd = repo_root.join("foo");
+        let cases = [
+            (Path::new("a.js").to_path_buf(), true),
+            (Path::new(".").to_path_buf(), true),
+            (Path::new("..").to_path_buf(), true),
+            (repo_root.join("foo").join("a.js"), true),
+            // paths are not required to exist
+            (Path::new("nonexistent/b.js").to_path_buf(), true),
+            (Path::new("../..").to_path_buf(), false),
+            (Path::new("../../repo2/a.js").to_path_buf(), false),
+            (tmpdir.path().join("other"), false),
+            (PathBuf::new(), false),
+        ];
+
+        for (idx, (path, want)) in cases.into_iter().enumerate() {
+            let got = is_inside(&cwd, &repo_root, &path);
+            assert_eq!(got, want, "#{}: for path {:?}", idx, path);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ensure_exists() -> Result<(), Box<dyn std::error::Error>> {
+        let tmpdir = TempDir::new()?;
+        let repo_root = tmpdir.path();
+        fs::create_dir(repo_root.join("foo"))?;
+        fs::write(repo_root.join("foo").join("a.js"), b"")?;
+
+        let cwd = repo_root.join("foo");
+        let cases = [
+            ("a.js", Ok(())),
+            (".", Ok(())),
+            ("*.js", Ok(())),
+            (
+                "nonexistent.js",
+                Err(NormalizePathError::NotFound(PathBuf::from(
+                    "nonexistent.js",
+                ))),
+            ),
+            (
+                "../bar/a.js",
+                Err(NormalizePathError::NotFound(PathBuf::from("../bar/a.js"))),
+            ),
+        ];
+
+        for (idx, (path, want)) in cases.into_iter().enumerate() {
+            let path = Path::new(path);
+            let normalized = normalize_path(&cwd, repo_root, path)?;
+            let got = ensure_exists(repo_root, path, &normalized);
+            assert_eq!(got, want, "#{}: for path {:?}", idx, path);
+        }
+
+        // outside of repository is reported before existence
+        let got = normalize_path(&cwd, repo_root, Path::new("../../nonexistent.js"));
+        assert_eq!(
+            got,
+            Err(NormalizePathError::OutSideOfRepo(PathBuf::from(
+                "../../nonexistent.js"
+            )))
+        );
+
+        Ok(())
+    }
 }
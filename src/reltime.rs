@@ -1,6 +1,6 @@
 use std::ops::Sub;
 
-use chrono::{DateTime, Days, Months, TimeZone};
+use chrono::{DateTime, Days, Months, TimeDelta, TimeZone};
 use once_cell::sync::Lazy;
 use regex::Regex;
 
@@ -8,11 +8,14 @@ use regex::Regex;
 pub enum Error {
     #[error("cannot parse {0}")]
     ParseError(String),
+    #[error("cannot parse {token:?} in {value:?}")]
+    UnexpectedToken { token: String, value: String },
     #[error("range error")]
     RangeError,
 }
 
 struct ReltimeBuilder {
+    hours: u32,
     days: u32,
     weeks: u32,
     months: u32,
@@ -34,9 +37,12 @@ impl ReltimeBuilder {
             .checked_mul(7)
             .ok_or(Error::RangeError)?
             .checked_add(self.days)
+            .ok_or(Error::RangeError)?
+            .checked_add(self.hours / 24)
             .ok_or(Error::RangeError)?;
 
         Ok(Self {
+            hours: self.hours % 24,
             days,
             weeks: 0,
             months,
@@ -47,55 +53,154 @@ impl ReltimeBuilder {
     fn build(self) -> Result<Reltime, Error> {
         let a = self.normalize()?;
         Ok(Reltime {
+            hours: a.hours,
             days: Days::new(a.days.into()),
             months: Months::new(a.months),
         })
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Reltime {
+    hours: u32,
     days: Days,
     months: Months,
 }
 
+impl Reltime {
+    /// Construct Reltime from each units.
+    ///
+    /// Returns `Error::RangeError` if the period cannot be represented.
+    pub fn checked(years: u32, months: u32, weeks: u32, days: u32) -> Result<Reltime, Error> {
+        ReltimeBuilder {
+            years,
+            months,
+            weeks,
+            days,
+            hours: 0,
+        }
+        .build()
+    }
+
+    /// Construct Reltime from fractional units.
+    ///
+    /// Fractions are carried down to smaller units: a year is 12 months,
+    /// a month is 30 days, a week is 7 days and a day is 24 hours.
+    /// The remaining fraction of an hour is rounded to the nearest hour.
+    fn fractional(years: f64, months: f64, weeks: f64, days: f64) -> Result<Reltime, Error> {
+        let (years, frac) = split_fraction(years)?;
+        let (months, frac) = split_fraction(months + frac * 12.0)?;
+        let (weeks, frac_weeks) = split_fraction(weeks)?;
+        let (days, frac) = split_fraction(days + frac * 30.0 + frac_weeks * 7.0)?;
+        ReltimeBuilder {
+            years,
+            months,
+            weeks,
+            days,
+            hours: (frac * 24.0).round() as u32,
+        }
+        .build()
+    }
+}
+
+/// Split non-negative number into its integral and fractional part.
+fn split_fraction(v: f64) -> Result<(u32, f64), Error> {
+    let whole = v.trunc();
+    if whole > u32::MAX.into() {
+        return Err(Error::RangeError);
+    }
+    Ok((whole as u32, v.fract()))
+}
+
+/// Resolve English terms like "yesterday" or "last week", ignoring case.
+fn named_term(value: &str) -> Option<Result<Reltime, Error>> {
+    let words: Vec<_> = value.split_whitespace().collect();
+    let term = words.join(" ").to_ascii_lowercase();
+    let (years, months, weeks, days) = match term.as_str() {
+        "today" => (0, 0, 0, 0),
+        "yesterday" => (0, 0, 0, 1),
+        "last week" => (0, 0, 1, 0),
+        "last month" => (0, 1, 0, 0),
+        "last year" => (1, 0, 0, 0),
+        _ => return None,
+    };
+    Some(Reltime::checked(years, months, weeks, days))
+}
+
+/// Rewrite git's approxidate-style "2.days.ago" into "2 days", keeping decimal points like "1.5.days.ago".
+fn dotted_ago(value: &str) -> Option<String> {
+    let dotted = value.trim().strip_suffix(".ago")?;
+    let chars: Vec<char> = dotted.chars().collect();
+    let spaced = chars
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            let between_digits = i > 0
+                && chars[i - 1].is_ascii_digit()
+                && chars.get(i + 1).is_some_and(char::is_ascii_digit);
+            if c == '.' && !between_digits {
+                ' '
+            } else {
+                c
+            }
+        })
+        .collect();
+    Some(spaced)
+}
+
 impl TryFrom<&str> for Reltime {
     type Error = Error;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        static RE: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"(?:(?P<yr>\d+)\s*(?:y|yrs?|years?))?(?:(?P<mo>\d+)\s*(?:mo|months?))?(?:(?P<w>\d+)\s*(?:w|weeks?))?(?:(?P<d>\d+)\s*(?:d|days?))?").unwrap()
-        });
-
-        match RE.captures(value) {
-            Some(caps) => {
-                let years = caps
-                    .name("yr")
-                    .map_or(Ok(0), |s| s.as_str().parse())
-                    .map_err(|_| Error::ParseError(value.to_string()))?;
-                let months = caps
-                    .name("mo")
-                    .map_or(Ok(0), |s| s.as_str().parse())
-                    .map_err(|_| Error::ParseError(value.to_string()))?;
-                let weeks = caps
-                    .name("w")
-                    .map_or(Ok(0), |s| s.as_str().parse())
-                    .map_err(|_| Error::ParseError(value.to_string()))?;
-                let days = caps
-                    .name("d")
-                    .map_or(Ok(0), |s| s.as_str().parse())
-                    .map_err(|_| Error::ParseError(value.to_string()))?;
-
-                Ok(ReltimeBuilder {
-                    years,
-                    months,
-                    weeks,
-                    days,
-                }
-                .build()?)
+        if let Some(named) = named_term(value) {
+            return named;
+        }
+        if let Some(spaced) = dotted_ago(value) {
+            return Reltime::try_from(spaced.as_str()).map_err(|e| match e {
+                // report the input as given
+                Error::ParseError(_) => Error::ParseError(value.to_string()),
+                Error::UnexpectedToken { token, .. } => Error::UnexpectedToken {
+                    token,
+                    value: value.to_string(),
+                },
+                e => e,
+            });
+        }
+
+        static RE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"\A(\d+(?:\.\d+)?)\s*([a-z]+)\s*").unwrap());
+
+        // components may come in any order, but each unit only once.
+        // the next number may follow a unit directly like "3y4w", as `[a-z]+` takes all letters.
+        let mut amounts: [Option<f64>; 4] = [None; 4];
+        let mut rest = value.trim_start();
+        if rest.is_empty() {
+            return Err(Error::ParseError(value.to_string()));
+        }
+        let unexpected = |token: &str| Error::UnexpectedToken {
+            token: token.to_string(),
+            value: value.to_string(),
+        };
+        while !rest.is_empty() {
+            let Some(caps) = RE.captures(rest) else {
+                return Err(unexpected(rest.split_whitespace().next().unwrap_or(rest)));
+            };
+            let unit = match &caps[2] {
+                "y" | "yr" | "yrs" | "year" | "years" => 0,
+                "mo" | "month" | "months" => 1,
+                "w" | "week" | "weeks" => 2,
+                "d" | "day" | "days" => 3,
+                other => return Err(unexpected(other)),
+            };
+            if amounts[unit].is_some() {
+                return Err(unexpected(caps[0].trim_end()));
             }
-            None => Err(Error::ParseError(value.to_string())),
+            amounts[unit] = Some(caps[1].parse().map_err(|_| unexpected(&caps[1]))?);
+            rest = &rest[caps[0].len()..];
         }
+
+        let [years, months, weeks, days] = amounts.map(|a| a.unwrap_or(0.0));
+        Reltime::fractional(years, months, weeks, days)
     }
 }
 
@@ -107,12 +212,13 @@ impl<Tz: TimeZone> Sub<Reltime> for DateTime<Tz> {
             .unwrap()
             .checked_sub_days(rhs.days)
             .unwrap()
+            - TimeDelta::hours(rhs.hours.into())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::reltime::Reltime;
+    use crate::reltime::{Error as ReltimeError, Reltime};
     use chrono::DateTime;
     use std::error::Error;
 
@@ -155,6 +261,23 @@ mod tests {
             ("2000-02-29T00:00:00+09:00", "1 years", "1999-02-28T00:00:00+09:00"),
             // 12 months will be round up to 1 year
             ("2000-02-29T00:00:00+09:00", "12mo", "1999-02-28T00:00:00+09:00"),
+            // fractions are carried down to smaller units
+            ("2022-01-02T00:00:00+09:00", "1.5d",   "2021-12-31T12:00:00+09:00"),
+            ("2022-01-04T00:00:00+09:00", "0.5w",   "2021-12-31T12:00:00+09:00"),
+            ("2022-01-05T00:00:00+09:00", "1.5w",   "2021-12-25T12:00:00+09:00"),
+            ("2022-07-01T00:00:00+09:00", "0.5y",   "2022-01-01T00:00:00+09:00"),
+            // a fraction of month is 30 days
+            ("2022-03-31T00:00:00+09:00", "1.5mo",  "2022-02-13T00:00:00+09:00"),
+            ("2022-01-31T00:00:00+09:00", "0.1mo",  "2022-01-28T00:00:00+09:00"),
+            // units may be written without spaces
+            ("2022-01-01T00:00:00+09:00", "3y4w",        "2018-12-01T00:00:00+09:00"),
+            ("2022-01-01T00:00:00+09:00", "1y6mo",       "2020-07-01T00:00:00+09:00"),
+            // or separated by spaces
+            ("2022-01-01T00:00:00+09:00", "1y 6mo",      "2020-07-01T00:00:00+09:00"),
+            ("2022-01-15T00:00:00+09:00", "1 week 1 day", "2022-01-07T00:00:00+09:00"),
+            // and in any order
+            ("2022-01-18T00:00:00+09:00", "3 days 2 weeks", "2022-01-01T00:00:00+09:00"),
+            ("2022-01-01T00:00:00+09:00", "6mo 1y",         "2020-07-01T00:00:00+09:00"),
         ];
 
         for (idx, (now, reltime, want)) in testcases.into_iter().enumerate() {
@@ -172,4 +295,116 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_named_terms() -> Result<(), Box<dyn Error>> {
+        let cases = [
+            ("today", Reltime::checked(0, 0, 0, 0)?),
+            ("yesterday", Reltime::checked(0, 0, 0, 1)?),
+            ("last week", Reltime::checked(0, 0, 1, 0)?),
+            ("last month", Reltime::checked(0, 1, 0, 0)?),
+            ("last year", Reltime::checked(1, 0, 0, 0)?),
+            ("Last  Week", Reltime::checked(0, 0, 1, 0)?),
+            // numeric forms still work
+            ("1w", Reltime::checked(0, 0, 1, 0)?),
+        ];
+
+        for (idx, (term, want)) in cases.into_iter().enumerate() {
+            let got = Reltime::try_from(term)?;
+            assert_eq!(got, want, "#{}: for {:?}", idx, term);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dotted_ago() -> Result<(), Box<dyn Error>> {
+        let cases = [
+            ("2.days.ago", Reltime::checked(0, 0, 0, 2)?),
+            ("1.week.ago", Reltime::checked(0, 0, 1, 0)?),
+            ("3.months.ago", Reltime::checked(0, 3, 0, 0)?),
+            ("1.year.6.months.ago", Reltime::checked(1, 6, 0, 0)?),
+            ("1.5.days.ago", Reltime::try_from("1.5d")?),
+        ];
+        for (idx, (input, want)) in cases.into_iter().enumerate() {
+            let got = Reltime::try_from(input)?;
+            assert_eq!(got, want, "#{}: for {:?}", idx, input);
+        }
+
+        assert!(matches!(
+            Reltime::try_from(".ago"),
+            Err(ReltimeError::ParseError(value)) if value == ".ago"
+        ));
+        assert!(matches!(
+            Reltime::try_from("2.dayz.ago"),
+            Err(ReltimeError::UnexpectedToken { token, value }) if token == "dayz" && value == "2.dayz.ago"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extraneous_characters() {
+        assert!(Reltime::try_from("1d").is_ok());
+        assert!(Reltime::try_from(" 1d ").is_ok());
+
+        for (idx, input) in ["", " "].into_iter().enumerate() {
+            assert!(
+                matches!(Reltime::try_from(input), Err(ReltimeError::ParseError(_))),
+                "#{}: wanted ParseError for {:?}",
+                idx,
+                input
+            );
+        }
+
+        let cases = [
+            ("release-1d", "release-1d"),
+            ("1d extra", "extra"),
+            ("1dx", "dx"),
+            ("x1d", "x1d"),
+            ("d", "d"),
+            ("1", "1"),
+            ("last weekend", "last"),
+            ("2 weeks 3 dayz", "dayz"),
+            ("1d 2d", "2d"),
+            ("1d-2d", "-2d"),
+        ];
+        for (idx, (input, want)) in cases.into_iter().enumerate() {
+            match Reltime::try_from(input) {
+                Err(ReltimeError::UnexpectedToken { token, value }) => {
+                    assert_eq!(token, want, "#{}: for {:?}", idx, input);
+                    assert_eq!(value, input, "#{}", idx);
+                }
+                got => panic!(
+                    "#{}: wanted UnexpectedToken for {:?}, got {:?}",
+                    idx, input, got
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_checked_range_error() {
+        assert!(Reltime::checked(1, 2, 3, 4).is_ok());
+
+        let cases = [
+            (u32::MAX, 0, 0, 0),
+            (u32::MAX / 12, 12, 0, 0),
+            (0, u32::MAX, 4, 0),
+            (0, 0, 3, u32::MAX),
+        ];
+        for (idx, (years, months, weeks, days)) in cases.into_iter().enumerate() {
+            let got = Reltime::checked(years, months, weeks, days);
+            assert!(
+                matches!(got, Err(ReltimeError::RangeError)),
+                "#{}: wanted RangeError",
+                idx
+            );
+        }
+
+        assert!(matches!(
+            Reltime::try_from("4294967295y"),
+            Err(ReltimeError::RangeError)
+        ));
+    }
 }
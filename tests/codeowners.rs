@@ -1,6 +1,7 @@
 mod support;
 
-use git_toolbox::github::codeowners::{CodeOwners, CodeOwnersError};
+use git2::ConfigLevel;
+use git_toolbox::github::codeowners::{CodeOwners, CodeOwnersBuilder, CodeOwnersError};
 use support::{git_add, git_init, mkdir_p, test_logger, write};
 use tempfile::TempDir;
 
@@ -57,3 +58,259 @@ baz/ baz-owner
         Some(&vec![String::from("frontend-developer")])
     );
 }
+
+#[test]
+fn codeowner_find_owners_bulk_is_equivalent_to_find_owners() {
+    let co = CodeOwners::try_from_bufread(
+        "\
+* @everyone
+*.js frontend-developer
+/docs/ docs-owner
+**/logs log-owner
+apps/*.rs
+"
+        .as_bytes(),
+    )
+    .unwrap();
+
+    let paths = [
+        "README.md",
+        "foo.js",
+        "src/foo.js",
+        "docs/index.md",
+        "docs/logs",
+        "a/b/logs/c",
+        "apps/main.rs",
+        "apps/sub/main.rs",
+    ];
+
+    let got = co.find_owners_bulk(paths);
+    let want: Vec<_> = paths.iter().map(|&p| (p, co.find_owners(p))).collect();
+
+    assert_eq!(got, want);
+}
+
+#[test]
+fn codeowner_try_from_repo_uses_only_first_file_found() {
+    let tmpdir = TempDir::new().unwrap();
+    let root = tmpdir.path();
+
+    let repo = git_init(root);
+    mkdir_p(root.join(".github"));
+    write(
+        root.join(".github/CODEOWNERS"),
+        "*.js frontend-developer\n".as_bytes(),
+    );
+    write(
+        root.join("CODEOWNERS"),
+        "*.js root-owner\n*.rs rust-developer\n".as_bytes(),
+    );
+    git_add(&repo, ".github/CODEOWNERS");
+    git_add(&repo, "CODEOWNERS");
+
+    let co = CodeOwners::try_from_repo(&repo).unwrap();
+    assert_eq!(
+        co.find_owners("foo.js"),
+        Some(&vec![String::from("frontend-developer")])
+    );
+    assert_eq!(co.find_owners("foo.rs"), None);
+}
+
+#[test]
+fn codeowner_try_from_repo_falls_back_to_root_file() {
+    let tmpdir = TempDir::new().unwrap();
+    let root = tmpdir.path();
+
+    let repo = git_init(root);
+    write(root.join("CODEOWNERS"), "*.rs rust-developer\n".as_bytes());
+    git_add(&repo, "CODEOWNERS");
+
+    let co = CodeOwners::try_from_repo(&repo).unwrap();
+    assert_eq!(
+        co.find_owners("foo.rs"),
+        Some(&vec![String::from("rust-developer")])
+    );
+}
+
+#[test]
+fn codeowner_try_from_repo_merged_applies_all_files_in_priority_order() {
+    let tmpdir = TempDir::new().unwrap();
+    let root = tmpdir.path();
+
+    let repo = git_init(root);
+    mkdir_p(root.join(".github"));
+    mkdir_p(root.join("docs"));
+    write(
+        root.join(".github/CODEOWNERS"),
+        "*.js frontend-developer\n".as_bytes(),
+    );
+    write(
+        root.join("CODEOWNERS"),
+        "*.js root-owner\n*.rs rust-developer\n".as_bytes(),
+    );
+    write(
+        root.join("docs/CODEOWNERS"),
+        "*.rs docs-owner\n*.md technical-writer\n".as_bytes(),
+    );
+    git_add(&repo, ".github/CODEOWNERS");
+    git_add(&repo, "CODEOWNERS");
+    git_add(&repo, "docs/CODEOWNERS");
+
+    let co = CodeOwners::try_from_repo_merged(&repo).unwrap();
+    assert_eq!(
+        co.find_owners("foo.js"),
+        Some(&vec![String::from("frontend-developer")])
+    );
+    assert_eq!(
+        co.find_owners("foo.rs"),
+        Some(&vec![String::from("rust-developer")])
+    );
+    assert_eq!(
+        co.find_owners("foo.md"),
+        Some(&vec![String::from("technical-writer")])
+    );
+    assert_eq!(
+        co.find_match("foo.js").and_then(|m| m.source()),
+        Some(".github/CODEOWNERS")
+    );
+    assert_eq!(
+        co.find_match("foo.md").and_then(|m| m.source()),
+        Some("docs/CODEOWNERS")
+    );
+}
+
+#[test]
+fn codeowner_try_from_repo_searches_paths_in_config_first() {
+    let tmpdir = TempDir::new().unwrap();
+    let root = tmpdir.path();
+
+    let repo = git_init(root);
+    {
+        let mut config = repo
+            .config()
+            .unwrap()
+            .open_level(ConfigLevel::Local)
+            .unwrap();
+        config
+            .set_multivar("whose.codeownerspath", "^$", "owners/CODEOWNERS")
+            .unwrap();
+        config
+            .set_multivar("whose.codeownerspath", "^$", "meta/CODEOWNERS")
+            .unwrap();
+    }
+    mkdir_p(root.join("meta"));
+    write(
+        root.join("meta/CODEOWNERS"),
+        "*.rs rust-developer\n".as_bytes(),
+    );
+    git_add(&repo, "meta/CODEOWNERS");
+
+    let co = CodeOwners::try_from_repo(&repo).unwrap();
+    assert_eq!(
+        co.find_owners("foo.rs"),
+        Some(&vec![String::from("rust-developer")])
+    );
+}
+
+#[test]
+fn codeowner_builder_reads_working_tree_of_given_locations() {
+    let tmpdir = TempDir::new().unwrap();
+    let root = tmpdir.path();
+
+    let repo = git_init(root);
+    mkdir_p(root.join(".github"));
+    write(
+        root.join(".github/CODEOWNERS"),
+        "*.rs github-owner\n".as_bytes(),
+    );
+    mkdir_p(root.join("meta"));
+    write(
+        root.join("meta/CODEOWNERS"),
+        "*.rs meta-owner\n*.md writer\n".as_bytes(),
+    );
+
+    // not staged yet
+    assert!(matches!(
+        CodeOwnersBuilder::new().from_repo(&repo),
+        Err(CodeOwnersError::NotIndexed)
+    ));
+
+    let co = CodeOwnersBuilder::new()
+        .with_locations(Some(vec![
+            String::from("meta/CODEOWNERS"),
+            String::from(".github/CODEOWNERS"),
+        ]))
+        .with_merge(true)
+        .from_workdir(&repo)
+        .unwrap();
+    assert_eq!(
+        co.find_owners("foo.rs"),
+        Some(&vec![String::from("meta-owner")])
+    );
+    assert_eq!(
+        co.find_match("foo.md").unwrap().source(),
+        Some("meta/CODEOWNERS")
+    );
+
+    assert!(matches!(
+        CodeOwnersBuilder::new()
+            .with_locations(Some(vec![String::from("docs/CODEOWNERS")]))
+            .from_workdir(&repo),
+        Err(CodeOwnersError::NotFound)
+    ));
+}
+
+#[test]
+fn codeowner_builder_refuses_large_or_malformed_file() {
+    let tmpdir = TempDir::new().unwrap();
+    let root = tmpdir.path();
+
+    let repo = git_init(root);
+    write(root.join("CODEOWNERS"), b"* @org/everyone\n\xff x\n");
+    git_add(&repo, "CODEOWNERS");
+
+    // line not in UTF-8 is skipped by default
+    let co = CodeOwnersBuilder::new().from_repo(&repo).unwrap();
+    assert_eq!(co.entries().count(), 1);
+
+    match CodeOwnersBuilder::new().with_strict(true).from_repo(&repo) {
+        Err(CodeOwnersError::Malformed { path, error }) => {
+            assert_eq!(path, "CODEOWNERS");
+            assert_eq!(error.line, 2);
+        }
+        r => panic!("unexpected result: {:?}", r),
+    }
+
+    assert!(matches!(
+        CodeOwnersBuilder::new()
+            .with_size_limit(Some(16))
+            .from_repo(&repo),
+        Err(CodeOwnersError::TooLarge { limit: 16, .. })
+    ));
+    assert!(CodeOwnersBuilder::new()
+        .with_size_limit(Some(21))
+        .from_repo(&repo)
+        .is_ok());
+}
+
+#[test]
+fn codeowner_find_match_tells_default_owner() {
+    let co = CodeOwners::try_from_bufread(
+        "\
+* @org/everyone
+*.js frontend-developer
+"
+        .as_bytes(),
+    )
+    .unwrap();
+
+    let m = co.find_match("README.md").unwrap();
+    assert!(m.is_default());
+    assert_eq!(m.pattern(), "*");
+    assert_eq!(m.owners(), &vec![String::from("@org/everyone")]);
+
+    let m = co.find_match("src/foo.js").unwrap();
+    assert!(!m.is_default());
+    assert_eq!(m.pattern(), "*.js");
+    assert_eq!(m.owners(), &vec![String::from("frontend-developer")]);
+}
@@ -0,0 +1,246 @@
+use std::{
+    cell::RefCell,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use git2::Status;
+use git_toolbox::{
+    app::dah::{run, run_interruptible, run_with_interval, Action, Collector, Dispatcher},
+    git::{HeadRef, RemoteRef},
+};
+
+/// Working copy simulated in memory, driven by a custom dispatcher
+/// instead of the git command line.
+struct Simulated {
+    head_ref: RefCell<HeadRef>,
+    status: RefCell<Status>,
+    // whether staging changes the index, or leaves it as is.
+    stages: bool,
+    pushed: RefCell<bool>,
+    calls: RefCell<Vec<String>>,
+}
+
+impl Simulated {
+    fn new(head_ref: &str, status: Status) -> Self {
+        Self {
+            head_ref: RefCell::new(HeadRef::new(head_ref).unwrap()),
+            status: RefCell::new(status),
+            stages: true,
+            pushed: RefCell::new(false),
+            calls: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn record(&self, call: &str) {
+        self.calls.borrow_mut().push(call.to_owned());
+    }
+}
+
+impl Collector for Simulated {
+    type Error = std::convert::Infallible;
+
+    fn default_branch(&self) -> Result<Option<String>, Self::Error> {
+        Ok(Some("main".to_owned()))
+    }
+
+    fn is_head_protected(&self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    fn head_ref(&self) -> Result<HeadRef, Self::Error> {
+        Ok(self.head_ref.borrow().clone())
+    }
+
+    fn upstream_ref(&self) -> Result<Option<RemoteRef>, Self::Error> {
+        if *self.pushed.borrow() {
+            let head_ref = self.head_ref.borrow();
+            let branch = head_ref.branch().unwrap();
+            Ok(Some(
+                RemoteRef::new(format!("refs/remotes/origin/{}", branch)).unwrap(),
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn is_synchronized(&self) -> Result<bool, Self::Error> {
+        Ok(*self.pushed.borrow())
+    }
+
+    fn ahead_behind(&self) -> Result<Option<(usize, usize)>, Self::Error> {
+        Ok(None)
+    }
+
+    fn is_based_on_remote(&self) -> Result<bool, Self::Error> {
+        Ok(*self.pushed.borrow())
+    }
+
+    fn has_foreign_upstream_commits(&self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    fn status(&self) -> Result<Status, Self::Error> {
+        Ok(*self.status.borrow())
+    }
+
+    // others are left to the defaults, as implementors outside the crate would.
+}
+
+impl Dispatcher for Simulated {
+    type Error = std::convert::Infallible;
+
+    fn status(&self) -> Result<(), Self::Error> {
+        self.record("status");
+        Ok(())
+    }
+
+    fn create_branch_and_switch(&self) -> Result<(), Self::Error> {
+        self.record("create_branch_and_switch");
+        *self.head_ref.borrow_mut() = HeadRef::new("refs/heads/topic").unwrap();
+        Ok(())
+    }
+
+    fn rename_branch_and_switch(&self) -> Result<(), Self::Error> {
+        self.record("rename_branch_and_switch");
+        *self.head_ref.borrow_mut() = HeadRef::new("refs/heads/topic").unwrap();
+        Ok(())
+    }
+
+    fn stage_changes(&self) -> Result<(), Self::Error> {
+        self.record("stage_changes");
+        if self.stages {
+            *self.status.borrow_mut() = Status::INDEX_MODIFIED;
+        }
+        Ok(())
+    }
+
+    fn commit(&self) -> Result<(), Self::Error> {
+        self.record("commit");
+        *self.status.borrow_mut() = Status::CURRENT;
+        Ok(())
+    }
+
+    fn pull_with_rebase(&self, upstream_ref: &str) -> Result<(), Self::Error> {
+        self.record(&format!("pull_with_rebase {}", upstream_ref));
+        Ok(())
+    }
+
+    fn push(&self, head_ref: &str, upstream_ref: Option<&str>) -> Result<(), Self::Error> {
+        self.record(&format!("push {} {:?}", head_ref, upstream_ref));
+        *self.pushed.borrow_mut() = true;
+        Ok(())
+    }
+}
+
+#[test]
+fn dah_runs_with_custom_dispatcher() {
+    let sim = Simulated::new("refs/heads/main", Status::WT_MODIFIED);
+
+    run::<_, _, std::convert::Infallible>(&sim, &sim, false).unwrap();
+    assert_eq!(
+        *sim.calls.borrow(),
+        vec![
+            "stage_changes",
+            "commit",
+            "rename_branch_and_switch",
+            "push refs/heads/topic None",
+        ]
+    );
+
+    // nothing to do any more
+    assert_eq!(Action::new(&sim), Ok(Action::None));
+}
+
+#[test]
+fn dah_runs_one_step_with_custom_dispatcher() {
+    let sim = Simulated::new("refs/heads/main", Status::WT_MODIFIED);
+
+    run::<_, _, std::convert::Infallible>(&sim, &sim, true).unwrap();
+    assert_eq!(*sim.calls.borrow(), vec!["stage_changes"]);
+    assert_eq!(Action::new(&sim), Ok(Action::Commit));
+}
+
+#[test]
+fn dah_stops_when_staging_changes_nothing() {
+    let sim = Simulated {
+        stages: false,
+        ..Simulated::new("refs/heads/topic", Status::WT_MODIFIED)
+    };
+
+    run::<_, _, std::convert::Infallible>(&sim, &sim, false).unwrap();
+    assert_eq!(*sim.calls.borrow(), vec!["stage_changes", "status"]);
+}
+
+#[test]
+fn dah_sleeps_between_actions() {
+    let sim = Simulated::new("refs/heads/main", Status::WT_MODIFIED);
+
+    run_with_interval::<_, _, std::convert::Infallible, _>(
+        &sim,
+        &sim,
+        false,
+        Duration::from_millis(10),
+        |d| sim.record(&format!("sleep {:?}", d)),
+    )
+    .unwrap();
+    assert_eq!(
+        *sim.calls.borrow(),
+        vec![
+            "stage_changes",
+            "sleep 10ms",
+            "commit",
+            "sleep 10ms",
+            "rename_branch_and_switch",
+            "sleep 10ms",
+            "push refs/heads/topic None",
+        ]
+    );
+
+    // never sleeps without interval
+    let sim = Simulated::new("refs/heads/main", Status::WT_MODIFIED);
+    run_with_interval::<_, _, std::convert::Infallible, _>(
+        &sim,
+        &sim,
+        false,
+        Duration::ZERO,
+        |_| panic!("slept"),
+    )
+    .unwrap();
+}
+
+#[test]
+fn dah_stops_between_actions_when_interrupted() {
+    let sim = Simulated::new("refs/heads/main", Status::WT_MODIFIED);
+    let interrupted = AtomicBool::new(false);
+
+    // runs through unless interrupted
+    run_interruptible::<_, _, std::convert::Infallible, _>(
+        &sim,
+        &sim,
+        false,
+        Duration::ZERO,
+        |_| {},
+        &interrupted,
+    )
+    .unwrap();
+    assert_eq!(sim.calls.borrow().len(), 4);
+
+    // interrupted while committing, like Ctrl-C hit in the editor
+    let sim = Simulated::new("refs/heads/main", Status::WT_MODIFIED);
+    run_interruptible::<_, _, std::convert::Infallible, _>(
+        &sim,
+        &sim,
+        false,
+        Duration::from_millis(1),
+        |_| {
+            if sim.calls.borrow().last().map(|c| c.as_str()) == Some("commit") {
+                interrupted.store(true, Ordering::Relaxed);
+            }
+        },
+        &interrupted,
+    )
+    .unwrap();
+    assert_eq!(*sim.calls.borrow(), vec!["stage_changes", "commit"]);
+    assert_eq!(Action::new(&sim), Ok(Action::RenameBranch));
+}